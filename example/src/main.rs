@@ -55,8 +55,11 @@ struct User {
 struct Post {
     #[primary_key]
     id: i32,
+    #[searchable]
     title: String,
+    #[searchable]
     subtitle: Option<String>,
+    #[searchable]
     content: String,
     publish_date: rp1::datetime::Date,
     publish_time: rp1::datetime::Time,
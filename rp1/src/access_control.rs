@@ -2,9 +2,10 @@
 
 use diesel::{
     backend::Backend, query_builder::BoxedSelectStatement, sql_types::Bool, BoxableExpression,
+    Connection,
 };
 
-use crate::{CrudInsertable, CrudStruct, CrudUpdatable};
+use crate::{CrudInsertable, CrudResult, CrudStruct, CrudUpdatable};
 
 /// A filter for when a list of items that will be queried needs to be filtered.
 pub enum PermissionFilter<QS, DB>
@@ -81,4 +82,116 @@ where
     fn allow_delete(&self, _: &Self::AuthUser) -> bool {
         true
     }
+
+    /// This function should return a boolean indicating if the user has access
+    /// to soft deleted items, using `with_deleted=true` in the read and list
+    /// routes, and to the history of deleted items. By default this is not
+    /// allowed, so deleted items stay hidden until access is granted
+    /// explicitly.
+    fn allow_read_deleted(_: &Self::AuthUser) -> bool {
+        false
+    }
+
+    /// This function should return a boolean indicating if the user has access
+    /// to restore the soft deleted item (`self`). By default all restores are
+    /// allowed.
+    fn allow_restore(&self, _: &Self::AuthUser) -> bool {
+        true
+    }
+
+    /// This function should return a boolean indicating if the user has access
+    /// to link items to, or unlink items from, the existing item (`self`)
+    /// using the many-to-many relation with the given name. It is also called
+    /// on the items being linked or unlinked, with the name of the relation
+    /// declared on the other struct. By default all changes are allowed.
+    fn allow_link(&self, _relation: &str, _: &Self::AuthUser) -> bool {
+        true
+    }
+
+    /// This function should return a boolean indicating if the user has access
+    /// to the audit entries of the existing item (`self`) using the history
+    /// route. By default this is allowed if the item can be read. The entries
+    /// of a deleted item are checked by `allow_read_deleted` instead.
+    fn allow_read_history(&self, user: &Self::AuthUser) -> bool {
+        self.allow_read(user)
+    }
+
+    /// The id of the user that is recorded as the actor of the changes in the
+    /// audit table. By default no actor is recorded.
+    fn actor_id(_: &Self::AuthUser) -> Option<String> {
+        None
+    }
+}
+
+/// Trait that needs to be implemented when the `hooks` property is enabled.
+/// The hooks are called by the create, update and delete routes, using the
+/// connection of the transaction in which the item is changed. Returning an
+/// error from a hook rolls back the transaction and is used as the response.
+pub trait CrudHooks
+where
+    Self: CrudUpdatable + CrudInsertable + CrudStruct + Sized,
+{
+    /// The database backend of the connection given to the hooks.
+    type Backend: Backend;
+
+    /// Called before the new item is validated and inserted, after the
+    /// permission checks. The permissions are checked again for the item as
+    /// changed by the hook.
+    fn before_create<C>(
+        _new: &mut <Self as CrudInsertable>::InsertType,
+        _conn: &C,
+    ) -> CrudResult<()>
+    where
+        C: Connection<Backend = Self::Backend>,
+    {
+        Ok(())
+    }
+
+    /// Called after the new item (`self`) is inserted.
+    fn after_create<C>(&self, _conn: &C) -> CrudResult<()>
+    where
+        C: Connection<Backend = Self::Backend>,
+    {
+        Ok(())
+    }
+
+    /// Called before the existing item (`self`) is updated to the new item,
+    /// after the permission checks and before validation. Partial updates are
+    /// given the complete new item as well. The permissions are checked again
+    /// for the item as changed by the hook.
+    fn before_update<C>(
+        &self,
+        _new: &mut <Self as CrudUpdatable>::PutType,
+        _conn: &C,
+    ) -> CrudResult<()>
+    where
+        C: Connection<Backend = Self::Backend>,
+    {
+        Ok(())
+    }
+
+    /// Called after the item is updated, with the updated item (`self`).
+    fn after_update<C>(&self, _conn: &C) -> CrudResult<()>
+    where
+        C: Connection<Backend = Self::Backend>,
+    {
+        Ok(())
+    }
+
+    /// Called before the existing item (`self`) is deleted, after the
+    /// permission checks.
+    fn before_delete<C>(&self, _conn: &C) -> CrudResult<()>
+    where
+        C: Connection<Backend = Self::Backend>,
+    {
+        Ok(())
+    }
+
+    /// Called after the item (`self`) is deleted.
+    fn after_delete<C>(&self, _conn: &C) -> CrudResult<()>
+    where
+        C: Connection<Backend = Self::Backend>,
+    {
+        Ok(())
+    }
 }
@@ -0,0 +1,143 @@
+//! Audit trail of the changes made by the generated routes.
+//!
+//! When the `audit` property is set, every create, update and delete writes
+//! an entry to the audit table, in the same transaction as the change. The
+//! entries of a single item are returned by its `history` route.
+
+use diesel::{backend::Backend, Queryable};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::datetime;
+
+/// The kind of change recorded by an audit entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+impl AuditAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+            AuditAction::Restore => "restore",
+        }
+    }
+}
+
+/// A change of a single item, as it is inserted in the audit table.
+pub struct AuditRecord {
+    /// The primary key of the item as JSON, see [audit_record_id].
+    pub record_id: String,
+    pub action: &'static str,
+    pub created_at: datetime::OffsetDateTime,
+    /// The changed fields before the change as a JSON object, `None` for a
+    /// created item.
+    pub before: Option<String>,
+    /// The changed fields after the change as a JSON object, `None` for a
+    /// deleted item.
+    pub after: Option<String>,
+}
+
+impl AuditRecord {
+    /// Records the change of the item with the primary key `key` from
+    /// `before` to `after`. Only the fields that differ are recorded when both
+    /// are given, otherwise all fields of the item are recorded.
+    pub fn new<K, T>(action: AuditAction, key: &K, before: Option<&T>, after: Option<&T>) -> Self
+    where
+        K: Serialize,
+        T: Serialize,
+    {
+        let mut before = before.map(to_object);
+        let mut after = after.map(to_object);
+        if let (Some(before), Some(after)) = (&mut before, &mut after) {
+            let unchanged = before
+                .iter()
+                .filter(|(field, value)| after.get(*field) == Some(value))
+                .map(|(field, _)| field.clone())
+                .collect::<Vec<_>>();
+            for field in unchanged {
+                before.remove(&field);
+                after.remove(&field);
+            }
+        }
+
+        AuditRecord {
+            record_id: audit_record_id(key),
+            action: action.as_str(),
+            created_at: time::OffsetDateTime::now_utc().into(),
+            before: before.map(|b| Value::Object(b).to_string()),
+            after: after.map(|a| Value::Object(a).to_string()),
+        }
+    }
+}
+
+/// The primary key of an item as it is stored in the audit table, i.e. the
+/// key serialized as JSON. Composite keys are stored as an array.
+pub fn audit_record_id<K: Serialize>(key: &K) -> String {
+    serde_json::to_string(key).unwrap_or_default()
+}
+
+fn to_object<T: Serialize>(item: &T) -> Map<String, Value> {
+    match serde_json::to_value(item) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    }
+}
+
+/// An entry of the audit table, as returned by the history route. The `id` is
+/// a `BigInt`, which requires a `bigint` or `bigserial` column on Postgres.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub resource: String,
+    pub record_id: String,
+    pub action: String,
+    pub actor: Option<String>,
+    pub created_at: datetime::OffsetDateTime,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+type AuditRow = (
+    i64,
+    String,
+    String,
+    String,
+    Option<String>,
+    datetime::OffsetDateTime,
+    Option<String>,
+    Option<String>,
+);
+
+/// The `before` and `after` columns contain JSON, which is stored as text
+/// to support all backends.
+impl<ST, DB> Queryable<ST, DB> for AuditEntry
+where
+    DB: Backend,
+    AuditRow: Queryable<ST, DB>,
+{
+    type Row = <AuditRow as Queryable<ST, DB>>::Row;
+
+    fn build(row: Self::Row) -> Self {
+        let (id, resource, record_id, action, actor, created_at, before, after) =
+            AuditRow::build(row);
+        let parse = |json: String| serde_json::from_str(&json).unwrap_or(Value::String(json));
+
+        AuditEntry {
+            id,
+            resource,
+            record_id,
+            action,
+            actor,
+            created_at,
+            before: before.map(parse),
+            after: after.map(parse),
+        }
+    }
+}
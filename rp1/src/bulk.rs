@@ -0,0 +1,92 @@
+//! Creating multiple items in a single request.
+//!
+//! The JSON create endpoint accepts either a single item or an array of items.
+//! All items of an array are checked before any of them is inserted, and the
+//! errors of all invalid items are reported at once, together with their index
+//! in the array.
+
+use rocket::serde::json::{Error as JsonError, Json};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{CrudError, CrudResult};
+
+/// The JSON body of a create route, where a body that cannot be parsed
+/// results in an invalid item error.
+pub fn json_body<T>(body: Result<Json<T>, JsonError<'_>>) -> CrudResult<T> {
+    match body {
+        Ok(body) => Ok(body.into_inner()),
+        Err(JsonError::Parse(_, e)) => Err(CrudError::InvalidItem(e.to_string())),
+        Err(JsonError::Io(e)) => Err(CrudError::InvalidItem(e.to_string())),
+    }
+}
+
+/// Either a single item or an array of items, depending on the JSON value it
+/// is parsed from. It is serialized the same way.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T: DeserializeOwned> OneOrMany<T> {
+    /// Parse a single item from an object, or all items of an array. If any
+    /// of the items in an array is invalid, the errors of all invalid items
+    /// are returned.
+    pub fn from_value(value: Value) -> Result<Self, CrudError> {
+        let values = match value {
+            Value::Array(values) => values,
+            value => {
+                return serde_json::from_value(value)
+                    .map(OneOrMany::One)
+                    .map_err(|e| CrudError::InvalidItem(e.to_string()));
+            }
+        };
+
+        let mut items = Vec::with_capacity(values.len());
+        let mut errors = vec![];
+        for (index, value) in values.into_iter().enumerate() {
+            match serde_json::from_value(value) {
+                Ok(item) => items.push(item),
+                Err(e) => errors.push(ItemError {
+                    index,
+                    error: CrudError::InvalidItem(e.to_string()),
+                }),
+            }
+        }
+        if errors.is_empty() {
+            Ok(OneOrMany::Many(items))
+        } else {
+            Err(CrudError::ItemErrors(errors))
+        }
+    }
+}
+
+/// The error of a single item in an array of items.
+#[derive(Debug)]
+pub struct ItemError {
+    /// The index of the item in the array.
+    pub index: usize,
+    pub error: CrudError,
+}
+
+/// Check each of the items, returning the errors of all items for which the
+/// check failed.
+pub fn check_items<T, F>(items: &[T], mut check: F) -> Result<(), CrudError>
+where
+    F: FnMut(&T) -> Result<(), CrudError>,
+{
+    let errors = items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| check(item).err().map(|error| ItemError { index, error }))
+        .collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(CrudError::ItemErrors(errors))
+    }
+}
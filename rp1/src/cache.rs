@@ -0,0 +1,160 @@
+//! Conditional requests for the read and list routes.
+//!
+//! Responses of these routes contain an `ETag`, and a `Last-Modified` header
+//! if the struct has a field marked as `#[last_modified]`. A client that
+//! sends these values back in the `If-None-Match` or `If-Modified-Since`
+//! headers gets an empty `304 Not Modified` response if nothing changed.
+
+use std::hash::Hasher;
+
+use fnv::FnvHasher;
+use rocket::http::{Method, Status};
+use rocket::request::Request;
+use rocket::response::{self, Responder};
+use rocket::Response;
+use serde::Serialize;
+use time::format_description::FormatItem;
+use time::macros::format_description;
+
+use crate::datetime;
+
+/// The format of dates in HTTP headers, which are always in GMT.
+const HTTP_DATE_FORMAT: &[FormatItem<'_>] = format_description!(
+    "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT"
+);
+
+/// The entity tag of a response body, which is a hash of its JSON
+/// representation. The FNV-1a hash is used as it does not depend on the Rust
+/// version or the running instance, so the tags stay valid across deploys and
+/// are the same on every instance.
+pub fn content_etag<T: Serialize>(content: &T) -> String {
+    let mut hasher = FnvHasher::default();
+    // a value that cannot be serialized cannot be responded with either
+    hasher.write(&serde_json::to_vec(content).unwrap_or_default());
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+/// Values that can be used as the `Last-Modified` time of an item. Times
+/// without an offset are assumed to be in UTC.
+pub trait LastModified {
+    fn last_modified(&self) -> Option<time::OffsetDateTime>;
+}
+
+impl LastModified for time::OffsetDateTime {
+    fn last_modified(&self) -> Option<time::OffsetDateTime> {
+        Some(*self)
+    }
+}
+
+impl LastModified for time::PrimitiveDateTime {
+    fn last_modified(&self) -> Option<time::OffsetDateTime> {
+        Some(self.assume_utc())
+    }
+}
+
+impl LastModified for datetime::OffsetDateTime {
+    fn last_modified(&self) -> Option<time::OffsetDateTime> {
+        Some(**self)
+    }
+}
+
+impl LastModified for datetime::PrimitiveDateTime {
+    fn last_modified(&self) -> Option<time::OffsetDateTime> {
+        Some(self.assume_utc())
+    }
+}
+
+impl<T: LastModified> LastModified for Option<T> {
+    fn last_modified(&self) -> Option<time::OffsetDateTime> {
+        self.as_ref().and_then(LastModified::last_modified)
+    }
+}
+
+/// Wraps the response of a read or list route to add the `ETag`,
+/// `Last-Modified` and `Cache-Control` headers. For `GET` requests with
+/// matching conditional headers the response is replaced by
+/// `304 Not Modified`.
+#[derive(Debug)]
+pub struct Cacheable<R> {
+    pub inner: R,
+    pub etag: String,
+    pub last_modified: Option<time::OffsetDateTime>,
+    pub cache_control: Option<&'static str>,
+}
+
+impl<R> Cacheable<R> {
+    pub fn new(inner: R, etag: String) -> Self {
+        Cacheable {
+            inner,
+            etag,
+            last_modified: None,
+            cache_control: None,
+        }
+    }
+
+    pub fn last_modified(mut self, last_modified: Option<time::OffsetDateTime>) -> Self {
+        self.last_modified = last_modified;
+        self
+    }
+
+    pub fn cache_control(mut self, cache_control: Option<&'static str>) -> Self {
+        self.cache_control = cache_control;
+        self
+    }
+
+    /// Whether the client already has this response, according to the
+    /// conditional headers of the request. `If-Modified-Since` is ignored if
+    /// `If-None-Match` is given.
+    fn is_not_modified(&self, req: &Request<'_>) -> bool {
+        if req.method() != Method::Get && req.method() != Method::Head {
+            return false;
+        }
+
+        // the weak comparison is used, ignoring the `W/` prefix of the tags
+        let etag = self.etag.trim_start_matches("W/");
+        let mut if_none_match = req
+            .headers()
+            .get("If-None-Match")
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim())
+            .filter(|tag| !tag.is_empty())
+            .peekable();
+        if if_none_match.peek().is_some() {
+            return if_none_match.any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag);
+        }
+
+        let since = req
+            .headers()
+            .get_one("If-Modified-Since")
+            .and_then(|since| time::PrimitiveDateTime::parse(since, HTTP_DATE_FORMAT).ok());
+        match (self.last_modified, since) {
+            // HTTP dates have a precision of seconds
+            (Some(last_modified), Some(since)) => {
+                last_modified.unix_timestamp() <= since.assume_utc().unix_timestamp()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for Cacheable<R> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = if self.is_not_modified(req) {
+            Response::build().status(Status::NotModified).finalize()
+        } else {
+            self.inner.respond_to(req)?
+        };
+        response.set_raw_header("ETag", self.etag);
+        if let Some(last_modified) = self.last_modified {
+            let utc = last_modified.to_offset(time::UtcOffset::UTC);
+            if let Ok(date) = utc.format(HTTP_DATE_FORMAT) {
+                response.set_raw_header("Last-Modified", date);
+            }
+        }
+        if let Some(cache_control) = self.cache_control {
+            response.set_raw_header("Cache-Control", cache_control);
+        }
+
+        Ok(response)
+    }
+}
@@ -1,13 +1,20 @@
+#[cfg(feature = "postgres")]
 use std::convert::TryFrom;
 use std::io::Write;
 use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 
+#[cfg(feature = "postgres")]
 use diesel::data_types::PgDate;
 use diesel::deserialize::FromSql;
+#[cfg(feature = "mysql")]
+use diesel::mysql::Mysql;
+#[cfg(feature = "postgres")]
 use diesel::pg::Pg;
 use diesel::serialize::{Output, ToSql};
 use diesel::sql_types;
+#[cfg(feature = "sqlite")]
+use diesel::sqlite::Sqlite;
 use rocket::form::{FromFormField, ValueField};
 use serde::de::Visitor;
 use serde::{Deserialize, Deserializer};
@@ -46,14 +53,16 @@ impl AsMut<time::Date> for Date {
     }
 }
 
+#[cfg(feature = "postgres")]
 impl ToSql<sql_types::Date, Pg> for Date {
     fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> diesel::serialize::Result {
-        let difference = (self.0 - time::macros::date!(2001 - 01 - 01)).whole_days();
+        let difference = (self.0 - time::macros::date!(2000 - 01 - 01)).whole_days();
         let postgres_diff = PgDate(i32::try_from(difference)?);
         ToSql::<sql_types::Date, Pg>::to_sql(&postgres_diff, out)
     }
 }
 
+#[cfg(feature = "postgres")]
 impl FromSql<sql_types::Date, Pg> for Date {
     fn from_sql(bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
         let pg_date: PgDate = FromSql::<sql_types::Date, Pg>::from_sql(bytes)?;
@@ -62,6 +71,40 @@ impl FromSql<sql_types::Date, Pg> for Date {
     }
 }
 
+#[cfg(feature = "sqlite")]
+impl ToSql<sql_types::Date, Sqlite> for Date {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> diesel::serialize::Result {
+        let text = self.0.format(super::sqlite::DATE_FORMAT)?;
+        ToSql::<sql_types::Text, Sqlite>::to_sql(&text, out)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl FromSql<sql_types::Date, Sqlite> for Date {
+    fn from_sql(
+        value: Option<&<Sqlite as diesel::backend::Backend>::RawValue>,
+    ) -> diesel::deserialize::Result<Self> {
+        let text: String = FromSql::<sql_types::Text, Sqlite>::from_sql(value)?;
+        Ok(Date(super::sqlite::parse_date(&text)?))
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl ToSql<sql_types::Date, Mysql> for Date {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Mysql>) -> diesel::serialize::Result {
+        let mysql_time = super::mysql::to_mysql_time(Some(self.0), None);
+        super::mysql::write_mysql_time(&mysql_time, out)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl FromSql<sql_types::Date, Mysql> for Date {
+    fn from_sql(bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
+        let mysql_time = super::mysql::read_mysql_time(bytes)?;
+        Ok(Date(super::mysql::date_from_mysql_time(&mysql_time)?))
+    }
+}
+
 impl<'v> FromFormField<'v> for Date {
     fn from_value(field: ValueField<'v>) -> rocket::form::Result<'v, Self> {
         let dt = Self::from_str(field.value)
@@ -2,8 +2,12 @@
 //! contexts.
 
 mod date;
+#[cfg(feature = "mysql")]
+mod mysql;
 mod offset_date_time;
 mod primitive_date_time;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 mod time;
 
 pub use self::date::Date;
@@ -0,0 +1,101 @@
+//! MySQL transfers date and time values as the `MYSQL_TIME` struct of the
+//! mysql client library. Diesel only implements the conversion of that struct
+//! together with chrono, so these helpers read and write its bytes directly,
+//! with the same layout diesel uses.
+
+use std::convert::TryFrom;
+use std::io::Write;
+use std::os::raw::{c_int, c_uint, c_ulong};
+use std::{mem, ptr, slice};
+
+use diesel::mysql::Mysql;
+use diesel::serialize::{IsNull, Output};
+
+const MYSQL_TIMESTAMP_DATE: c_int = 0;
+const MYSQL_TIMESTAMP_DATETIME: c_int = 1;
+const MYSQL_TIMESTAMP_TIME: c_int = 2;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(super) struct MysqlTime {
+    year: c_uint,
+    month: c_uint,
+    day: c_uint,
+    hour: c_uint,
+    minute: c_uint,
+    second: c_uint,
+    second_part: c_ulong,
+    neg: bool,
+    time_type: c_int,
+    time_zone_displacement: c_int,
+}
+
+pub(super) fn to_mysql_time(date: Option<time::Date>, time: Option<time::Time>) -> MysqlTime {
+    let time_type = match (date, time) {
+        (Some(_), Some(_)) => MYSQL_TIMESTAMP_DATETIME,
+        (Some(_), None) => MYSQL_TIMESTAMP_DATE,
+        (None, _) => MYSQL_TIMESTAMP_TIME,
+    };
+    let (year, month, day) =
+        date.map(|d| d.to_calendar_date())
+            .unwrap_or((0, time::Month::January, 0));
+    let (hour, minute, second, micro) = time.map(|t| t.as_hms_micro()).unwrap_or((0, 0, 0, 0));
+
+    MysqlTime {
+        year: year as _,
+        month: if date.is_some() { month as _ } else { 0 },
+        day: day as _,
+        hour: hour as _,
+        minute: minute as _,
+        second: second as _,
+        second_part: micro as _,
+        neg: false,
+        time_type,
+        time_zone_displacement: 0,
+    }
+}
+
+pub(super) fn write_mysql_time<W: Write>(
+    value: &MysqlTime,
+    out: &mut Output<W, Mysql>,
+) -> diesel::serialize::Result {
+    let bytes = unsafe {
+        slice::from_raw_parts(
+            value as *const MysqlTime as *const u8,
+            mem::size_of::<MysqlTime>(),
+        )
+    };
+    out.write_all(bytes)?;
+    Ok(IsNull::No)
+}
+
+pub(super) fn read_mysql_time(bytes: Option<&[u8]>) -> diesel::deserialize::Result<MysqlTime> {
+    let bytes = bytes.ok_or("Unexpected null for non-null column")?;
+    if bytes.len() < mem::size_of::<MysqlTime>() {
+        return Err("Invalid MYSQL_TIME value".into());
+    }
+    // the bytes are not necessarily aligned
+    let value = unsafe { ptr::read_unaligned(bytes.as_ptr() as *const MysqlTime) };
+    if value.neg {
+        return Err("Negative times are not supported".into());
+    }
+    Ok(value)
+}
+
+pub(super) fn date_from_mysql_time(value: &MysqlTime) -> diesel::deserialize::Result<time::Date> {
+    let month = time::Month::try_from(value.month as u8)?;
+    Ok(time::Date::from_calendar_date(
+        value.year as i32,
+        month,
+        value.day as u8,
+    )?)
+}
+
+pub(super) fn time_from_mysql_time(value: &MysqlTime) -> diesel::deserialize::Result<time::Time> {
+    Ok(time::Time::from_hms_micro(
+        value.hour as u8,
+        value.minute as u8,
+        value.second as u8,
+        value.second_part as u32,
+    )?)
+}
@@ -1,18 +1,26 @@
+#[cfg(feature = "postgres")]
 use std::convert::TryFrom;
 use std::io::Write;
 use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 
+#[cfg(feature = "postgres")]
 use diesel::data_types::PgTimestamp;
 use diesel::deserialize::FromSql;
+#[cfg(feature = "mysql")]
+use diesel::mysql::Mysql;
+#[cfg(feature = "postgres")]
 use diesel::pg::Pg;
 use diesel::serialize::{Output, ToSql};
 use diesel::sql_types;
+#[cfg(feature = "sqlite")]
+use diesel::sqlite::Sqlite;
 use rocket::form::{FromFormField, ValueField};
 use serde::de::Visitor;
 use serde::{Deserialize, Deserializer};
 use time;
 use time::format_description::well_known::Rfc3339;
+#[cfg(feature = "postgres")]
 use time::macros::datetime;
 
 /// An OffsetDateTime from [time] wrapper that can be used in diesel, serde and rocket contexts.
@@ -20,8 +28,15 @@ use time::macros::datetime;
 ///
 /// Note that you should prefer an `OffsetDateTime` over a [super::PrimitiveDateTime].
 /// This type can only be implemented on an SQL column that stores timezone information.
+/// On backends without such a column type (SQLite and MySQL) a regular timestamp column
+/// is used, in which the datetime is stored in UTC.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, FromSqlRow, AsExpression)]
-#[sql_type = "diesel::sql_types::Timestamptz"]
+#[cfg_attr(feature = "postgres", sql_type = "diesel::sql_types::Timestamptz")]
+#[cfg_attr(
+    any(feature = "sqlite", feature = "mysql"),
+    sql_type = "diesel::sql_types::Timestamp"
+)]
+#[cfg_attr(feature = "mysql", sql_type = "diesel::sql_types::Datetime")]
 pub struct OffsetDateTime(time::OffsetDateTime);
 
 impl Deref for OffsetDateTime {
@@ -50,6 +65,7 @@ impl AsMut<time::OffsetDateTime> for OffsetDateTime {
     }
 }
 
+#[cfg(feature = "postgres")]
 impl ToSql<sql_types::Timestamptz, Pg> for OffsetDateTime {
     fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> diesel::serialize::Result {
         let difference = (self.0 - datetime!(2000-01-01 00:00:00 UTC)).whole_microseconds();
@@ -58,6 +74,7 @@ impl ToSql<sql_types::Timestamptz, Pg> for OffsetDateTime {
     }
 }
 
+#[cfg(feature = "postgres")]
 impl FromSql<sql_types::Timestamptz, Pg> for OffsetDateTime {
     fn from_sql(bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
         use time::ext::NumericalDuration;
@@ -69,6 +86,65 @@ impl FromSql<sql_types::Timestamptz, Pg> for OffsetDateTime {
     }
 }
 
+#[cfg(feature = "sqlite")]
+impl ToSql<sql_types::Timestamp, Sqlite> for OffsetDateTime {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> diesel::serialize::Result {
+        let utc = self.0.to_offset(time::UtcOffset::UTC);
+        let text = utc.format(super::sqlite::TIMESTAMP_FORMAT)?;
+        ToSql::<sql_types::Text, Sqlite>::to_sql(&text, out)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl FromSql<sql_types::Timestamp, Sqlite> for OffsetDateTime {
+    fn from_sql(
+        value: Option<&<Sqlite as diesel::backend::Backend>::RawValue>,
+    ) -> diesel::deserialize::Result<Self> {
+        let text: String = FromSql::<sql_types::Text, Sqlite>::from_sql(value)?;
+        match time::OffsetDateTime::parse(&text, &Rfc3339) {
+            Ok(dt) => Ok(OffsetDateTime(dt)),
+            Err(_) => Ok(OffsetDateTime(
+                super::sqlite::parse_timestamp(&text)?.assume_utc(),
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl ToSql<sql_types::Timestamp, Mysql> for OffsetDateTime {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Mysql>) -> diesel::serialize::Result {
+        let utc = self.0.to_offset(time::UtcOffset::UTC);
+        let mysql_time = super::mysql::to_mysql_time(Some(utc.date()), Some(utc.time()));
+        super::mysql::write_mysql_time(&mysql_time, out)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl FromSql<sql_types::Timestamp, Mysql> for OffsetDateTime {
+    fn from_sql(bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
+        let mysql_time = super::mysql::read_mysql_time(bytes)?;
+        let date = super::mysql::date_from_mysql_time(&mysql_time)?;
+        let time = super::mysql::time_from_mysql_time(&mysql_time)?;
+        Ok(OffsetDateTime(
+            time::PrimitiveDateTime::new(date, time).assume_utc(),
+        ))
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl ToSql<sql_types::Datetime, Mysql> for OffsetDateTime {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Mysql>) -> diesel::serialize::Result {
+        ToSql::<sql_types::Timestamp, Mysql>::to_sql(self, out)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl FromSql<sql_types::Datetime, Mysql> for OffsetDateTime {
+    fn from_sql(bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
+        FromSql::<sql_types::Timestamp, Mysql>::from_sql(bytes)
+    }
+}
+
 impl<'v> FromFormField<'v> for OffsetDateTime {
     fn from_value(field: ValueField<'v>) -> rocket::form::Result<'v, Self> {
         let dt = Self::from_str(field.value)
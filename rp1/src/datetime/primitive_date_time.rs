@@ -1,18 +1,26 @@
+#[cfg(feature = "postgres")]
 use std::convert::TryFrom;
 use std::io::Write;
 use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 
+#[cfg(feature = "postgres")]
 use diesel::data_types::PgTimestamp;
 use diesel::deserialize::FromSql;
+#[cfg(feature = "mysql")]
+use diesel::mysql::Mysql;
+#[cfg(feature = "postgres")]
 use diesel::pg::Pg;
 use diesel::serialize::{Output, ToSql};
 use diesel::sql_types;
+#[cfg(feature = "sqlite")]
+use diesel::sqlite::Sqlite;
 use rocket::form::{FromFormField, ValueField};
 use serde::de::Visitor;
 use serde::{Deserialize, Deserializer};
 use time;
 use time::format_description::well_known::Rfc3339;
+#[cfg(feature = "postgres")]
 use time::macros::datetime;
 
 /// A PrimitiveDateTime from [time] wrapper that can be used in diesel, serde and rocket contexts.
@@ -21,7 +29,8 @@ use time::macros::datetime;
 /// Note that you should prefer an `PrimitiveDateTime` over a [super::OffsetDateTime].
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, FromSqlRow, AsExpression)]
 #[sql_type = "diesel::sql_types::Timestamp"]
-#[sql_type = "diesel::sql_types::Timestamptz"]
+#[cfg_attr(feature = "postgres", sql_type = "diesel::sql_types::Timestamptz")]
+#[cfg_attr(feature = "mysql", sql_type = "diesel::sql_types::Datetime")]
 pub struct PrimitiveDateTime(time::PrimitiveDateTime);
 
 impl Deref for PrimitiveDateTime {
@@ -50,6 +59,7 @@ impl AsMut<time::PrimitiveDateTime> for PrimitiveDateTime {
     }
 }
 
+#[cfg(feature = "postgres")]
 impl ToSql<sql_types::Timestamp, Pg> for PrimitiveDateTime {
     fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> diesel::serialize::Result {
         let difference = (self.0 - datetime!(2000-01-01 00:00:00)).whole_microseconds();
@@ -58,6 +68,7 @@ impl ToSql<sql_types::Timestamp, Pg> for PrimitiveDateTime {
     }
 }
 
+#[cfg(feature = "postgres")]
 impl FromSql<sql_types::Timestamp, Pg> for PrimitiveDateTime {
     fn from_sql(bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
         use time::ext::NumericalDuration;
@@ -69,6 +80,57 @@ impl FromSql<sql_types::Timestamp, Pg> for PrimitiveDateTime {
     }
 }
 
+#[cfg(feature = "sqlite")]
+impl ToSql<sql_types::Timestamp, Sqlite> for PrimitiveDateTime {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> diesel::serialize::Result {
+        let text = self.0.format(super::sqlite::TIMESTAMP_FORMAT)?;
+        ToSql::<sql_types::Text, Sqlite>::to_sql(&text, out)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl FromSql<sql_types::Timestamp, Sqlite> for PrimitiveDateTime {
+    fn from_sql(
+        value: Option<&<Sqlite as diesel::backend::Backend>::RawValue>,
+    ) -> diesel::deserialize::Result<Self> {
+        let text: String = FromSql::<sql_types::Text, Sqlite>::from_sql(value)?;
+        Ok(PrimitiveDateTime(super::sqlite::parse_timestamp(&text)?))
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl ToSql<sql_types::Timestamp, Mysql> for PrimitiveDateTime {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Mysql>) -> diesel::serialize::Result {
+        let mysql_time = super::mysql::to_mysql_time(Some(self.0.date()), Some(self.0.time()));
+        super::mysql::write_mysql_time(&mysql_time, out)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl FromSql<sql_types::Timestamp, Mysql> for PrimitiveDateTime {
+    fn from_sql(bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
+        let mysql_time = super::mysql::read_mysql_time(bytes)?;
+        Ok(PrimitiveDateTime(time::PrimitiveDateTime::new(
+            super::mysql::date_from_mysql_time(&mysql_time)?,
+            super::mysql::time_from_mysql_time(&mysql_time)?,
+        )))
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl ToSql<sql_types::Datetime, Mysql> for PrimitiveDateTime {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Mysql>) -> diesel::serialize::Result {
+        ToSql::<sql_types::Timestamp, Mysql>::to_sql(self, out)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl FromSql<sql_types::Datetime, Mysql> for PrimitiveDateTime {
+    fn from_sql(bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
+        FromSql::<sql_types::Timestamp, Mysql>::from_sql(bytes)
+    }
+}
+
 impl<'v> FromFormField<'v> for PrimitiveDateTime {
     fn from_value(field: ValueField<'v>) -> rocket::form::Result<'v, Self> {
         let dt = Self::from_str(field.value)
@@ -0,0 +1,50 @@
+//! SQLite does not have dedicated date and time storage, instead diesel
+//! stores these types as text. These helpers format and parse the textual
+//! representations accepted by the SQLite date and time functions.
+
+use time::format_description::FormatItem;
+use time::macros::format_description;
+
+pub(super) const DATE_FORMAT: &[FormatItem<'_>] = format_description!("[year]-[month]-[day]");
+
+pub(super) const TIME_FORMAT: &[FormatItem<'_>] =
+    format_description!("[hour]:[minute]:[second].[subsecond digits:6]");
+
+pub(super) const TIMESTAMP_FORMAT: &[FormatItem<'_>] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:6]");
+
+const TIME_PARSE_FORMATS: &[&[FormatItem<'_>]] = &[
+    format_description!("[hour]:[minute]:[second].[subsecond]"),
+    format_description!("[hour]:[minute]:[second]"),
+    format_description!("[hour]:[minute]"),
+];
+
+const TIMESTAMP_PARSE_FORMATS: &[&[FormatItem<'_>]] = &[
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond]"),
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second]"),
+    format_description!("[year]-[month]-[day] [hour]:[minute]"),
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond]"),
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]"),
+    format_description!("[year]-[month]-[day]T[hour]:[minute]"),
+];
+
+pub(super) fn parse_date(text: &str) -> diesel::deserialize::Result<time::Date> {
+    time::Date::parse(text, DATE_FORMAT).map_err(|_| format!("Invalid date {}", text).into())
+}
+
+pub(super) fn parse_time(text: &str) -> diesel::deserialize::Result<time::Time> {
+    // SQLite allows a trailing `Z` to indicate UTC
+    let text = text.trim_end_matches('Z');
+    TIME_PARSE_FORMATS
+        .iter()
+        .find_map(|format| time::Time::parse(text, format).ok())
+        .ok_or_else(|| format!("Invalid time {}", text).into())
+}
+
+pub(super) fn parse_timestamp(text: &str) -> diesel::deserialize::Result<time::PrimitiveDateTime> {
+    let text = text.trim_end_matches('Z');
+    TIMESTAMP_PARSE_FORMATS
+        .iter()
+        .find_map(|format| time::PrimitiveDateTime::parse(text, format).ok())
+        .ok_or_else(|| format!("Invalid datetime {}", text).into())
+}
@@ -2,11 +2,17 @@ use std::io::Write;
 use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 
+#[cfg(feature = "postgres")]
 use diesel::data_types::PgTime;
 use diesel::deserialize::FromSql;
+#[cfg(feature = "mysql")]
+use diesel::mysql::Mysql;
+#[cfg(feature = "postgres")]
 use diesel::pg::Pg;
 use diesel::serialize::{Output, ToSql};
 use diesel::sql_types;
+#[cfg(feature = "sqlite")]
+use diesel::sqlite::Sqlite;
 use rocket::form::{FromFormField, ValueField};
 use serde::de::Visitor;
 use serde::{Deserialize, Deserializer};
@@ -45,6 +51,7 @@ impl AsMut<time::Time> for Time {
     }
 }
 
+#[cfg(feature = "postgres")]
 impl ToSql<sql_types::Time, Pg> for Time {
     fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> diesel::serialize::Result {
         let micros = self.0.hour() as i64 * 3600 * 1_000_000
@@ -56,6 +63,7 @@ impl ToSql<sql_types::Time, Pg> for Time {
     }
 }
 
+#[cfg(feature = "postgres")]
 impl FromSql<sql_types::Time, Pg> for Time {
     fn from_sql(bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
         use time::ext::NumericalDuration;
@@ -65,6 +73,40 @@ impl FromSql<sql_types::Time, Pg> for Time {
     }
 }
 
+#[cfg(feature = "sqlite")]
+impl ToSql<sql_types::Time, Sqlite> for Time {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> diesel::serialize::Result {
+        let text = self.0.format(super::sqlite::TIME_FORMAT)?;
+        ToSql::<sql_types::Text, Sqlite>::to_sql(&text, out)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl FromSql<sql_types::Time, Sqlite> for Time {
+    fn from_sql(
+        value: Option<&<Sqlite as diesel::backend::Backend>::RawValue>,
+    ) -> diesel::deserialize::Result<Self> {
+        let text: String = FromSql::<sql_types::Text, Sqlite>::from_sql(value)?;
+        Ok(Time(super::sqlite::parse_time(&text)?))
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl ToSql<sql_types::Time, Mysql> for Time {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Mysql>) -> diesel::serialize::Result {
+        let mysql_time = super::mysql::to_mysql_time(None, Some(self.0));
+        super::mysql::write_mysql_time(&mysql_time, out)
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl FromSql<sql_types::Time, Mysql> for Time {
+    fn from_sql(bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
+        let mysql_time = super::mysql::read_mysql_time(bytes)?;
+        Ok(Time(super::mysql::time_from_mysql_time(&mysql_time)?))
+    }
+}
+
 impl<'v> FromFormField<'v> for Time {
     fn from_value(field: ValueField<'v>) -> rocket::form::Result<'v, Self> {
         let dt = Self::from_str(field.value)
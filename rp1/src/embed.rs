@@ -0,0 +1,163 @@
+//! Embedding related items in responses.
+//!
+//! Structs with `belongs_to` or `has_many` relations accept an `embed`
+//! parameter on the read and list endpoints, e.g. `?embed=post,comments`.
+//! The related items of all returned items are loaded using a single query
+//! per relation, and are added to the JSON output of every item under the
+//! name of the relation.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::str::FromStr;
+
+use diesel::backend::Backend;
+use diesel::result::{Error, QueryResult};
+use diesel::Connection;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{CrudError, CrudResult};
+
+/// This trait is implemented on the main struct and loads the items that are
+/// embedded in the output of structs that declare a `belongs_to` relation to
+/// this struct.
+///
+/// The `AuthUser` is the user of the request embedding the items, the
+/// permission checks of this struct are applied for that user. Structs that
+/// do not use authorization implement this trait for any user.
+pub trait CrudEmbed<AuthUser>: Sized {
+    /// The database backend of the struct.
+    type Backend: Backend;
+    /// The type of the primary key.
+    type Key;
+
+    /// Load the items with one of the given primary keys, which the user is
+    /// allowed to read.
+    fn load_embedded<C>(
+        conn: &C,
+        keys: Vec<Self::Key>,
+        auth_user: &AuthUser,
+    ) -> QueryResult<Vec<(Self::Key, Self)>>
+    where
+        C: Connection<Backend = Self::Backend>;
+}
+
+/// This trait is implemented on the main struct for every `belongs_to`
+/// relation to a `Parent` struct, and loads the items that are embedded in
+/// the output of a parent declaring a `has_many` relation to this struct.
+pub trait CrudBelongsTo<Parent, AuthUser>: Sized {
+    /// The database backend of the struct.
+    type Backend: Backend;
+    /// The type of the foreign key referring to the parent.
+    type Key;
+
+    /// Load the items of which the foreign key refers to one of the given
+    /// parents, which the user is allowed to read. The items are ordered by
+    /// their primary key.
+    fn load_belonging<C>(
+        conn: &C,
+        keys: Vec<Self::Key>,
+        auth_user: &AuthUser,
+    ) -> QueryResult<Vec<(Self::Key, Self)>>
+    where
+        C: Connection<Backend = Self::Backend>;
+}
+
+/// An item with the related items that were embedded in it, which are
+/// serialized as additional fields of the item.
+#[derive(Debug, Serialize)]
+pub struct Embedded<T> {
+    #[serde(flatten)]
+    pub item: T,
+    #[serde(flatten)]
+    pub embedded: Map<String, Value>,
+}
+
+impl<T> Embedded<T> {
+    pub fn new(item: T, embedded: Map<String, Value>) -> Embedded<T> {
+        Embedded { item, embedded }
+    }
+}
+
+/// Parse the relations in the `embed` parameter, every value can contain
+/// multiple comma separated relations.
+pub fn parse_embed<T>(values: &[String]) -> CrudResult<Vec<T>>
+where
+    T: FromStr<Err = String> + PartialEq,
+{
+    let mut relations = vec![];
+    for name in values.iter().flat_map(|v| v.split(',')).map(str::trim) {
+        if name.is_empty() {
+            continue;
+        }
+        let relation = name.parse().map_err(CrudError::InvalidEmbed)?;
+        if !relations.contains(&relation) {
+            relations.push(relation);
+        }
+    }
+
+    Ok(relations)
+}
+
+/// The distinct keys of the related items, to be passed to the query loading
+/// these items.
+pub fn embed_keys<K: Eq + Hash + Clone>(keys: &[Option<K>]) -> Vec<K> {
+    let mut seen = HashSet::new();
+    keys.iter()
+        .flatten()
+        .filter(|key| seen.insert(*key))
+        .cloned()
+        .collect()
+}
+
+/// The JSON value of the related item for every key, or `null` if there is
+/// no key or the item was not loaded.
+pub fn embed_one<K, T>(keys: &[Option<K>], items: Vec<(K, T)>) -> QueryResult<Vec<Value>>
+where
+    K: Eq + Hash,
+    T: Serialize,
+{
+    let items = items
+        .into_iter()
+        .map(|(key, item)| Ok((key, to_value(&item)?)))
+        .collect::<QueryResult<HashMap<_, _>>>()?;
+
+    Ok(keys
+        .iter()
+        .map(|key| {
+            key.as_ref()
+                .and_then(|key| items.get(key))
+                .cloned()
+                .unwrap_or(Value::Null)
+        })
+        .collect())
+}
+
+/// A JSON array with the related items for every key, in the order they were
+/// loaded.
+pub fn embed_many<K, T>(keys: &[Option<K>], items: Vec<(K, T)>) -> QueryResult<Vec<Value>>
+where
+    K: Eq + Hash,
+    T: Serialize,
+{
+    let mut groups: HashMap<K, Vec<Value>> = HashMap::new();
+    for (key, item) in items {
+        groups.entry(key).or_default().push(to_value(&item)?);
+    }
+
+    Ok(keys
+        .iter()
+        .map(|key| {
+            let items = key
+                .as_ref()
+                .and_then(|key| groups.get(key))
+                .cloned()
+                .unwrap_or_default();
+            Value::Array(items)
+        })
+        .collect())
+}
+
+fn to_value<T: Serialize>(item: &T) -> QueryResult<Value> {
+    serde_json::to_value(item).map_err(|e| Error::SerializationError(Box::new(e)))
+}
@@ -7,6 +7,8 @@ use std::io::Cursor;
 use std::num::ParseIntError;
 use std::str::ParseBoolError;
 
+use crate::ItemError;
+
 /// Indicates an error while trying to parse a filter value in the query string.
 #[derive(thiserror::Error, Debug)]
 pub enum ParseError {
@@ -27,6 +29,12 @@ pub enum ParseError {
 
     #[error("Unknown field '{0}'")]
     UnknownField(String),
+
+    #[error("Operator '{0}' cannot be used to compare with null")]
+    NullOperator(String),
+
+    #[error("Invalid filter group '{0}'")]
+    InvalidFilterGroup(String),
 }
 
 impl From<ParseIntError> for ParseError {
@@ -72,10 +80,26 @@ pub enum CrudError {
     ValidationErrors(::validator::ValidationErrors),
     #[error("Field {0} is not allowed to be changed")]
     UnchangeableField(String),
+    #[error("The item has been changed since it was retrieved")]
+    PreconditionFailed,
     #[error("Invalid sort specification: {0}")]
     InvalidSortSpec(String),
     #[error("Invalid filter: {0}")]
     InvalidFilterSpec(String),
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+    #[error("Invalid search query: {0}")]
+    InvalidSearchQuery(String),
+    #[error("Invalid embed: {0}")]
+    InvalidEmbed(String),
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+    #[error("Invalid item: {0}")]
+    InvalidItem(String),
+    #[error("More than {0} items match the filter")]
+    TooManyItems(i64),
+    #[error("{} of the items could not be processed", .0.len())]
+    ItemErrors(Vec<ItemError>),
     #[error("An unexpected value was returned from the database")]
     DbValueError,
 }
@@ -103,8 +127,25 @@ impl CrudError {
             #[cfg(feature = "validation")]
             CrudError::ValidationErrors(_) => Status::BadRequest,
             CrudError::UnchangeableField(_) => Status::BadRequest,
+            CrudError::PreconditionFailed => Status::PreconditionFailed,
             CrudError::InvalidSortSpec(_) => Status::BadRequest,
             CrudError::InvalidFilterSpec(_) => Status::BadRequest,
+            CrudError::InvalidCursor(_) => Status::BadRequest,
+            CrudError::InvalidSearchQuery(_) => Status::BadRequest,
+            CrudError::InvalidEmbed(_) => Status::BadRequest,
+            CrudError::InvalidQuery(_) => Status::BadRequest,
+            CrudError::InvalidItem(_) => Status::UnprocessableEntity,
+            CrudError::TooManyItems(_) => Status::BadRequest,
+            // the status of the item errors if they agree, `400` otherwise
+            CrudError::ItemErrors(errors) => {
+                let mut statuses = errors.iter().map(|e| e.error.status());
+                let first = statuses.next().unwrap_or(Status::BadRequest);
+                if statuses.all(|status| status == first) {
+                    first
+                } else {
+                    Status::BadRequest
+                }
+            }
             CrudError::DbValueError => Status::InternalServerError,
         }
     }
@@ -113,11 +154,23 @@ impl CrudError {
 impl<'r> Responder<'r, 'static> for CrudError {
     fn respond_to(self, _: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
         let status = self.status();
-        let body: String = ::serde_json::json!({
+        let mut body = ::serde_json::json!({
             "error": status.code,
             "message": self.to_string(),
-        })
-        .to_string();
+        });
+        if let CrudError::ItemErrors(errors) = &self {
+            body["errors"] = errors
+                .iter()
+                .map(|e| {
+                    ::serde_json::json!({
+                        "index": e.index,
+                        "error": e.error.status().code,
+                        "message": e.error.to_string(),
+                    })
+                })
+                .collect();
+        }
+        let body = body.to_string();
         Response::build()
             .status(status)
             .header(ContentType::JSON)
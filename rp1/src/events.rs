@@ -0,0 +1,236 @@
+//! Server-sent events of the changes made by the generated routes.
+//!
+//! When the `events` property is set, the create, update and delete routes
+//! publish the changed items to the [EventChannel] of the struct, which is
+//! managed by the fairing returned by its `events_fairing` function. The
+//! `GET /events` route streams these events to its subscribers.
+
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rocket::fairing::AdHoc;
+use rocket::futures::Stream;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::sync::broadcast;
+use rocket::tokio::time::{interval_at, Instant};
+use serde::Serialize;
+
+/// The number of events kept by the channel of the `events_fairing`, which
+/// can be resent to subscribers that reconnect.
+pub const DEFAULT_EVENT_BUFFER: usize = 1024;
+
+/// The interval at which a comment is sent to keep idle streams open.
+const HEARTBEAT: Duration = Duration::from_secs(30);
+
+/// The kind of change of an event, used as the name of the event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl EventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Created => "created",
+            EventKind::Updated => "updated",
+            EventKind::Deleted => "deleted",
+        }
+    }
+}
+
+/// A change of a single item. Deleted events contain the item as it was
+/// before it was deleted.
+#[derive(Debug)]
+pub struct CrudEvent<T> {
+    pub id: u64,
+    pub kind: EventKind,
+    pub item: T,
+}
+
+impl<T: Serialize> CrudEvent<T> {
+    fn to_sse(&self) -> Event {
+        Event::json(&self.item)
+            .event(self.kind.as_str())
+            .id(self.id.to_string())
+    }
+}
+
+/// A subscription to the events of an [EventChannel].
+pub struct Subscription<T> {
+    /// The buffered events after the last event id of the subscriber.
+    pub missed: Vec<Arc<CrudEvent<T>>>,
+    /// The id of the last published event, if some of the events after the
+    /// last event id of the subscriber are no longer buffered.
+    pub reset: Option<u64>,
+    /// The receiver of new events.
+    pub receiver: broadcast::Receiver<Arc<CrudEvent<T>>>,
+}
+
+struct EventBuffer<T> {
+    next_id: u64,
+    events: VecDeque<Arc<CrudEvent<T>>>,
+}
+
+/// An in-process broadcast channel of the events of a single struct, which
+/// keeps the most recent events so they can be resent to subscribers that
+/// missed them.
+pub struct EventChannel<T> {
+    sender: broadcast::Sender<Arc<CrudEvent<T>>>,
+    buffer: Mutex<EventBuffer<T>>,
+    capacity: usize,
+}
+
+impl<T> EventChannel<T>
+where
+    T: Send + Sync + 'static,
+{
+    /// Creates a channel keeping the last `capacity` events. The ids start at
+    /// the current time in microseconds, so they keep increasing when the
+    /// application is restarted.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let (sender, _) = broadcast::channel(capacity);
+        let start = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_micros() as u64);
+        EventChannel {
+            sender,
+            buffer: Mutex::new(EventBuffer {
+                next_id: start + 1,
+                events: VecDeque::with_capacity(capacity),
+            }),
+            capacity,
+        }
+    }
+
+    /// A fairing managing a channel with the default buffer size as state.
+    pub fn fairing(name: &'static str) -> AdHoc {
+        AdHoc::on_ignite(name, |rocket| async {
+            rocket.manage(EventChannel::<T>::new(DEFAULT_EVENT_BUFFER))
+        })
+    }
+
+    /// Sends the change of `item` to all current subscribers.
+    pub fn publish(&self, kind: EventKind, item: T) {
+        let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        let event = Arc::new(CrudEvent {
+            id: buffer.next_id,
+            kind,
+            item,
+        });
+        buffer.next_id += 1;
+        if buffer.events.len() == self.capacity {
+            buffer.events.pop_front();
+        }
+        buffer.events.push_back(event.clone());
+        // there may be no subscribers at all
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribes to the events of the channel, returning the buffered events
+    /// after `last_event_id` as well, if given. If some of those events are no
+    /// longer buffered, e.g. because the id is from before a restart, the
+    /// subscription is reset instead. The buffer stays locked until the
+    /// receiver is created, so no events are missed or received twice.
+    pub fn subscribe(&self, last_event_id: Option<u64>) -> Subscription<T> {
+        let buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        let last_id = buffer.next_id - 1;
+        let oldest_id = buffer
+            .events
+            .front()
+            .map_or(buffer.next_id, |event| event.id);
+        let (missed, reset) = match last_event_id {
+            Some(id) if id.saturating_add(1) >= oldest_id && id <= last_id => {
+                let missed = buffer
+                    .events
+                    .iter()
+                    .filter(|event| event.id > id)
+                    .cloned()
+                    .collect();
+                (missed, None)
+            }
+            Some(_) => (vec![], Some(last_id)),
+            None => (vec![], None),
+        };
+        Subscription {
+            missed,
+            reset,
+            receiver: self.sender.subscribe(),
+        }
+    }
+}
+
+/// The `Last-Event-ID` header of a client reconnecting to an event stream.
+/// Invalid values are ignored.
+pub struct LastEventId(pub Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = req
+            .headers()
+            .get_one("Last-Event-ID")
+            .and_then(|id| id.trim().parse().ok());
+        Outcome::Success(LastEventId(id))
+    }
+}
+
+/// Streams the events of the `channel` of which the item passes `allow`,
+/// starting with the buffered events after the `last_event_id`. The stream
+/// ends when the subscriber falls too far behind, the client is then expected
+/// to reconnect using the id of the last event it received. If the events it
+/// missed are no longer buffered, the stream starts with a `reset` event
+/// instead, after which the client should reload the items.
+///
+/// The heartbeat of the stream is sent as a separate event, as the heartbeat
+/// of Rocket may be sent in between the lines of an event.
+pub fn event_stream<T, F>(
+    channel: &EventChannel<T>,
+    last_event_id: LastEventId,
+    allow: F,
+) -> EventStream<impl Stream<Item = Event>>
+where
+    T: Serialize + Send + Sync + 'static,
+    F: Fn(&T) -> bool + Send + 'static,
+{
+    let Subscription {
+        missed,
+        reset,
+        mut receiver,
+    } = channel.subscribe(last_event_id.0);
+    EventStream::from(rocket::response::stream::stream! {
+        if let Some(id) = reset {
+            yield Event::data("null").event("reset").id(id.to_string());
+        }
+        for event in missed {
+            if allow(&event.item) {
+                yield event.to_sse();
+            }
+        }
+        let mut heartbeat = interval_at(Instant::now() + HEARTBEAT, HEARTBEAT);
+        loop {
+            let received = rocket::tokio::select! {
+                received = receiver.recv() => Some(received),
+                _ = heartbeat.tick() => None,
+            };
+            match received {
+                Some(Ok(event)) => {
+                    if allow(&event.item) {
+                        yield event.to_sse();
+                    }
+                }
+                // lagging or closed
+                Some(Err(_)) => break,
+                None => yield Event::comment(""),
+            }
+        }
+    })
+    .heartbeat(None)
+}
@@ -57,8 +57,101 @@ pub enum FilterOperator<T> {
     /// Only records that have one of the given values in the given field will
     /// be returned. Note that the values themselves may not contain a comma,
     /// as then they will be interpreted as separate values. It is not
-    /// recommended to use this filter for unstructured user data.
+    /// recommended to use this filter for unstructured user data. For
+    /// nullable fields an empty value in the list matches `NULL`, e.g.
+    /// `filter[field].in=,value1` matches either `NULL` or `value1`.
     EqAny(Vec<T>),
+
+    /// Null filter, i.e. `filter[field].isnull=true` or
+    /// `filter[field].notnull=true`.
+    ///
+    /// Only records where the given field is `NULL` (for `true`) or is not
+    /// `NULL` (for `false`) will be returned. The `notnull` operator is parsed
+    /// into this filter with the value inverted. This filter is only
+    /// available for nullable fields.
+    IsNull(bool),
+
+    /// Pattern filter, i.e. `filter[field].like=value`.
+    ///
+    /// Only records whose value in the given field matches the given `LIKE`
+    /// pattern will be returned. In the pattern `%` matches any sequence of
+    /// characters and `_` matches a single character, use `\` to match these
+    /// characters literally. The `contains`, `startswith` and `endswith`
+    /// operators are parsed into a pattern as well, in which case any `%` and
+    /// `_` in the value are escaped. This filter is only available for text
+    /// fields.
+    Like(T),
+
+    /// Case-insensitive pattern filter, i.e. `filter[field].ilike=value`.
+    ///
+    /// Like [FilterOperator::Like], but both the field and the pattern are
+    /// compared in lowercase. The `icontains`, `istartswith` and `iendswith`
+    /// operators are parsed into this filter. This filter is only available
+    /// for text fields.
+    ILike(T),
+}
+
+/// Escape the `LIKE` wildcard characters `%` and `_` (and the escape
+/// character `\` itself) in a value, so that it only matches literally.
+pub fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Convert a `LIKE` pattern, using `\` as the escape character, into the
+/// equivalent SQLite `GLOB` pattern. Unlike `LIKE`, `GLOB` is case-sensitive
+/// in SQLite.
+pub fn like_to_glob(pattern: &str) -> String {
+    fn push_literal(glob: &mut String, c: char) {
+        if matches!(c, '*' | '?' | '[') {
+            glob.push('[');
+            glob.push(c);
+            glob.push(']');
+        } else {
+            glob.push(c);
+        }
+    }
+
+    let mut glob = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => push_literal(&mut glob, chars.next().unwrap_or('\\')),
+            '%' => glob.push('*'),
+            '_' => glob.push('?'),
+            c => push_literal(&mut glob, c),
+        }
+    }
+    glob
+}
+
+/// Parser function for the text filters, which returns whether the filter is
+/// case-insensitive together with the `LIKE` pattern. Returns `None` if the
+/// operator is not a text operator.
+fn parse_text_operand(op: &str, value: &str) -> Option<(bool, String)> {
+    let (case_insensitive, pattern) = match op {
+        "like" => (false, value.to_owned()),
+        "ilike" => (true, value.to_owned()),
+        "contains" => (false, format!("%{}%", escape_like(value))),
+        "icontains" => (true, format!("%{}%", escape_like(value))),
+        "startswith" => (false, format!("{}%", escape_like(value))),
+        "istartswith" => (true, format!("{}%", escape_like(value))),
+        "endswith" => (false, format!("%{}", escape_like(value))),
+        "iendswith" => (true, format!("%{}", escape_like(value))),
+        _ => return None,
+    };
+
+    if case_insensitive {
+        Some((true, pattern.to_lowercase()))
+    } else {
+        Some((false, pattern))
+    }
 }
 
 /// Parser function for filters that expect a single filter value
@@ -88,29 +181,55 @@ impl<T: FromStr> FilterOperator<Option<T>>
 where
     <T as FromStr>::Err: Into<ParseError>,
 {
+    /// Parse a filter for a nullable field with an empty value, which is
+    /// interpreted as `NULL`. Only the operators that have a meaning when
+    /// comparing to `NULL` are allowed: `eq` matches `NULL`, `ne` matches
+    /// anything but `NULL` and `in` matches only `NULL`.
     pub fn from_none(op: &str) -> Result<Self, ParseError> {
         match op {
             "eq" => Ok(FilterOperator::Eq(None)),
             "ne" => Ok(FilterOperator::Ne(None)),
-            "gt" => Ok(FilterOperator::Gt(None)),
-            "ge" => Ok(FilterOperator::Ge(None)),
-            "lt" => Ok(FilterOperator::Lt(None)),
-            "le" => Ok(FilterOperator::Le(None)),
-            "in" => Ok(FilterOperator::EqAny(vec![])),
+            "gt" | "ge" | "lt" | "le" => Err(ParseError::NullOperator(op.to_owned())),
+            "in" => Ok(FilterOperator::EqAny(vec![None])),
             _ => Err(ParseError::UnknownOperator(op.to_owned())),
         }
     }
 
+    /// Parse a filter for a nullable field. Besides the operators supported by
+    /// [FilterOperator::try_parse] this supports the `isnull` and `notnull`
+    /// operators, and empty values in the list of the `in` operator.
     pub fn try_parse_option(op: &str, value: &str) -> Result<Self, ParseError> {
+        match op {
+            "isnull" => return Ok(FilterOperator::IsNull(parse_single_operand(value)?)),
+            "notnull" => {
+                let not_null: bool = parse_single_operand(value)?;
+                return Ok(FilterOperator::IsNull(!not_null));
+            }
+            "in" => {
+                return value
+                    .split(',')
+                    .map(|segment| match segment {
+                        "" => Ok(None),
+                        segment => parse_single_operand(segment).map(Some),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(FilterOperator::EqAny)
+            }
+            _ => {}
+        }
+
         let value: FilterOperator<T> = FilterOperator::try_parse(op, value)?;
         Ok(match value {
             FilterOperator::Eq(v) => FilterOperator::Eq(Some(v)),
             FilterOperator::Ne(v) => FilterOperator::Ne(Some(v)),
-            FilterOperator::Gt(v) => FilterOperator::Ne(Some(v)),
-            FilterOperator::Ge(v) => FilterOperator::Ne(Some(v)),
-            FilterOperator::Lt(v) => FilterOperator::Ne(Some(v)),
-            FilterOperator::Le(v) => FilterOperator::Ne(Some(v)),
+            FilterOperator::Gt(v) => FilterOperator::Gt(Some(v)),
+            FilterOperator::Ge(v) => FilterOperator::Ge(Some(v)),
+            FilterOperator::Lt(v) => FilterOperator::Lt(Some(v)),
+            FilterOperator::Le(v) => FilterOperator::Le(Some(v)),
             FilterOperator::EqAny(v) => FilterOperator::EqAny(v.into_iter().map(Some).collect()),
+            FilterOperator::IsNull(v) => FilterOperator::IsNull(v),
+            FilterOperator::Like(v) => FilterOperator::Like(Some(v)),
+            FilterOperator::ILike(v) => FilterOperator::ILike(Some(v)),
         })
     }
 }
@@ -132,3 +251,29 @@ where
         }
     }
 }
+
+impl FilterOperator<String> {
+    /// Parse a filter for a text field, which besides the operators supported
+    /// by [FilterOperator::try_parse] also supports the text operators.
+    pub fn try_parse_text(op: &str, value: &str) -> Result<Self, ParseError> {
+        match parse_text_operand(op, value) {
+            Some((false, pattern)) => Ok(FilterOperator::Like(pattern)),
+            Some((true, pattern)) => Ok(FilterOperator::ILike(pattern)),
+            None => FilterOperator::try_parse(op, value),
+        }
+    }
+}
+
+impl FilterOperator<Option<String>> {
+    /// Parse a filter for a nullable text field, see
+    /// [FilterOperator::try_parse_text] and [FilterOperator::try_parse_option].
+    /// An empty value is interpreted as `NULL` for the non-text operators.
+    pub fn try_parse_text_option(op: &str, value: &str) -> Result<Self, ParseError> {
+        match parse_text_operand(op, value) {
+            Some((false, pattern)) => Ok(FilterOperator::Like(Some(pattern))),
+            Some((true, pattern)) => Ok(FilterOperator::ILike(Some(pattern))),
+            None if value.is_empty() => FilterOperator::from_none(op),
+            None => FilterOperator::try_parse_option(op, value),
+        }
+    }
+}
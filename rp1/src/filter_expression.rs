@@ -0,0 +1,331 @@
+//! Boolean combinations of filters using `and`, `or` and `not` groups.
+
+use diesel::backend::Backend;
+use diesel::dsl::{not, sql};
+use diesel::expression::BoxableExpression;
+use diesel::expression_methods::BoolExpressionMethods;
+use diesel::sql_types::Bool;
+use rocket::form::{self, DataField, FromForm, ValueField};
+use serde::de::{Deserialize, Deserializer, Error as _};
+use serde_json::Value;
+
+use crate::ParseError;
+
+/// Boxed boolean condition on a table, used to filter list queries.
+pub type FilterCondition<QS, DB> = Box<dyn BoxableExpression<QS, DB, SqlType = Bool>>;
+
+/// A specification of filters on the fields of a single table, in which all
+/// filters should match. This trait is implemented by the [crate::crud] macro
+/// for the generated filter spec struct.
+pub trait FilterSpec: Default {
+    /// The table the filters apply to.
+    type Table: 'static;
+    /// The database backend the conditions are created for.
+    type Backend: Backend + 'static;
+
+    /// Parse a filter on a single field and add it to the spec.
+    fn push(&mut self, field: &str, operator: &str, value: &str) -> Result<(), ParseError>;
+
+    /// The condition matching all filters in the spec, or `None` if the spec
+    /// does not contain any filters.
+    fn condition(&self) -> Option<FilterCondition<Self::Table, Self::Backend>>;
+
+    /// Whether the name refers to a relation of the table, of which the
+    /// fields can be filtered on as well.
+    fn is_relation(_name: &str) -> bool {
+        false
+    }
+
+    /// Parse a filter on a related table and add it to the spec, the keys are
+    /// the parts of the parameter name after the name of the relation.
+    fn push_related(
+        &mut self,
+        relation: &str,
+        _keys: &[&str],
+        _value: &str,
+    ) -> Result<(), ParseError> {
+        Err(ParseError::UnknownField(relation.to_owned()))
+    }
+}
+
+/// A tree of filters, parsed from the `filter` query parameters.
+///
+/// The filters directly in the expression all have to match, just like the
+/// `and` groups. Of the `or` groups at least one has to match and the `not`
+/// group should not match. In a query these groups are given as nested keys,
+/// where the groups in an `and` or `or` group are labeled (usually by an
+/// index) to distinguish them:
+///
+/// * `filter[or][0][role]=admin&filter[or][1][role]=editor` matches all items
+///   where the role is either `admin` or `editor`.
+/// * `filter[not][role]=admin` matches all items where the role is not
+///   `admin`.
+/// * `filter[and][0][or][0][role]=admin&filter[and][0][or][1][id]lt=10` adds
+///   another set of alternatives next to those in `filter[or]`.
+///
+/// Groups can be nested as deep as you like.
+///
+/// Fields of related tables are filtered on by prefixing them with the name of
+/// the relation, e.g. `filter[post.title]contains=rust` or
+/// `filter[post][title]contains=rust`.
+///
+/// The expression can also be deserialized from a JSON object, using the same
+/// structure as the query parameters, e.g.
+/// `{ "or": [{ "role": "admin" }, { "id": { "lt": 10 } }] }`. The values of
+/// the `in` operator may be given as an array, and a `null` value is the same
+/// as an empty value in a query parameter.
+#[derive(Debug)]
+pub struct FilterExpression<S> {
+    /// Filters on the fields of the table, all of which should match.
+    pub spec: S,
+    /// Groups that should all match.
+    pub and: Vec<(String, FilterExpression<S>)>,
+    /// Groups of which at least one should match.
+    pub or: Vec<(String, FilterExpression<S>)>,
+    /// Group that should not match.
+    pub not: Option<Box<FilterExpression<S>>>,
+}
+
+impl<S: Default> Default for FilterExpression<S> {
+    fn default() -> Self {
+        FilterExpression {
+            spec: S::default(),
+            and: vec![],
+            or: vec![],
+            not: None,
+        }
+    }
+}
+
+/// Find the group with the given label, or add a new group if there is none.
+fn labeled_group<'a, S: Default>(
+    groups: &'a mut Vec<(String, FilterExpression<S>)>,
+    label: &str,
+) -> &'a mut FilterExpression<S> {
+    let index = match groups.iter().position(|(l, _)| l == label) {
+        Some(index) => index,
+        None => {
+            groups.push((label.to_owned(), Default::default()));
+            groups.len() - 1
+        }
+    };
+    &mut groups[index].1
+}
+
+impl<S: FilterSpec> FilterExpression<S> {
+    /// Parse a filter and add it to the expression. The keys are the parts of
+    /// the parameter name after `filter`, e.g. `["or", "0", "role", "ne"]`
+    /// for `filter[or][0][role]ne`. If the operator is left off, `eq` is
+    /// used.
+    pub fn push(&mut self, keys: &[&str], value: &str) -> Result<(), ParseError> {
+        let invalid_group = || ParseError::InvalidFilterGroup(keys.join("."));
+        match keys {
+            ["and", label, rest @ ..] if !rest.is_empty() => {
+                labeled_group(&mut self.and, label).push(rest, value)
+            }
+            ["or", label, rest @ ..] if !rest.is_empty() => {
+                labeled_group(&mut self.or, label).push(rest, value)
+            }
+            ["not", rest @ ..] if !rest.is_empty() => self
+                .not
+                .get_or_insert_with(Default::default)
+                .push(rest, value),
+            ["and", ..] | ["or", ..] | ["not", ..] => Err(invalid_group()),
+            [relation, rest @ ..] if S::is_relation(relation) && !rest.is_empty() => {
+                self.spec.push_related(relation, rest, value)
+            }
+            [field] => self.spec.push(field, "eq", value),
+            [field, operator] => self.spec.push(field, operator, value),
+            [] => Err(ParseError::UnknownField(String::new())),
+            _ => Err(ParseError::UnknownField(keys.join("."))),
+        }
+    }
+
+    /// Parse the filters in a JSON value and add them to the expression, see
+    /// [FilterExpression::push] for the meaning of the keys.
+    fn push_json(&mut self, keys: &mut Vec<String>, value: &Value) -> Result<(), ParseError> {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    // like in a query parameter, `post.title` is the same as
+                    // a nested `post` and `title` key
+                    let len = keys.len();
+                    keys.extend(key.split('.').map(|k| k.to_owned()));
+                    match (key.as_str(), value) {
+                        ("and", Value::Array(groups)) | ("or", Value::Array(groups)) => {
+                            for (i, group) in groups.iter().enumerate() {
+                                keys.push(i.to_string());
+                                self.push_json(keys, group)?;
+                                keys.pop();
+                            }
+                        }
+                        _ => self.push_json(keys, value)?,
+                    }
+                    keys.truncate(len);
+                }
+                Ok(())
+            }
+            Value::Array(values) => {
+                let values = values
+                    .iter()
+                    .map(|value| json_operand(keys, value))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.push_json_operand(keys, &values.join(","))
+            }
+            value => {
+                let value = json_operand(keys, value)?;
+                self.push_json_operand(keys, &value)
+            }
+        }
+    }
+
+    fn push_json_operand(&mut self, keys: &[String], value: &str) -> Result<(), ParseError> {
+        let keys = keys.iter().map(|k| k.as_str()).collect::<Vec<_>>();
+        self.push(&keys, value)
+    }
+
+    /// The condition matching this expression, or `None` if the expression
+    /// does not contain any filters and thus matches everything.
+    pub fn condition(&self) -> Option<FilterCondition<S::Table, S::Backend>> {
+        let mut conditions = vec![];
+        conditions.extend(self.spec.condition());
+        for (_, group) in self.and.iter() {
+            conditions.extend(group.condition());
+        }
+        if !self.or.is_empty() {
+            // a group without filters matches everything, which makes the
+            // whole `or` group match everything
+            let alternatives = self
+                .or
+                .iter()
+                .map(|(_, group)| group.condition())
+                .collect::<Option<Vec<_>>>();
+            conditions.extend(alternatives.and_then(any_condition));
+        }
+        if let Some(group) = &self.not {
+            conditions.push(match group.condition() {
+                Some(condition) => Box::new(not(condition)),
+                None => Box::new(sql::<Bool>("1 = 0")),
+            });
+        }
+
+        all_conditions(conditions)
+    }
+}
+
+/// Convert a scalar JSON value to the representation used in a query.
+fn json_operand(keys: &[String], value: &Value) -> Result<String, ParseError> {
+    match value {
+        Value::Null => Ok(String::new()),
+        Value::String(s) => Ok(s.clone()),
+        Value::Bool(_) | Value::Number(_) => Ok(value.to_string()),
+        Value::Array(_) | Value::Object(_) => Err(ParseError::InvalidFilterGroup(keys.join("."))),
+    }
+}
+
+impl<'de, S: FilterSpec> Deserialize<'de> for FilterExpression<S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        let mut expression: FilterExpression<S> = Default::default();
+        expression
+            .push_json(&mut vec![], &value)
+            .map_err(D::Error::custom)?;
+
+        Ok(expression)
+    }
+}
+
+/// Combine conditions such that all of them should match, returns `None` if
+/// there are no conditions.
+pub fn all_conditions<QS, DB>(
+    conditions: Vec<FilterCondition<QS, DB>>,
+) -> Option<FilterCondition<QS, DB>>
+where
+    DB: Backend + 'static,
+    QS: 'static,
+{
+    conditions
+        .into_iter()
+        .reduce(|all, condition| Box::new(all.and(condition)))
+}
+
+/// Combine conditions such that at least one of them should match, returns
+/// `None` if there are no conditions.
+pub fn any_condition<QS, DB>(
+    conditions: Vec<FilterCondition<QS, DB>>,
+) -> Option<FilterCondition<QS, DB>>
+where
+    DB: Backend + 'static,
+    QS: 'static,
+{
+    conditions
+        .into_iter()
+        .reduce(|any, condition| Box::new(any.or(condition)))
+}
+
+#[doc(hidden)]
+pub struct FilterExpressionContext<'r, S> {
+    expression: FilterExpression<S>,
+    errors: form::Errors<'r>,
+}
+
+impl<'r, S: FilterSpec> FilterExpressionContext<'r, S> {
+    fn push(&mut self, mut name: form::name::NameView<'r>, value: &str) {
+        let mut keys = vec![];
+        // a bracketed key like `[post.title]` is a single key in rocket, but
+        // is the same as `[post][title]` in a filter
+        while let Some(key) = name.key() {
+            keys.extend(key.as_str().split('.'));
+            name.shift();
+        }
+        if let Err(e) = self.expression.push(&keys, value) {
+            self.errors.push(form::Error::custom(e));
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, S: FilterSpec + Send + 'r> FromForm<'r> for FilterExpression<S> {
+    type Context = FilterExpressionContext<'r, S>;
+
+    fn init(_: form::Options) -> Self::Context {
+        FilterExpressionContext {
+            expression: Default::default(),
+            errors: form::Errors::new(),
+        }
+    }
+
+    fn push_value(ctxt: &mut Self::Context, field: ValueField<'r>) {
+        ctxt.push(field.name, field.value);
+    }
+
+    async fn push_data(ctxt: &mut Self::Context, field: DataField<'r, '_>) {
+        use rocket::data::ToByteUnit;
+
+        let limit = 256.kibibytes();
+        let bytes = match field.data.open(limit).into_bytes().await {
+            Ok(b) => b,
+            Err(e) => {
+                ctxt.errors.push(e.into());
+                return;
+            }
+        };
+        if !bytes.is_complete() {
+            ctxt.errors
+                .push(form::error::ErrorKind::from((None, Some(limit))).into());
+            return;
+        }
+        match String::from_utf8(bytes.into_inner()) {
+            Ok(data) => ctxt.push(field.name, &data),
+            Err(e) => ctxt.errors.push(e.utf8_error().into()),
+        }
+    }
+
+    fn finalize(ctxt: Self::Context) -> form::Result<'r, Self> {
+        if ctxt.errors.is_empty() {
+            Ok(ctxt.expression)
+        } else {
+            Err(ctxt.errors)
+        }
+    }
+}
@@ -1,5 +1,10 @@
 //! Contains some utilities mostly useful internally, but may be used in other
 //! places a well.
+use diesel::backend::Backend;
+use diesel::dsl::{sql, Nullable};
+use diesel::expression::{AsExpression, BoxableExpression, Expression, SqlLiteral};
+use diesel::sql_types::IntoNullable;
+use diesel::{Column, NullableExpressionMethods};
 use serde::de::{Deserialize, Deserializer};
 
 /// Deserialize helper to support double option types.
@@ -17,3 +22,202 @@ where
 {
     Deserialize::deserialize(de).map(Some)
 }
+
+/// Boxed select expression for a column in a partial result, see
+/// [partial_column].
+pub type PartialColumn<C, DB> = Box<
+    dyn BoxableExpression<
+        <C as Column>::Table,
+        DB,
+        SqlType = <<C as Expression>::SqlType as IntoNullable>::Nullable,
+    >,
+>;
+
+/// Select expression for a column in a partial result.
+///
+/// If the column was selected the column itself is selected (as a nullable
+/// value), otherwise a `NULL` value is selected in its place. This allows a
+/// query to always select the same number of columns, regardless of which
+/// fields were selected by the user.
+pub fn partial_column<C, DB>(column: C, selected: bool) -> PartialColumn<C, DB>
+where
+    DB: Backend,
+    C: Column + NullableExpressionMethods,
+    C::SqlType: IntoNullable,
+    Nullable<C>:
+        BoxableExpression<C::Table, DB, SqlType = <C::SqlType as IntoNullable>::Nullable> + 'static,
+    SqlLiteral<<C::SqlType as IntoNullable>::Nullable>:
+        BoxableExpression<C::Table, DB, SqlType = <C::SqlType as IntoNullable>::Nullable> + 'static,
+{
+    if selected {
+        Box::new(column.nullable())
+    } else {
+        Box::new(sql::<<C::SqlType as IntoNullable>::Nullable>("NULL"))
+    }
+}
+
+sql_function! {
+    /// The SQL `LOWER` function, used for case-insensitive text filters.
+    fn lower<ST>(x: ST) -> ST;
+}
+
+sql_function! {
+    /// The SQL `COALESCE` function for text values, used to combine nullable
+    /// text fields into a single search document.
+    fn coalesce(x: diesel::sql_types::Nullable<diesel::sql_types::Text>, y: diesel::sql_types::Text) -> diesel::sql_types::Text;
+}
+
+diesel_infix_operator!(Glob, " GLOB ");
+diesel_infix_operator!(LikeBinary, " LIKE BINARY ");
+
+/// The SQLite `GLOB` operator, used for case-sensitive text filters as `LIKE`
+/// ignores the case of ASCII characters in SQLite. The pattern should be
+/// converted using [crate::like_to_glob].
+pub fn glob<L, R>(left: L, right: R) -> Glob<L, R::Expression>
+where
+    L: Expression,
+    R: AsExpression<L::SqlType>,
+{
+    Glob::new(left, right.as_expression())
+}
+
+/// The MySQL `LIKE BINARY` operator, used for case-sensitive text filters as
+/// `LIKE` ignores case using the default collations of MySQL.
+pub fn like_binary<L, R>(left: L, right: R) -> LikeBinary<L, R::Expression>
+where
+    L: Expression,
+    R: AsExpression<L::SqlType>,
+{
+    LikeBinary::new(left, right.as_expression())
+}
+
+/// Split a search query into the lowercase `LIKE` patterns of its words, used
+/// on backends without full-text search. A row matches the query if every
+/// pattern matches at least one of the searchable fields.
+pub fn search_patterns(q: &str) -> Vec<String> {
+    q.split_whitespace()
+        .map(|word| format!("%{}%", crate::escape_like(&word.to_lowercase())))
+        .collect()
+}
+
+/// Full-text search support for Postgres.
+///
+/// The functions are not declared using `sql_function!`, as that also
+/// registers them for SQLite when its feature is enabled in diesel, which
+/// fails for the Postgres specific types.
+#[cfg(feature = "postgres")]
+pub mod full_text {
+    use diesel::expression::{AppearsOnTable, Expression, NonAggregate, SelectableExpression};
+    use diesel::pg::Pg;
+    use diesel::query_builder::{AstPass, QueryFragment};
+    use diesel::sql_types::{Float, Text};
+    use diesel::QueryResult;
+
+    /// The Postgres `tsvector` type.
+    #[derive(Debug, Clone, Copy, Default, QueryId, SqlType)]
+    #[postgres(oid = "3614", array_oid = "3643")]
+    pub struct TsVector;
+
+    /// The Postgres `tsquery` type.
+    #[derive(Debug, Clone, Copy, Default, QueryId, SqlType)]
+    #[postgres(oid = "3615", array_oid = "3645")]
+    pub struct TsQuery;
+
+    macro_rules! pg_function {
+        (
+            $(#[$meta:meta])*
+            fn $fn_name:ident($($arg:ident: $arg_ty:ty),+) -> $return_ty:ty;
+            struct $struct_name:ident;
+        ) => {
+            #[derive(Debug, Clone, Copy, QueryId)]
+            #[allow(non_camel_case_types)]
+            #[doc(hidden)]
+            pub struct $struct_name<$($arg),+> {
+                $($arg: $arg),+
+            }
+
+            $(#[$meta])*
+            #[allow(non_camel_case_types)]
+            pub fn $fn_name<$($arg),+>($($arg: $arg),+) -> $struct_name<$($arg::Expression),+>
+            where
+                $($arg: diesel::expression::AsExpression<$arg_ty>),+
+            {
+                $struct_name {
+                    $($arg: $arg.as_expression()),+
+                }
+            }
+
+            #[allow(non_camel_case_types)]
+            impl<$($arg),+> Expression for $struct_name<$($arg),+>
+            where
+                $($arg: Expression),+
+            {
+                type SqlType = $return_ty;
+            }
+
+            #[allow(non_camel_case_types)]
+            impl<$($arg),+> QueryFragment<Pg> for $struct_name<$($arg),+>
+            where
+                $($arg: QueryFragment<Pg>),+
+            {
+                fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+                    let args: &[&dyn QueryFragment<Pg>] = &[$(&self.$arg),+];
+                    out.push_sql(concat!(stringify!($fn_name), "("));
+                    for (i, arg) in args.iter().enumerate() {
+                        if i > 0 {
+                            out.push_sql(", ");
+                        }
+                        arg.walk_ast(out.reborrow())?;
+                    }
+                    out.push_sql(")");
+                    Ok(())
+                }
+            }
+
+            #[allow(non_camel_case_types)]
+            impl<$($arg,)+ QS> AppearsOnTable<QS> for $struct_name<$($arg),+>
+            where
+                Self: Expression,
+                $($arg: AppearsOnTable<QS>),+
+            {
+            }
+
+            #[allow(non_camel_case_types)]
+            impl<$($arg,)+ QS> SelectableExpression<QS> for $struct_name<$($arg),+>
+            where
+                Self: AppearsOnTable<QS>,
+                $($arg: SelectableExpression<QS>),+
+            {
+            }
+
+            #[allow(non_camel_case_types)]
+            impl<$($arg),+> NonAggregate for $struct_name<$($arg),+>
+            where
+                Self: Expression,
+                $($arg: NonAggregate),+
+            {
+            }
+        };
+    }
+
+    pg_function! {
+        /// Convert a document to a `tsvector`, using the default text search
+        /// configuration of the database.
+        fn to_tsvector(document: Text) -> TsVector;
+        struct ToTsVector;
+    }
+
+    pg_function! {
+        /// Convert plain text to a `tsquery` matching all of its words.
+        fn plainto_tsquery(query: Text) -> TsQuery;
+        struct PlainToTsQuery;
+    }
+
+    pg_function! {
+        /// Rank how well a `tsvector` matches a `tsquery`.
+        fn ts_rank(vector: TsVector, query: TsQuery) -> Float;
+        struct TsRank;
+    }
+
+    diesel_infix_operator!(Matches, " @@ ", backend: Pg);
+}
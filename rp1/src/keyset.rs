@@ -0,0 +1,153 @@
+//! Keyset (cursor) pagination support.
+
+use std::fmt::Write;
+
+use diesel::backend::Backend;
+use diesel::dsl::sql;
+use diesel::expression::BoxableExpression;
+use diesel::expression_methods::BoolExpressionMethods;
+use diesel::sql_types::Bool;
+use rocket::response::{self, Responder};
+use rocket::Request;
+use serde_json::Value;
+
+use crate::{CrudError, CrudResult, SortSpec};
+
+/// Boxed condition on a single key column of a keyset, see [keyset_condition].
+pub type KeysetCondition<QS, DB> = Box<dyn BoxableExpression<QS, DB, SqlType = Bool>>;
+
+/// An opaque cursor pointing at a row in a sorted list.
+///
+/// The cursor contains the value of every column the list was sorted on, in
+/// order, followed by the primary key. A list request with `?after=cursor`
+/// will only return the rows that come after the row the cursor points at.
+/// Because the cursor contains the sort specification of every key, it can
+/// only be used with the same sort specification as the request it was
+/// retrieved from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    keys: Vec<(SortSpec<String>, Value)>,
+}
+
+impl Cursor {
+    /// Create a cursor from the sort specifications of the key fields and the
+    /// values of the row the cursor should point at.
+    pub fn new(keys: Vec<(SortSpec<String>, Value)>) -> Cursor {
+        Cursor { keys }
+    }
+
+    /// The sort specifications of the key fields in this cursor together with
+    /// their value.
+    pub fn keys(&self) -> &[(SortSpec<String>, Value)] {
+        &self.keys
+    }
+
+    /// Make sure that the cursor was created for the given sort
+    /// specifications.
+    pub fn check(&self, keys: &[SortSpec<String>]) -> CrudResult<()> {
+        let matches = self.keys.len() == keys.len()
+            && self
+                .keys
+                .iter()
+                .zip(keys)
+                .all(|((spec, _), key)| spec == key);
+
+        if matches {
+            Ok(())
+        } else {
+            Err(CrudError::InvalidCursor(
+                "cursor does not match the sort specification".to_owned(),
+            ))
+        }
+    }
+
+    /// Encode the cursor as an opaque string that can be used in an url.
+    pub fn encode(&self) -> String {
+        let keys = self
+            .keys
+            .iter()
+            .map(|(spec, value)| Value::Array(vec![Value::String(spec.to_string()), value.clone()]))
+            .collect();
+        let json = Value::Array(keys).to_string();
+
+        json.bytes().fold(String::new(), |mut s, b| {
+            let _ = write!(s, "{:02x}", b);
+            s
+        })
+    }
+
+    /// Decode a cursor that was encoded using [Cursor::encode].
+    pub fn decode(input: &str) -> CrudResult<Cursor> {
+        let invalid = || CrudError::InvalidCursor("malformed cursor".to_owned());
+
+        let bytes = input
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| {
+                std::str::from_utf8(pair)
+                    .ok()
+                    .filter(|pair| pair.len() == 2)
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+        let keys: Vec<(String, Value)> = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+
+        let keys = keys
+            .into_iter()
+            .map(|(key, value)| Ok((key.parse().map_err(|_| invalid())?, value)))
+            .collect::<CrudResult<_>>()?;
+
+        Ok(Cursor { keys })
+    }
+}
+
+/// Build the condition that only keeps the rows after the given keys.
+///
+/// For every key this function expects a condition that the column equals the
+/// key value and a condition that the column comes after the key value in the
+/// sort order. The resulting condition is
+/// `(a > x) OR (a = x AND b > y) OR (a = x AND b = y AND c > z) ...`, which
+/// unlike a row value comparison also works when the keys are not all sorted
+/// in the same direction.
+pub fn keyset_condition<K, QS, DB, F>(keys: &[K], conditions: F) -> KeysetCondition<QS, DB>
+where
+    DB: Backend + 'static,
+    QS: 'static,
+    F: Fn(&K) -> (KeysetCondition<QS, DB>, KeysetCondition<QS, DB>),
+{
+    let mut condition: Option<KeysetCondition<QS, DB>> = None;
+    for (i, key) in keys.iter().enumerate() {
+        let (_, mut term) = conditions(key);
+        for previous in keys[..i].iter().rev() {
+            let (eq, _) = conditions(previous);
+            term = Box::new(eq.and(term));
+        }
+        condition = Some(match condition {
+            Some(condition) => Box::new(condition.or(term)),
+            None => term,
+        });
+    }
+
+    // without any keys there is no row to continue after
+    condition.unwrap_or_else(|| Box::new(sql::<Bool>("1 = 0")))
+}
+
+/// Wraps a list response to include the cursor of the last item in the list
+/// in an `X-Next-Cursor` header.
+#[derive(Debug)]
+pub struct WithCursor<R> {
+    pub inner: R,
+    pub cursor: Option<String>,
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for WithCursor<R> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = self.inner.respond_to(req)?;
+        if let Some(cursor) = self.cursor {
+            response.set_raw_header("X-Next-Cursor", cursor);
+        }
+
+        Ok(response)
+    }
+}
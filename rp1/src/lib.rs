@@ -3,10 +3,23 @@
 #[macro_use]
 extern crate diesel;
 
+mod audit;
+mod bulk;
+mod cache;
 pub mod datetime;
+mod embed;
 mod error;
+mod events;
 mod filter;
+mod filter_expression;
+mod keyset;
+mod link;
+pub mod openapi;
+mod pagination;
+mod relation;
+mod soft_delete;
 mod sort;
+mod version;
 
 pub mod helper;
 
@@ -15,9 +28,21 @@ pub mod access_control;
 use ::rocket::serde::json::Json;
 
 pub use access_control::*;
+pub use audit::*;
+pub use bulk::*;
+pub use cache::*;
+pub use embed::*;
 pub use error::*;
+pub use events::*;
 pub use filter::*;
+pub use filter_expression::*;
+pub use keyset::*;
+pub use link::*;
+pub use pagination::*;
+pub use relation::*;
+pub use soft_delete::*;
 pub use sort::*;
+pub use version::*;
 
 pub use rp1_macros::crud;
 
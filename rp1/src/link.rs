@@ -0,0 +1,65 @@
+//! Linking items through join tables.
+//!
+//! Structs with a `many_to_many` relation get routes to list the related
+//! items, and to link or unlink them by inserting or deleting rows of the join
+//! table. The related items are listed by the list implementation of the
+//! related struct, so the same parameters can be used.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use diesel::expression::BoxableExpression;
+use diesel::sql_types::Bool;
+use diesel::Table;
+use rocket::http::uri::Origin;
+
+use crate::{CrudEmbed, CrudResult};
+
+/// This trait is implemented on the main struct when the list endpoint is
+/// enabled, and lists the items that are linked to an item of a struct
+/// declaring a `many_to_many` relation to this struct. The `Database` is the
+/// Rocket database struct of the struct.
+#[rocket::async_trait]
+pub trait CrudLinked<AuthUser, Database>: CrudEmbed<AuthUser> {
+    /// The response of the list endpoint of the struct.
+    type Output;
+    /// The diesel table of the struct.
+    type Table: Table;
+
+    /// List the items selected by the `linked` condition, using the list
+    /// parameters in the query of the `uri`.
+    async fn list_linked(
+        db: Database,
+        linked: LinkedCondition<Self::Table, <Self as CrudEmbed<AuthUser>>::Backend>,
+        uri: &Origin<'_>,
+        auth_user: AuthUser,
+    ) -> CrudResult<Self::Output>;
+
+    /// Whether the user is allowed to link the item to, or unlink it from, an
+    /// item of the struct declaring the `many_to_many` relation with the given
+    /// name. This uses `allow_link` of `CheckPermissions` if the struct uses
+    /// authorization, otherwise all changes are allowed.
+    fn allow_link(&self, relation: &str, auth_user: &AuthUser) -> bool;
+}
+
+/// Builds the condition selecting the linked items from the primary key
+/// column of their table, usually a subquery on the join table. This keeps the
+/// number of query parameters independent of the number of linked items.
+pub type LinkedCondition<QS, DB> = Box<
+    dyn Fn(<QS as Table>::PrimaryKey) -> Box<dyn BoxableExpression<QS, DB, SqlType = Bool>> + Send,
+>;
+
+/// The keys in the given order, without duplicates.
+pub fn distinct_keys<K: Eq + Hash + Clone>(keys: Vec<K>) -> Vec<K> {
+    let mut seen = HashSet::new();
+    keys.into_iter()
+        .filter(|key| seen.insert(key.clone()))
+        .collect()
+}
+
+/// The loaded items in the order of their (distinct) keys, or `None` if any of
+/// the items was not loaded.
+pub fn linked_items<K: Eq + Hash, T>(keys: &[K], items: Vec<(K, T)>) -> Option<Vec<T>> {
+    let mut items = items.into_iter().collect::<HashMap<_, _>>();
+    keys.iter().map(|key| items.remove(key)).collect()
+}
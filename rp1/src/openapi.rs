@@ -0,0 +1,353 @@
+//! OpenAPI 3 documentation for the generated endpoints.
+//!
+//! Every struct with the [crate::crud] macro gets an `openapi()` function that
+//! describes its routes and schemas as a [Resource]. Combine the resources of
+//! your application into a single document using [OpenApi], which can also be
+//! mounted to serve the document:
+//!
+//! ```rust,ignore
+//! rocket::build()
+//!     .mount("/users", User::get_routes())
+//!     .mount("/posts", Post::get_routes())
+//!     .mount(
+//!         "/",
+//!         OpenApi::new("My API", "1.0.0")
+//!             .resource("/users", User::openapi())
+//!             .resource("/posts", Post::openapi()),
+//!     )
+//! ```
+
+use std::sync::Arc;
+
+use rocket::http::{ContentType, Method};
+use rocket::route::{Handler, Outcome};
+use rocket::{Data, Request, Route};
+use serde_json::{json, Map, Value};
+
+/// The OpenAPI version of the generated documents.
+pub const OPENAPI_VERSION: &str = "3.0.3";
+
+/// Description of the routes and schemas of a single crud resource.
+///
+/// Paths in a resource are relative to the path the routes are mounted at.
+#[derive(Debug, Clone, Default)]
+pub struct Resource {
+    /// Path items by (relative) path, e.g. `/` or `/{id}`.
+    pub paths: Map<String, Value>,
+    /// Schemas that should be added to the components of the document.
+    pub schemas: Map<String, Value>,
+}
+
+impl Resource {
+    /// The description of nested routes, consisting of the given operations
+    /// (as relative path and method) of this resource, moved to `prefix`. The
+    /// prefix contains the path `parameter` referring to the parent, and the
+    /// operation ids are prefixed with `operation_prefix`.
+    pub fn nested(
+        &self,
+        prefix: &str,
+        parameter: Value,
+        operations: &[(&str, &str)],
+        operation_prefix: &str,
+    ) -> Resource {
+        let mut nested = Resource {
+            paths: Map::new(),
+            schemas: self.schemas.clone(),
+        };
+        for (path, method) in operations {
+            let mut operation = match self.paths.get(*path).and_then(|item| item.get(method)) {
+                Some(operation) => operation.clone(),
+                None => continue,
+            };
+            if let Some(id) = operation["operationId"].as_str() {
+                operation["operationId"] = json!(format!("{}_{}", operation_prefix, id));
+            }
+            match operation["parameters"].as_array_mut() {
+                Some(parameters) => parameters.insert(0, parameter.clone()),
+                None => operation["parameters"] = json!([parameter.clone()]),
+            }
+            if operation["responses"].get("404").is_none() {
+                operation["responses"]["404"] = error_response("Parent not found");
+            }
+
+            let path = match *path {
+                "/" => prefix.to_owned(),
+                path => format!("{}{}", prefix, path),
+            };
+            nested.paths.entry(path).or_insert_with(|| json!({}))[*method] = operation;
+        }
+
+        nested
+    }
+
+    /// The schema of the primary key, taken from the path parameter of the
+    /// routes of a single item. If there are no such routes, any value is
+    /// allowed.
+    pub fn key_schema(&self) -> Value {
+        self.paths
+            .get("/{id}")
+            .and_then(Value::as_object)
+            .and_then(|item| item.values().next())
+            .and_then(|operation| operation["parameters"].get(0))
+            .map(|parameter| parameter["schema"].clone())
+            .unwrap_or_else(|| json!({}))
+    }
+}
+
+/// An OpenAPI document combining several resources.
+///
+/// This struct can be mounted directly, in which case the document is served
+/// as JSON at `/openapi.json` (relative to the mount point), use
+/// [OpenApi::path] to change this path.
+#[derive(Debug, Clone)]
+pub struct OpenApi {
+    path: String,
+    document: Value,
+}
+
+impl OpenApi {
+    /// Create an empty document with the given title and version of your API.
+    pub fn new(title: &str, version: &str) -> OpenApi {
+        let mut schemas = Map::new();
+        schemas.insert("CrudError".to_owned(), error_schema());
+
+        OpenApi {
+            path: "/openapi.json".to_owned(),
+            document: json!({
+                "openapi": OPENAPI_VERSION,
+                "info": {
+                    "title": title,
+                    "version": version,
+                },
+                "paths": {},
+                "components": {
+                    "schemas": schemas,
+                },
+            }),
+        }
+    }
+
+    /// Change the path at which the document is served.
+    pub fn path(mut self, path: &str) -> OpenApi {
+        self.path = path.to_owned();
+        self
+    }
+
+    /// Add a resource whose routes are mounted at `base`.
+    pub fn resource(mut self, base: &str, resource: Resource) -> OpenApi {
+        let base = base.trim_end_matches('/');
+        if let Some(paths) = self.document["paths"].as_object_mut() {
+            for (path, item) in resource.paths {
+                let path = match path.as_str() {
+                    "/" if !base.is_empty() => base.to_owned(),
+                    _ => format!("{}{}", base, path),
+                };
+                match paths.get_mut(&path).and_then(Value::as_object_mut) {
+                    Some(existing) => {
+                        if let Value::Object(item) = item {
+                            existing.extend(item);
+                        }
+                    }
+                    None => {
+                        paths.insert(path, item);
+                    }
+                }
+            }
+        }
+        if let Some(schemas) = self.document["components"]["schemas"].as_object_mut() {
+            schemas.extend(resource.schemas);
+        }
+
+        self
+    }
+
+    /// The complete OpenAPI document.
+    pub fn document(&self) -> &Value {
+        &self.document
+    }
+}
+
+impl From<OpenApi> for Vec<Route> {
+    fn from(openapi: OpenApi) -> Vec<Route> {
+        let handler = DocumentHandler(Arc::new(openapi.document.to_string()));
+        vec![Route::new(Method::Get, &openapi.path, handler)]
+    }
+}
+
+#[derive(Clone)]
+struct DocumentHandler(Arc<String>);
+
+#[rocket::async_trait]
+impl Handler for DocumentHandler {
+    async fn handle<'r>(&self, req: &'r Request<'_>, _: Data<'r>) -> Outcome<'r> {
+        Outcome::from(req, (ContentType::JSON, self.0.as_str().to_owned()))
+    }
+}
+
+/// Schema of the error body returned by all endpoints, see [crate::CrudError].
+pub fn error_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["error", "message"],
+        "properties": {
+            "error": { "type": "integer", "description": "The HTTP status code" },
+            "message": { "type": "string" },
+            "errors": {
+                "type": "array",
+                "description": "The errors of the items of an array, by their index",
+                "items": {
+                    "type": "object",
+                    "required": ["index", "error", "message"],
+                    "properties": {
+                        "index": { "type": "integer" },
+                        "error": { "type": "integer" },
+                        "message": { "type": "string" },
+                    },
+                },
+            },
+        },
+    })
+}
+
+/// Schema of the entries returned by the history route, see
+/// [crate::AuditEntry].
+pub fn audit_entry_schema() -> Value {
+    let changes = json!({
+        "type": "object",
+        "nullable": true,
+        "description": "The changed fields, or all fields of a created or deleted item",
+    });
+    json!({
+        "type": "object",
+        "required": ["id", "resource", "record_id", "action", "actor", "created_at", "before", "after"],
+        "properties": {
+            "id": { "type": "integer" },
+            "resource": { "type": "string" },
+            "record_id": { "type": "string", "description": "The primary key as JSON" },
+            "action": { "type": "string", "enum": ["create", "update", "delete", "restore"] },
+            "actor": { "type": "string", "nullable": true },
+            "created_at": { "type": "string", "format": "date-time" },
+            "before": changes,
+            "after": changes,
+        },
+    })
+}
+
+/// A reference to a schema in the components of the document.
+pub fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// A response with an error body.
+pub fn error_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": {
+            "application/json": { "schema": schema_ref("CrudError") },
+        },
+    })
+}
+
+/// A request body that can either be JSON or form encoded.
+pub fn request_body(schema: Value) -> Value {
+    json!({
+        "required": true,
+        "content": {
+            "application/json": { "schema": schema },
+            "application/x-www-form-urlencoded": { "schema": schema },
+        },
+    })
+}
+
+/// A successful response with a JSON body.
+pub fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": {
+            "application/json": { "schema": schema },
+        },
+    })
+}
+
+/// Add the `ETag` header containing the version of the item to a response.
+pub fn with_etag(mut response: Value) -> Value {
+    response["headers"] = json!({
+        "ETag": {
+            "description": "The version of the item",
+            "schema": { "type": "string" },
+        },
+    });
+    response
+}
+
+/// Add the headers of a response of the read and list routes, which can be
+/// used in conditional requests.
+pub fn with_cache_headers(mut response: Value, last_modified: bool, cache_control: bool) -> Value {
+    if !response["headers"].is_object() {
+        response["headers"] = json!({});
+    }
+    let headers = &mut response["headers"];
+    headers["ETag"] = json!({
+        "description": "The version of the item, or a hash of the response",
+        "schema": { "type": "string" },
+    });
+    if last_modified {
+        headers["Last-Modified"] = json!({ "schema": { "type": "string" } });
+    }
+    if cache_control {
+        headers["Cache-Control"] = json!({ "schema": { "type": "string" } });
+    }
+    response
+}
+
+/// The response to a conditional request if the client already has the
+/// current version of the response.
+pub fn not_modified_response() -> Value {
+    json!({ "description": "Not modified" })
+}
+
+/// A schema allowing either a single value or an array of values.
+pub fn one_or_many(schema: Value) -> Value {
+    json!({
+        "oneOf": [
+            schema.clone(),
+            { "type": "array", "items": schema },
+        ],
+    })
+}
+
+/// Mark a schema as nullable.
+pub fn nullable(mut schema: Value) -> Value {
+    if let Value::Object(ref mut schema) = schema {
+        schema.insert("nullable".to_owned(), Value::Bool(true));
+    }
+    schema
+}
+
+/// An object schema with the given properties, of which the properties in
+/// `required` must always be present.
+pub fn object_schema(properties: Vec<(&str, Value)>, required: Vec<&str>) -> Value {
+    let properties = properties
+        .into_iter()
+        .map(|(name, schema)| (name.to_owned(), schema))
+        .collect::<Map<_, _>>();
+    let mut schema = json!({
+        "type": "object",
+        "properties": properties,
+    });
+    if !required.is_empty() {
+        schema["required"] = json!(required);
+    }
+
+    schema
+}
+
+/// A path or query parameter.
+pub fn parameter(name: &str, location: &str, required: bool, schema: Value) -> Value {
+    json!({
+        "name": name,
+        "in": location,
+        "required": required,
+        "schema": schema,
+    })
+}
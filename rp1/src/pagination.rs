@@ -0,0 +1,169 @@
+//! Pagination metadata for list responses.
+
+use rocket::http::uri::Origin;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::Request;
+
+/// A single page of results from a list endpoint.
+///
+/// This is returned by the list endpoint when the `pagination` property of the
+/// [crate::crud] macro is enabled. Next to the JSON body, the response will
+/// include an `X-Total-Count` header and a `Link` header (as described in
+/// RFC 8288) with the `next` and `prev` links, if there are any.
+///
+/// If the current page was requested using a cursor (`?after=cursor`), the
+/// `next` link will also use a cursor instead of an offset. There is no `prev`
+/// link for such pages.
+#[derive(Debug, serde::Serialize)]
+pub struct Page<T> {
+    /// The items on this page.
+    pub items: Vec<T>,
+    /// The total number of items matching the filters of the request.
+    pub total: i64,
+    /// The offset of the first item on this page.
+    pub offset: i64,
+    /// The maximum number of items on this page.
+    pub limit: i64,
+    /// Url of the next page, if there are any items after this page.
+    pub next: Option<String>,
+    /// Url of the previous page, if there are any items before this page.
+    pub prev: Option<String>,
+    /// Cursor pointing at the last item on this page, if the page is full.
+    pub cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Create a new page without next and previous links, e.g. for a request
+    /// that cannot be repeated by following a link.
+    pub fn unlinked(
+        items: Vec<T>,
+        total: i64,
+        offset: i64,
+        limit: i64,
+        cursor: Option<String>,
+    ) -> Page<T> {
+        Page {
+            items,
+            total,
+            offset,
+            limit,
+            next: None,
+            prev: None,
+            cursor,
+        }
+    }
+
+    /// Create a new page, the next and previous links are derived from the
+    /// uri of the current request.
+    pub fn new(
+        items: Vec<T>,
+        total: i64,
+        offset: i64,
+        limit: i64,
+        cursor: Option<String>,
+        uri: &Origin<'_>,
+    ) -> Page<T> {
+        let (next, prev) = if has_query_param(uri, "after") {
+            let next = cursor.as_ref().map(|cursor| {
+                page_uri(
+                    uri,
+                    &[("after", cursor.clone()), ("limit", limit.to_string())],
+                )
+            });
+            (next, None)
+        } else {
+            let next = if offset + limit < total {
+                Some(page_uri(uri, &offset_params(offset + limit, limit)))
+            } else {
+                None
+            };
+            let prev = if offset > 0 {
+                Some(page_uri(
+                    uri,
+                    &offset_params(i64::max(0, offset - limit), limit),
+                ))
+            } else {
+                None
+            };
+            (next, prev)
+        };
+
+        Page {
+            next,
+            prev,
+            ..Page::unlinked(items, total, offset, limit, cursor)
+        }
+    }
+
+    fn link_header(&self) -> Option<String> {
+        let links = [("next", &self.next), ("prev", &self.prev)]
+            .iter()
+            .filter_map(|(rel, uri)| {
+                uri.as_ref()
+                    .map(|uri| format!("<{}>; rel=\"{}\"", uri, rel))
+            })
+            .collect::<Vec<_>>();
+
+        if links.is_empty() {
+            None
+        } else {
+            Some(links.join(", "))
+        }
+    }
+}
+
+const PAGE_PARAMS: [&str; 3] = ["offset", "limit", "after"];
+
+fn has_query_param(uri: &Origin<'_>, param: &str) -> bool {
+    uri.query()
+        .map(|q| q.raw_segments().any(|s| s.split_at_byte(b'=').0 == param))
+        .unwrap_or(false)
+}
+
+fn offset_params(offset: i64, limit: i64) -> [(&'static str, String); 2] {
+    [("offset", offset.to_string()), ("limit", limit.to_string())]
+}
+
+/// Returns the uri of the current request with the pagination query parameters
+/// (`offset`, `limit` and `after`) replaced by the given parameters, all other
+/// query parameters are kept as is.
+fn page_uri(uri: &Origin<'_>, params: &[(&str, String)]) -> String {
+    let mut query = uri
+        .query()
+        .map(|q| {
+            q.raw_segments()
+                .filter(|s| {
+                    let (key, _) = s.split_at_byte(b'=');
+                    !s.is_empty() && !PAGE_PARAMS.contains(&key.as_str())
+                })
+                .map(|s| s.as_str().to_owned())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    query.extend(
+        params
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value)),
+    );
+
+    format!("{}?{}", uri.path(), query.join("&"))
+}
+
+impl<'r, T: serde::Serialize> Responder<'r, 'static> for Page<T> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let total = self.total;
+        let link = self.link_header();
+        let cursor = self.cursor.clone();
+        let mut response = Json(self).respond_to(req)?;
+        response.set_raw_header("X-Total-Count", total.to_string());
+        if let Some(link) = link {
+            response.set_raw_header("Link", link);
+        }
+        if let Some(cursor) = cursor {
+            response.set_raw_header("X-Next-Cursor", cursor);
+        }
+
+        Ok(response)
+    }
+}
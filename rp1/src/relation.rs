@@ -0,0 +1,221 @@
+//! Filtering and sorting on the fields of related structs.
+//!
+//! A struct can declare a relation to another crud struct using a
+//! `belongs_to` attribute. The fields of the related struct can then be used
+//! in the filters and sort parameters of the list endpoint, e.g.
+//! `filter[post.title]contains=rust` or `sort=post.title`. The related rows
+//! are looked up with a subquery using the foreign key, so the list query
+//! itself still only selects from the table of the struct. Sorting on a
+//! related field uses a correlated subquery, which is evaluated for every
+//! matching row. The results are the same as those of an inner join on the
+//! related table: rows without a related row are excluded when filtering or
+//! sorting on a field of the related struct.
+
+use diesel::backend::Backend;
+use diesel::expression::{
+    AppearsOnTable, BoxableExpression, Expression, NonAggregate, SelectableExpression,
+};
+use diesel::query_builder::{AstPass, QueryFragment};
+use diesel::query_source::Table;
+use diesel::result::QueryResult;
+use diesel::sql_types::Bool;
+
+use crate::{CrudFilterSpec, FilterCondition, FilterExpression, SortSpec};
+
+/// Boxed expression to order a query on a table by.
+pub type OrderExpression<QS, DB> = Box<dyn BoxableExpression<QS, DB, SqlType = ()>>;
+
+/// An expression referring to the primary key of a related table, usually a
+/// foreign key column.
+pub trait ForeignKey<QS, DB: Backend>:
+    SelectableExpression<QS> + NonAggregate + QueryFragment<DB> + Clone + 'static
+{
+}
+
+impl<T, QS, DB> ForeignKey<QS, DB> for T
+where
+    DB: Backend,
+    T: SelectableExpression<QS> + NonAggregate + QueryFragment<DB> + Clone + 'static,
+{
+}
+
+/// This trait is implemented on the main struct and allows other structs to
+/// filter and sort on its fields using a `belongs_to` relation. Only the
+/// filterable and sortable fields of the struct are available to other
+/// structs.
+pub trait CrudRelated: CrudFilterSpec {
+    /// The database backend of the struct.
+    type Backend: Backend;
+    /// The fields the struct can be sorted on.
+    type SortableFields: std::str::FromStr<Err = String> + std::fmt::Display;
+
+    /// Condition on another table that matches the rows of which the foreign
+    /// key refers to a row matching the filter, or `None` if the filter
+    /// does not contain any filters.
+    fn related_condition<QS, FK>(
+        foreign_key: FK,
+        filter: &FilterExpression<Self::FilterSpecType>,
+    ) -> Option<FilterCondition<QS, Self::Backend>>
+    where
+        QS: 'static,
+        FK: ForeignKey<QS, Self::Backend>;
+
+    /// The expressions to order the rows of another table by the field of
+    /// the row their foreign key refers to.
+    fn related_order<QS, FK>(
+        foreign_key: FK,
+        sort_spec: SortSpec<Self::SortableFields>,
+    ) -> Vec<OrderExpression<QS, Self::Backend>>
+    where
+        QS: 'static,
+        FK: ForeignKey<QS, Self::Backend>;
+
+    /// Condition on another table that matches the rows of which the foreign
+    /// key refers to a row that has a value for the sort field, i.e. for
+    /// which the related rows of all relations in the field exist.
+    fn related_sort_condition<QS, FK>(
+        foreign_key: FK,
+        field: &Self::SortableFields,
+    ) -> FilterCondition<QS, Self::Backend>
+    where
+        QS: 'static,
+        FK: ForeignKey<QS, Self::Backend>;
+}
+
+/// Condition that matches if the foreign key refers to a row of the related
+/// table matching a condition on that table, i.e.
+/// `foreign_key IN (SELECT primary_key FROM table WHERE condition)`.
+#[derive(Debug, Clone)]
+pub struct RelatedCondition<T, FK, C> {
+    table: T,
+    foreign_key: FK,
+    condition: Option<C>,
+}
+
+impl<T, FK, C> RelatedCondition<T, FK, C> {
+    pub fn new(table: T, foreign_key: FK, condition: C) -> Self {
+        RelatedCondition {
+            table,
+            foreign_key,
+            condition: Some(condition),
+        }
+    }
+}
+
+impl<T, FK> RelatedCondition<T, FK, ()> {
+    /// Condition that matches if the foreign key refers to any row of the
+    /// related table, i.e. `foreign_key IN (SELECT primary_key FROM table)`.
+    pub fn exists(table: T, foreign_key: FK) -> Self {
+        RelatedCondition {
+            table,
+            foreign_key,
+            condition: None,
+        }
+    }
+}
+
+impl<T, FK: Expression, C> Expression for RelatedCondition<T, FK, C> {
+    type SqlType = Bool;
+}
+
+impl<T, FK, C, QS> AppearsOnTable<QS> for RelatedCondition<T, FK, C>
+where
+    FK: AppearsOnTable<QS>,
+    Self: Expression,
+{
+}
+
+impl<T, FK, C, QS> SelectableExpression<QS> for RelatedCondition<T, FK, C>
+where
+    FK: SelectableExpression<QS>,
+    Self: AppearsOnTable<QS>,
+{
+}
+
+impl<T, FK, C> NonAggregate for RelatedCondition<T, FK, C> where Self: Expression {}
+
+impl<T, FK, C, DB> QueryFragment<DB> for RelatedCondition<T, FK, C>
+where
+    DB: Backend,
+    T: Table,
+    T::FromClause: QueryFragment<DB>,
+    T::PrimaryKey: QueryFragment<DB>,
+    FK: QueryFragment<DB>,
+    C: QueryFragment<DB>,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        self.foreign_key.walk_ast(out.reborrow())?;
+        out.push_sql(" IN (SELECT ");
+        self.table.primary_key().walk_ast(out.reborrow())?;
+        out.push_sql(" FROM ");
+        self.table.from_clause().walk_ast(out.reborrow())?;
+        if let Some(condition) = &self.condition {
+            out.push_sql(" WHERE ");
+            condition.walk_ast(out.reborrow())?;
+        }
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+/// The value of a column of the row of a related table the foreign key refers
+/// to, i.e. `(SELECT column FROM table WHERE primary_key = foreign_key)`.
+#[derive(Debug, Clone)]
+pub struct RelatedValue<T, FK, C> {
+    table: T,
+    foreign_key: FK,
+    column: C,
+}
+
+impl<T, FK, C> RelatedValue<T, FK, C> {
+    pub fn new(table: T, foreign_key: FK, column: C) -> Self {
+        RelatedValue {
+            table,
+            foreign_key,
+            column,
+        }
+    }
+}
+
+impl<T, FK, C: Expression> Expression for RelatedValue<T, FK, C> {
+    type SqlType = C::SqlType;
+}
+
+impl<T, FK, C, QS> AppearsOnTable<QS> for RelatedValue<T, FK, C>
+where
+    FK: AppearsOnTable<QS>,
+    Self: Expression,
+{
+}
+
+impl<T, FK, C, QS> SelectableExpression<QS> for RelatedValue<T, FK, C>
+where
+    FK: SelectableExpression<QS>,
+    Self: AppearsOnTable<QS>,
+{
+}
+
+impl<T, FK, C> NonAggregate for RelatedValue<T, FK, C> where Self: Expression {}
+
+impl<T, FK, C, DB> QueryFragment<DB> for RelatedValue<T, FK, C>
+where
+    DB: Backend,
+    T: Table,
+    T::FromClause: QueryFragment<DB>,
+    T::PrimaryKey: QueryFragment<DB>,
+    FK: QueryFragment<DB>,
+    C: QueryFragment<DB>,
+{
+    fn walk_ast(&self, mut out: AstPass<DB>) -> QueryResult<()> {
+        out.push_sql("(SELECT ");
+        self.column.walk_ast(out.reborrow())?;
+        out.push_sql(" FROM ");
+        self.table.from_clause().walk_ast(out.reborrow())?;
+        out.push_sql(" WHERE ");
+        self.table.primary_key().walk_ast(out.reborrow())?;
+        out.push_sql(" = ");
+        self.foreign_key.walk_ast(out.reborrow())?;
+        out.push_sql(")");
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+//! Soft deletes using a marker field.
+//!
+//! Items of a struct with a field marked as `#[soft_delete]` are not removed
+//! by the delete route, instead the marker is set. Marked items are excluded
+//! from all other routes, unless they are explicitly requested, and can be
+//! restored again.
+
+use crate::datetime;
+
+/// Values of a field marked as `#[soft_delete]`. Timestamps are set to the
+/// time of deletion, a `bool` is set to `true`.
+pub trait SoftDelete {
+    /// The value of the marker of a deleted item.
+    fn deleted() -> Self;
+
+    /// The value of the marker of an item that is not deleted.
+    fn restored() -> Self;
+
+    fn is_deleted(&self) -> bool;
+}
+
+impl SoftDelete for bool {
+    fn deleted() -> Self {
+        true
+    }
+
+    fn restored() -> Self {
+        false
+    }
+
+    fn is_deleted(&self) -> bool {
+        *self
+    }
+}
+
+/// Types of which the current time can be the deletion time of an item.
+pub trait DeletedAt {
+    fn now() -> Self;
+}
+
+impl DeletedAt for datetime::OffsetDateTime {
+    fn now() -> Self {
+        time::OffsetDateTime::now_utc().into()
+    }
+}
+
+impl DeletedAt for datetime::PrimitiveDateTime {
+    fn now() -> Self {
+        let now = time::OffsetDateTime::now_utc();
+        time::PrimitiveDateTime::new(now.date(), now.time()).into()
+    }
+}
+
+impl<T: DeletedAt> SoftDelete for Option<T> {
+    fn deleted() -> Self {
+        Some(T::now())
+    }
+
+    fn restored() -> Self {
+        None
+    }
+
+    fn is_deleted(&self) -> bool {
+        self.is_some()
+    }
+}
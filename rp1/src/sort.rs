@@ -1,5 +1,5 @@
 use std::fmt::{self, Display, Formatter};
-use std::str::from_utf8;
+use std::str::{from_utf8, FromStr};
 
 use rocket::data::ToByteUnit;
 use rocket::form::{self, name::NameView, DataField, FromFormField, ValueField};
@@ -7,7 +7,9 @@ use rocket::form::{self, name::NameView, DataField, FromFormField, ValueField};
 /// Specifies in which direction a sorting operation should occur.
 ///
 /// See the docs for [SortSpec] for a detailed description of the query syntax.
-#[derive(serde::Deserialize, serde::Serialize, rocket::FromFormField, Debug, PartialEq, Eq)]
+#[derive(
+    serde::Deserialize, serde::Serialize, rocket::FromFormField, Debug, Clone, Copy, PartialEq, Eq,
+)]
 pub enum SortDirection {
     /// Sort the field in ascending order.
     #[serde(rename = "asc")]
@@ -17,13 +19,60 @@ pub enum SortDirection {
     Desc,
 }
 
+/// Specifies where `NULL` values should be placed when sorting.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NullsOrder {
+    /// Place `NULL` values before all other values.
+    #[serde(rename = "first")]
+    First,
+    /// Place `NULL` values after all other values.
+    #[serde(rename = "last")]
+    Last,
+}
+
 /// Sort specification, contains a field and direction.
 ///
-/// A field enum is generated based on the fields in the struct.
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+/// A field enum is generated based on the fields in the struct. In a query a
+/// sort specification is written as the field name, prefixed with `-` to sort
+/// in descending order. Modifiers can be added after the field name, separated
+/// by a `:`:
+///
+/// * `nullsfirst` or `nullslast`: place `NULL` values before or after all
+///   other values, e.g. `sort=-publish_date:nullslast`. Without this modifier
+///   the database decides where `NULL` values are placed.
+/// * `ci`: sort a text field case-insensitively, e.g. `sort=username:ci`.
+///
+/// The [Display] implementation writes the specification in the same format.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub struct SortSpec<T> {
     pub field: T,
     pub direction: SortDirection,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nulls: Option<NullsOrder>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub case_insensitive: bool,
+}
+
+impl<T> SortSpec<T> {
+    /// Sort specification for a field without any modifiers.
+    pub fn new(field: T, direction: SortDirection) -> SortSpec<T> {
+        SortSpec {
+            field,
+            direction,
+            nulls: None,
+            case_insensitive: false,
+        }
+    }
+
+    /// The same sort specification for another representation of the field.
+    pub fn map<U>(&self, f: impl FnOnce(&T) -> U) -> SortSpec<U> {
+        SortSpec {
+            field: f(&self.field),
+            direction: self.direction,
+            nulls: self.nulls,
+            case_insensitive: self.case_insensitive,
+        }
+    }
 }
 
 impl<T: Display> Display for SortSpec<T> {
@@ -31,29 +80,68 @@ impl<T: Display> Display for SortSpec<T> {
         if self.direction == SortDirection::Desc {
             write!(f, "-")?;
         }
-        write!(f, "{}", self.field)
+        write!(f, "{}", self.field)?;
+        match self.nulls {
+            Some(NullsOrder::First) => write!(f, ":nullsfirst")?,
+            Some(NullsOrder::Last) => write!(f, ":nullslast")?,
+            None => {}
+        }
+        if self.case_insensitive {
+            write!(f, ":ci")?;
+        }
+
+        Ok(())
     }
 }
 
-impl<'v, T: FromFormField<'v>> SortSpec<T> {
-    fn from_str(mut data: &'v str, name: &NameView<'v>) -> form::Result<'v, SortSpec<T>> {
-        let mut direction = SortDirection::Asc;
-        if data.starts_with('-') {
-            data = data.trim_start_matches('-');
-            direction = SortDirection::Desc;
-        } else if data.starts_with('+') {
-            data = data.trim_start_matches('+');
+/// Split a sort specification into the field name and a sort specification
+/// containing the direction and modifiers.
+fn parse_sort_spec(mut data: &str) -> Result<SortSpec<&str>, String> {
+    let mut direction = SortDirection::Asc;
+    if data.starts_with('-') {
+        data = data.trim_start_matches('-');
+        direction = SortDirection::Desc;
+    } else if data.starts_with('+') {
+        data = data.trim_start_matches('+');
+    }
+
+    // in practice, a `sort=+username` will give us a data that starts with a space ` username`
+    data = data.trim_start_matches(' ');
+
+    let mut parts = data.split(':');
+    let mut spec = SortSpec::new(parts.next().unwrap_or_default(), direction);
+    for modifier in parts {
+        match modifier {
+            "nullsfirst" | "nullslast" if spec.nulls.is_some() => {
+                return Err(format!("Conflicting sort modifier '{}'", modifier));
+            }
+            "nullsfirst" => spec.nulls = Some(NullsOrder::First),
+            "nullslast" => spec.nulls = Some(NullsOrder::Last),
+            "ci" => spec.case_insensitive = true,
+            _ => return Err(format!("Unknown sort modifier '{}'", modifier)),
         }
+    }
 
-        // in practice, a `sort=+username` will give us a data that starts with a space ` username`
-        data = data.trim_start_matches(' ');
+    Ok(spec)
+}
 
+impl<'v, T: FromFormField<'v>> SortSpec<T> {
+    fn from_str(data: &'v str, name: &NameView<'v>) -> form::Result<'v, SortSpec<T>> {
+        let spec = parse_sort_spec(data).map_err(form::Error::validation)?;
         let field = FromFormField::from_value(ValueField {
             name: *name,
-            value: data,
+            value: spec.field,
         })?;
 
-        Ok(SortSpec { field, direction })
+        Ok(spec.map(|_| field))
+    }
+}
+
+impl FromStr for SortSpec<String> {
+    type Err = String;
+
+    fn from_str(data: &str) -> Result<Self, Self::Err> {
+        parse_sort_spec(data).map(|spec| spec.map(|field| field.to_string()))
     }
 }
 
@@ -0,0 +1,113 @@
+//! Optimistic concurrency using a version field.
+//!
+//! The version of an item is exposed as its `ETag`. A client can send this
+//! tag back in the `If-Match` header of an update or delete request, which is
+//! then only executed if the item was not changed in the meantime. Otherwise
+//! the request fails with `412 Precondition Failed`. Every update changes the
+//! version: integers are incremented and timestamps are set to the current
+//! time.
+
+use crate::datetime;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::Response;
+use serde::Serialize;
+use serde_json::Value;
+use std::convert::Infallible;
+
+/// The entity tag of an item with the given version. Versions that serialize
+/// to a string, such as timestamps, are used as is, other values are used in
+/// their JSON representation.
+pub fn etag<V: Serialize>(version: &V) -> String {
+    let version = match serde_json::to_value(version) {
+        Ok(Value::String(version)) => version,
+        Ok(version) => version.to_string(),
+        Err(_) => String::new(),
+    };
+    format!("\"{}\"", version)
+}
+
+/// Types of which the current time can be the version of an item. Integer
+/// versions are incremented by the update instead, other types cannot be used
+/// as a version.
+pub trait VersionTimestamp {
+    fn now() -> Self;
+}
+
+impl VersionTimestamp for datetime::OffsetDateTime {
+    fn now() -> Self {
+        time::OffsetDateTime::now_utc().into()
+    }
+}
+
+impl VersionTimestamp for datetime::PrimitiveDateTime {
+    fn now() -> Self {
+        let now = time::OffsetDateTime::now_utc();
+        time::PrimitiveDateTime::new(now.date(), now.time()).into()
+    }
+}
+
+/// The entity tags in the `If-Match` header of a request. A missing header or
+/// `*` matches any version of an item.
+#[derive(Debug, Default)]
+pub struct IfMatch(Option<Vec<String>>);
+
+impl IfMatch {
+    /// Whether the header lists specific entity tags.
+    pub fn is_conditional(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Whether the given entity tag is matched. Weak tags never match, as
+    /// `If-Match` uses the strong comparison.
+    pub fn matches(&self, etag: &str) -> bool {
+        match &self.0 {
+            Some(tags) => tags.iter().any(|tag| tag == etag),
+            None => true,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let tags = req
+            .headers()
+            .get("If-Match")
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim().to_owned())
+            .filter(|tag| !tag.is_empty())
+            .collect::<Vec<_>>();
+
+        if tags.is_empty() || tags.iter().any(|tag| tag == "*") {
+            Outcome::Success(IfMatch(None))
+        } else {
+            Outcome::Success(IfMatch(Some(tags)))
+        }
+    }
+}
+
+/// A JSON response for an item with a version, which is sent in the `ETag`
+/// header.
+#[derive(Debug)]
+pub struct Versioned<T> {
+    pub item: T,
+    pub etag: String,
+}
+
+impl<T> Versioned<T> {
+    pub fn new(item: T, etag: String) -> Self {
+        Versioned { item, etag }
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Versioned<T> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        Response::build_from(Json(self.item).respond_to(req)?)
+            .raw_header("ETag", self.etag)
+            .ok()
+    }
+}
@@ -14,6 +14,9 @@ pub fn crud_impl(args: AttributeArgs, item: TokenStream) -> crate::Result {
     let mut routes = vec![];
 
     tokens.push(crate::derive::common::derive_field_list(&props));
+    tokens.extend(crate::derive::common::derive_primary_key_check(&props));
+    tokens.push(crate::derive::embed::derive_embed(&props));
+    tokens.extend(crate::derive::nested::derive_find_parent(&props));
 
     if props.create {
         let (toks, mut func) = crate::derive::create::derive_crud_create(&props);
@@ -43,12 +46,43 @@ pub fn crud_impl(args: AttributeArgs, item: TokenStream) -> crate::Result {
         routes.append(&mut func);
     }
 
+    let (toks, mut func) = crate::derive::audit::derive_crud_history(&props);
+    tokens.push(toks);
+    routes.append(&mut func);
+
+    let (toks, mut func) = crate::derive::events::derive_crud_events(&props);
+    tokens.push(toks);
+    routes.append(&mut func);
+
     if props.list {
         let (toks, mut func) = crate::derive::list::derive_crud_list(&props);
         tokens.push(toks);
         routes.append(&mut func);
     }
 
+    let (toks, mut func) = crate::derive::bulk::derive_crud_bulk(&props);
+    tokens.push(toks);
+    routes.append(&mut func);
+
+    let (toks, mut func) = crate::derive::many_to_many::derive_many_to_many(&props);
+    tokens.push(toks);
+    routes.append(&mut func);
+
+    tokens.push(crate::derive::openapi::derive_openapi(&props));
+
+    let nested_routes = crate::derive::nested::nested_routes(&props);
+    let nested_routes_fn = if props.parent.is_some() {
+        Some(quote! {
+            /// The nested routes of this struct, to be mounted at the base of
+            /// the routes of the parent.
+            pub fn get_nested_routes() -> Vec<::rocket::Route> {
+                rocket::routes![#(#nested_routes),*]
+            }
+        })
+    } else {
+        None
+    };
+
     let CrudProps {
         module_name,
         ident,
@@ -82,6 +116,8 @@ pub fn crud_impl(args: AttributeArgs, item: TokenStream) -> crate::Result {
                 pub fn get_routes() -> Vec<::rocket::Route> {
                     rocket::routes![#(#routes),*]
                 }
+
+                #nested_routes_fn
             }
 
             impl ::rp1::CrudStruct for #ident {
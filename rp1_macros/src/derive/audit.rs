@@ -0,0 +1,162 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::Ident;
+
+use crate::{
+    derive::common::{derive_auth_param, derive_id_binding, derive_id_params, derive_id_route},
+    props::CrudProps,
+};
+
+/// Binds the `actor` recorded in the audit entries, which must be done before
+/// the `auth_user` is moved into the closure running the queries.
+pub(crate) fn derive_audit_actor(props: &CrudProps) -> Option<TokenStream> {
+    let ident = &props.ident;
+    props.audit.as_ref()?;
+    if props.auth {
+        Some(quote!(let actor = <#ident as ::rp1::CheckPermissions>::actor_id(&auth_user);))
+    } else {
+        Some(quote!(let actor: Option<String> = None;))
+    }
+}
+
+/// Derives the insert of an audit entry recording the change of `row` from
+/// `before` to `after`, which are both an `Option` of a reference to an item.
+pub(crate) fn derive_audit_write(
+    props: &CrudProps,
+    action: &str,
+    row: TokenStream,
+    before: TokenStream,
+    after: TokenStream,
+) -> Option<TokenStream> {
+    let CrudProps {
+        ident,
+        schema_path,
+        module_name,
+        ..
+    } = props;
+    let audit = props.audit.as_ref()?;
+    let resource = module_name.to_string();
+    let action = format_ident!("{}", action);
+    let keys = props.primary_key_fields().map(|f| &f.ident);
+    let columns = quote!(#schema_path::#audit::columns);
+
+    Some(quote! {
+        let record = ::rp1::AuditRecord::new::<_, #ident>(
+            ::rp1::AuditAction::#action,
+            &(#(#row.#keys.clone()),*),
+            #before,
+            #after,
+        );
+        diesel::insert_into(#schema_path::#audit::table)
+            .values((
+                #columns::resource.eq(#resource),
+                #columns::record_id.eq(record.record_id),
+                #columns::action.eq(record.action),
+                #columns::actor.eq(actor.clone()),
+                #columns::created_at.eq(record.created_at),
+                #columns::before.eq(record.before),
+                #columns::after.eq(record.after),
+            ))
+            .execute(conn)?;
+    })
+}
+
+/// Derives the `GET /<id>/history` route, which returns the audit entries of
+/// a single item, oldest first.
+pub(crate) fn derive_crud_history(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
+    let CrudProps {
+        ident,
+        database_struct,
+        schema_path,
+        table_name,
+        module_name,
+        ..
+    } = props;
+    let audit = match &props.audit {
+        Some(audit) => audit,
+        None => return (quote!(), vec![]),
+    };
+    let resource = module_name.to_string();
+    let columns = quote!(#schema_path::#audit::columns);
+
+    let auth_param = derive_auth_param(props);
+    let route = format!("{}/history", derive_id_route(props));
+    let id_params = derive_id_params(props);
+    let id_binding = derive_id_binding(props);
+    // the entries of a deleted item, of which there is no row left to check
+    // the permissions with, are only available if deleted items may be read
+    let (auth_check, found_check) = if props.auth {
+        let soft_deleted = props.soft_delete_field().map(|marker| {
+            let marker = &marker.ident;
+            quote! {
+                Some(row) if ::rp1::SoftDelete::is_deleted(&row.#marker) => {
+                    <#ident as ::rp1::CheckPermissions>::allow_read_deleted(&auth_user)
+                        && <#ident as ::rp1::CheckPermissions>::allow_read_history(&row, &auth_user)
+                }
+            }
+        });
+        (
+            Some(quote! {
+                let row = #schema_path::#table_name::table
+                    .find(id.clone())
+                    .first::<#ident>(conn)
+                    .optional()?;
+                let found = row.is_some();
+                let allowed = match row {
+                    #soft_deleted
+                    Some(row) => {
+                        <#ident as ::rp1::CheckPermissions>::allow_read_history(&row, &auth_user)
+                    }
+                    None => <#ident as ::rp1::CheckPermissions>::allow_read_deleted(&auth_user),
+                };
+                if !allowed {
+                    return Err(::rp1::CrudError::NotFound);
+                }
+            }),
+            Some(quote! {
+                if !found && entries.is_empty() {
+                    return Err(::rp1::CrudError::NotFound);
+                }
+            }),
+        )
+    } else {
+        (None, None)
+    };
+
+    let tokens = quote! {
+        #[::rocket::get(#route)]
+        async fn history_fn(
+            db: #database_struct,
+            #id_params
+            #auth_param
+        ) -> ::rp1::CrudJsonResult<Vec<::rp1::AuditEntry>>
+        {
+            #id_binding
+
+            let entries = db.run(move |conn| -> ::rp1::CrudResult<_> {
+                #auth_check
+                let entries = #schema_path::#audit::table
+                    .filter(#columns::resource.eq(#resource))
+                    .filter(#columns::record_id.eq(::rp1::audit_record_id(&id)))
+                    .order(#columns::id)
+                    .select((
+                        #columns::id,
+                        #columns::resource,
+                        #columns::record_id,
+                        #columns::action,
+                        #columns::actor,
+                        #columns::created_at,
+                        #columns::before,
+                        #columns::after,
+                    ))
+                    .load::<::rp1::AuditEntry>(conn)?;
+                #found_check
+                Ok(entries)
+            })
+            .await?;
+            Ok(::rocket::serde::json::Json(entries))
+        }
+    };
+
+    (tokens, vec![format_ident!("history_fn")])
+}
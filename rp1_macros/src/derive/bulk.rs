@@ -0,0 +1,442 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::Ident;
+
+use crate::{
+    derive::{
+        audit::{derive_audit_actor, derive_audit_write},
+        common::{
+            derive_auth_param, derive_changeset, derive_not_deleted, derive_soft_delete_changeset,
+            derive_write_transaction,
+        },
+        events::{derive_events_param, derive_publish, EventsParam},
+    },
+    props::CrudProps,
+};
+
+/// Derives the `PATCH /` and `DELETE /` routes, which update or delete all
+/// items matching the filter in the query.
+pub(crate) fn derive_crud_bulk(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
+    if !props.bulk || !(props.update || props.delete) {
+        return (quote!(), vec![]);
+    }
+
+    let CrudProps {
+        ident,
+        database_struct,
+        schema_path,
+        table_name,
+        filter_ident,
+        patch_ident,
+        put_ident,
+        ..
+    } = props;
+    let backend = props.backend.diesel_type();
+
+    let auth_param = derive_auth_param(props);
+    let EventsParam {
+        route_param: events_route_param,
+        ..
+    } = derive_events_param(props);
+    let audit_actor = derive_audit_actor(props);
+    let transaction = derive_write_transaction(props);
+    let (auth_ref_param, auth_ref) = if props.auth {
+        (
+            Some(quote!(auth_user: &<#ident as ::rp1::CheckPermissions>::AuthUser,)),
+            Some(quote!(&auth_user,)),
+        )
+    } else {
+        (None, None)
+    };
+    // the condition is combined with the permission filter, as the rows are
+    // locked by a query that cannot be boxed
+    let auth_filter = if props.auth {
+        Some(quote! {
+            let condition: ::rp1::FilterCondition<#schema_path::#table_name::table, #backend> =
+                match <#ident as ::rp1::CheckPermissions>::filter_list(auth_user) {
+                    ::rp1::PermissionFilter::KeepAll => condition,
+                    ::rp1::PermissionFilter::KeepNone => return Err(::rp1::CrudError::Forbidden),
+                    ::rp1::PermissionFilter::Filter(allowed) => Box::new(condition.and(allowed)),
+                };
+        })
+    } else {
+        None
+    };
+    let lock = if props.backend.supports_row_locking() {
+        Some(quote!(let query = query.for_update();))
+    } else {
+        None
+    };
+    // one row more than the limit is loaded to find out whether it is exceeded
+    let (limit, limit_check) = match props.bulk_limit {
+        Some(bulk_limit) => (
+            Some(quote!(let query = query.limit(#bulk_limit + 1);)),
+            Some(quote! {
+                if rows.len() as i64 > #bulk_limit {
+                    return Err(::rp1::CrudError::TooManyItems(#bulk_limit));
+                }
+            }),
+        ),
+        None => (None, None),
+    };
+
+    // soft deleted rows are never changed again by a bulk route
+    let not_deleted =
+        derive_not_deleted(props).map(|condition| quote!(let query = query.filter(#condition);));
+
+    let validate = if cfg!(feature = "validation") {
+        Some(quote! {
+            use ::validator::Validate;
+            value.validate()?;
+        })
+    } else {
+        None
+    };
+
+    let mut tokens = vec![quote! {
+        /// The condition of the filter of a bulk route, which must contain at
+        /// least one filter.
+        fn bulk_condition(
+            filter: &::rp1::FilterExpression<#filter_ident>,
+        ) -> ::rp1::CrudResult<::rp1::FilterCondition<#schema_path::#table_name::table, #backend>> {
+            filter.condition().ok_or_else(|| {
+                ::rp1::CrudError::InvalidFilterSpec("at least one filter is required".to_owned())
+            })
+        }
+    }];
+    let select_rows = if loads_rows(props) {
+        tokens.push(quote! {
+            /// Load the rows matching the filter of a bulk route, which are
+            /// locked until the transaction ends.
+            fn bulk_rows<C>(
+                conn: &C,
+                filter: &::rp1::FilterExpression<#filter_ident>,
+                #auth_ref_param
+            ) -> ::rp1::CrudResult<Vec<#ident>>
+            where
+                C: ::diesel::Connection<Backend = #backend>,
+            {
+                let condition = bulk_condition(filter)?;
+                #auth_filter
+                let query = #schema_path::#table_name::table.filter(condition);
+                #not_deleted
+                #limit
+                #lock
+                let rows = query.load::<#ident>(conn)?;
+                #limit_check
+
+                Ok(rows)
+            }
+        });
+        quote!(let rows = bulk_rows(conn, &filter, #auth_ref)?;)
+    } else {
+        quote!(let condition = bulk_condition(&filter)?;)
+    };
+    let mut routes = vec![];
+
+    if props.update {
+        let update_check = if props.auth {
+            Some(quote! {
+                for row in rows.iter() {
+                    let put_value = #put_ident::create(row, &value);
+                    if !<#ident as ::rp1::CheckPermissions>::allow_update(row, &put_value, &auth_user) {
+                        return Err(::rp1::CrudError::Forbidden);
+                    }
+                }
+            })
+        } else {
+            None
+        };
+        // the audit entries, events and hooks need the updated rows, which
+        // are loaded again
+        let keys = props
+            .primary_key_fields()
+            .map(|f| &f.ident)
+            .collect::<Vec<_>>();
+        let load_new_rows = if props.audit.is_some() || props.events || props.hooks {
+            Some(quote! {
+                let new_rows = rows
+                    .iter()
+                    .map(|row| {
+                        #schema_path::#table_name::table
+                            .find((#(row.#keys.clone()),*))
+                            .first::<#ident>(conn)
+                    })
+                    .collect::<::diesel::QueryResult<Vec<_>>>()?;
+            })
+        } else {
+            None
+        };
+        let audit_update = derive_audit_write(
+            props,
+            "Update",
+            quote!(row),
+            quote!(Some(row)),
+            quote!(Some(new_row)),
+        )
+        .map(|audit| {
+            quote! {
+                for (row, new_row) in rows.iter().zip(new_rows.iter()) {
+                    #audit
+                }
+            }
+        });
+        let (update_result, publish_update) =
+            derive_bulk_result(props, "Updated", quote!(updated), quote!(new_rows));
+        let changeset = derive_changeset(props, quote!(&value));
+        // the hooks can change every item differently, so each row is
+        // updated with its own changes, which are checked again after the hook
+        let (update_rows, after_update_hook) = if props.hooks {
+            let hook_auth_check = if props.auth {
+                Some(quote! {
+                    if !<#ident as ::rp1::CheckPermissions>::allow_update(row, &value, &auth_user) {
+                        return Err(::rp1::CrudError::Forbidden);
+                    }
+                })
+            } else {
+                None
+            };
+            (
+                quote! {{
+                    let mut count = 0;
+                    for row in rows.iter() {
+                        let mut value = #put_ident::create(row, &value);
+                        <#ident as ::rp1::CrudHooks>::before_update(row, &mut value, conn)?;
+                        #hook_auth_check
+                        value.validate_update(row)?;
+
+                        #validate
+
+                        count += diesel::update(
+                            #schema_path::#table_name::table.find((#(row.#keys.clone()),*)),
+                        )
+                        .set(#changeset)
+                        .execute(conn)?;
+                    }
+                    count
+                }},
+                Some(quote! {
+                    for new_row in new_rows.iter() {
+                        <#ident as ::rp1::CrudHooks>::after_update(new_row, conn)?;
+                    }
+                }),
+            )
+        } else {
+            (
+                derive_bulk_statement(
+                    props,
+                    |target| quote!(diesel::update(#target).set(#changeset)),
+                ),
+                None,
+            )
+        };
+
+        tokens.push(quote! {
+            #[::rocket::patch("/?<filter>", format = "json", data = "<value>")]
+            async fn bulk_update_fn(
+                db: #database_struct,
+                filter: Result<::rp1::FilterExpression<#filter_ident>, ::rocket::form::Errors<'_>>,
+                value: ::rocket::serde::json::Json<#patch_ident>,
+                #events_route_param
+                #auth_param
+            ) -> ::rp1::CrudResult<::serde_json::Value>
+            {
+                let filter = filter.map_err(|e| ::rp1::CrudError::InvalidFilterSpec(e.to_string()))?;
+                let value = value.into_inner();
+
+                #validate
+
+                #audit_actor
+                let updated = db.run(move |conn| {
+                    #transaction(|| -> ::rp1::CrudResult<_> {
+                        #select_rows
+                        #update_check
+                        let updated = #update_rows;
+                        #load_new_rows
+                        #after_update_hook
+                        #audit_update
+                        Ok(#update_result)
+                    })
+                })
+                .await?;
+                #publish_update
+                Ok(::serde_json::json!({
+                    "updated": updated,
+                }))
+            }
+        });
+        routes.push(format_ident!("bulk_update_fn"));
+    }
+
+    if props.delete {
+        let delete_check = if props.auth {
+            Some(quote! {
+                for row in rows.iter() {
+                    if !<#ident as ::rp1::CheckPermissions>::allow_delete(row, &auth_user) {
+                        return Err(::rp1::CrudError::Forbidden);
+                    }
+                }
+            })
+        } else {
+            None
+        };
+        let audit_delete = derive_audit_write(
+            props,
+            "Delete",
+            quote!(row),
+            quote!(Some(row)),
+            quote!(None),
+        )
+        .map(|audit| {
+            quote! {
+                for row in rows.iter() {
+                    #audit
+                }
+            }
+        });
+        let (delete_result, publish_delete) =
+            derive_bulk_result(props, "Deleted", quote!(deleted), quote!(rows));
+        let (before_delete_hook, after_delete_hook) = if props.hooks {
+            (
+                Some(quote! {
+                    for row in rows.iter() {
+                        <#ident as ::rp1::CrudHooks>::before_delete(row, conn)?;
+                    }
+                }),
+                Some(quote! {
+                    for row in rows.iter() {
+                        <#ident as ::rp1::CrudHooks>::after_delete(row, conn)?;
+                    }
+                }),
+            )
+        } else {
+            (None, None)
+        };
+        let delete_rows = match derive_soft_delete_changeset(props, quote!(deleted)) {
+            Some(changeset) => derive_bulk_statement(
+                props,
+                |target| quote!(diesel::update(#target).set(#changeset)),
+            ),
+            None => derive_bulk_statement(props, |target| quote!(diesel::delete(#target))),
+        };
+
+        tokens.push(quote! {
+            #[::rocket::delete("/?<filter>")]
+            async fn bulk_delete_fn(
+                db: #database_struct,
+                filter: Result<::rp1::FilterExpression<#filter_ident>, ::rocket::form::Errors<'_>>,
+                #events_route_param
+                #auth_param
+            ) -> ::rp1::CrudResult<::serde_json::Value>
+            {
+                let filter = filter.map_err(|e| ::rp1::CrudError::InvalidFilterSpec(e.to_string()))?;
+
+                #audit_actor
+                let deleted = db.run(move |conn| {
+                    #transaction(|| -> ::rp1::CrudResult<_> {
+                        #select_rows
+                        #delete_check
+                        #before_delete_hook
+                        let deleted = #delete_rows;
+                        #after_delete_hook
+                        #audit_delete
+                        Ok(#delete_result)
+                    })
+                })
+                .await?;
+                #publish_delete
+                Ok(::serde_json::json!({
+                    "deleted": deleted,
+                }))
+            }
+        });
+        routes.push(format_ident!("bulk_delete_fn"));
+    }
+
+    (quote!(#(#tokens)*), routes)
+}
+
+/// Whether the bulk routes load the matching rows before changing them, which
+/// is only needed for the permission checks, the hooks, the audit entries, the
+/// events and the limit.
+fn loads_rows(props: &CrudProps) -> bool {
+    props.auth || props.hooks || props.audit.is_some() || props.events || props.bulk_limit.is_some()
+}
+
+/// Derives the block executing the `statement` on the matching rows, which
+/// evaluates to the number of affected rows. The `statement` is given the target
+/// of the update or delete statement. Without loaded `rows` a single statement
+/// changes all rows matching the `condition`. Otherwise rows with a single
+/// primary key are changed in batches, composite keys are changed one row at a
+/// time.
+fn derive_bulk_statement(
+    props: &CrudProps,
+    statement: impl Fn(TokenStream) -> TokenStream,
+) -> TokenStream {
+    let CrudProps {
+        schema_path,
+        table_name,
+        ..
+    } = props;
+    if !loads_rows(props) {
+        let not_deleted = derive_not_deleted(props).map(|condition| quote!(.filter(#condition)));
+        let statement = statement(quote! {
+            #schema_path::#table_name::table.filter(condition)#not_deleted
+        });
+        return quote!(#statement.execute(conn)?);
+    }
+    let keys = props
+        .primary_key_fields()
+        .map(|f| &f.ident)
+        .collect::<Vec<_>>();
+
+    if props.has_composite_key() {
+        let statement = statement(quote! {
+            #schema_path::#table_name::table.find((#(row.#keys.clone()),*))
+        });
+        return quote! {{
+            let mut count = 0;
+            for row in rows.iter() {
+                count += #statement.execute(conn)?;
+            }
+            count
+        }};
+    }
+
+    let key = keys[0];
+    let statement = statement(quote! {
+        #schema_path::#table_name::table.filter(#schema_path::#table_name::columns::#key.eq_any(keys.to_vec()))
+    });
+    // SQLite limits the number of parameters of a single statement
+    quote! {{
+        let keys = rows.iter().map(|row| row.#key.clone()).collect::<Vec<_>>();
+        let mut count = 0;
+        for keys in keys.chunks(500) {
+            count += #statement.execute(conn)?;
+        }
+        count
+    }}
+}
+
+/// Derives the result of the transaction of a bulk route, and the publishing of
+/// the changed `rows` once it is committed. Without events only the `count` is
+/// returned.
+fn derive_bulk_result(
+    props: &CrudProps,
+    kind: &str,
+    count: TokenStream,
+    rows: TokenStream,
+) -> (TokenStream, Option<TokenStream>) {
+    match derive_publish(props, kind, quote!(row)) {
+        Some(publish) => (
+            quote!((#count, #rows)),
+            Some(quote! {
+                let (#count, rows) = #count;
+                for row in rows {
+                    #publish
+                }
+            }),
+        ),
+        None => (count, None),
+    }
+}
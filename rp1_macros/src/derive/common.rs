@@ -1,7 +1,8 @@
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::quote;
+use syn::LitInt;
 
-use crate::props::CrudProps;
+use crate::props::{integer_ty_name, CrudField, CrudProps};
 
 pub(crate) fn derive_auth_param(props: &CrudProps) -> Option<TokenStream> {
     let ident = &props.ident;
@@ -14,6 +15,365 @@ pub(crate) fn derive_auth_param(props: &CrudProps) -> Option<TokenStream> {
     }
 }
 
+/// Path of the routes that work on a single item, i.e. `/<id>` or, for a
+/// composite primary key, one segment per key field (`/<post_id>/<tag_id>`).
+pub(crate) fn derive_id_route(props: &CrudProps) -> String {
+    if props.has_composite_key() {
+        props
+            .primary_key_fields()
+            .map(|f| format!("/<{}>", f.ident))
+            .collect()
+    } else {
+        "/<id>".to_owned()
+    }
+}
+
+/// Route parameters for the primary key segments from [derive_id_route].
+pub(crate) fn derive_id_params(props: &CrudProps) -> TokenStream {
+    if props.has_composite_key() {
+        let params = props.primary_key_fields().map(|f| {
+            let CrudField { ident, ty, .. } = f;
+            quote!(#ident: #ty,)
+        });
+        quote!(#(#params)*)
+    } else {
+        let primary_type = &props.primary_type;
+        quote!(id: #primary_type,)
+    }
+}
+
+/// Combines the route parameters from [derive_id_params] into a single `id`
+/// binding that can be passed to `find`.
+pub(crate) fn derive_id_binding(props: &CrudProps) -> Option<TokenStream> {
+    if props.has_composite_key() {
+        let idents = props.primary_key_fields().map(|f| &f.ident);
+        Some(quote!(let id = (#(#idents),*);))
+    } else {
+        None
+    }
+}
+
+/// Inserts `value` and returns the inserted row. Backends that do not support
+/// `RETURNING` retrieve the row in the same transaction instead, either using
+/// the last inserted id or using the given composite primary key.
+pub(crate) fn derive_insert_returning(props: &CrudProps) -> TokenStream {
+    let CrudProps {
+        ident,
+        schema_path,
+        table_name,
+        backend,
+        ..
+    } = props;
+
+    if backend.supports_returning() {
+        return quote! {
+            diesel::insert_into(#schema_path::#table_name::table)
+                .values(&value)
+                .get_result(conn)
+        };
+    }
+
+    let find_inserted = if props.has_composite_key() {
+        let idents = props.primary_key_fields().map(|f| &f.ident);
+        quote! {
+            #schema_path::#table_name::table
+                .find((#(value.#idents.clone()),*))
+        }
+    } else {
+        let pk = props.primary_key_fields().map(|f| &f.ident).next();
+        let last_insert_id = backend.last_insert_id();
+        quote! {
+            #schema_path::#table_name::table
+                .filter(#schema_path::#table_name::columns::#pk.eq(diesel::dsl::sql(#last_insert_id)))
+        }
+    };
+
+    quote! {
+        conn.transaction(|| {
+            diesel::insert_into(#schema_path::#table_name::table)
+                .values(&value)
+                .execute(conn)?;
+            #find_inserted.first::<#ident>(conn)
+        })
+    }
+}
+
+/// Inserts all items in `values` in a single transaction and returns the
+/// inserted rows. Backends that support `RETURNING` use a single multi-row
+/// insert, others insert the rows one by one to retrieve each of them.
+pub(crate) fn derive_insert_many_returning(props: &CrudProps) -> TokenStream {
+    let CrudProps {
+        schema_path,
+        table_name,
+        backend,
+        ..
+    } = props;
+
+    if backend.supports_returning() {
+        return quote! {
+            conn.transaction(|| {
+                diesel::insert_into(#schema_path::#table_name::table)
+                    .values(&values)
+                    .get_results(conn)
+            })
+        };
+    }
+
+    let ident = &props.ident;
+    let insert_returning = derive_insert_returning(props);
+    quote! {
+        conn.transaction(|| {
+            values
+                .into_iter()
+                .map(|value| #insert_returning)
+                .collect::<diesel::QueryResult<Vec<#ident>>>()
+        })
+    }
+}
+
+/// Updates the row identified by `id` using the changeset in `value` and
+/// returns the updated row. A soft deleted row is not updated, and is not
+/// found.
+pub(crate) fn derive_update_returning(props: &CrudProps) -> TokenStream {
+    let CrudProps {
+        ident,
+        schema_path,
+        table_name,
+        backend,
+        ..
+    } = props;
+
+    let not_deleted = derive_not_deleted(props).map(|condition| quote!(.filter(#condition)));
+
+    if backend.supports_returning() {
+        quote! {
+            diesel::update(#schema_path::#table_name::table.find(id)#not_deleted)
+                .set(&value)
+                .get_result(conn)
+        }
+    } else {
+        quote! {
+            conn.transaction(|| {
+                diesel::update(#schema_path::#table_name::table.find(id.clone())#not_deleted)
+                    .set(&value)
+                    .execute(conn)?;
+                #schema_path::#table_name::table
+                    .find(id)
+                    #not_deleted
+                    .first::<#ident>(conn)
+            })
+        }
+    }
+}
+
+/// Starts a transaction in which a row is read, checked and written. SQLite
+/// does not support row locking, so it takes the write lock of the database
+/// when the transaction starts instead.
+pub(crate) fn derive_write_transaction(props: &CrudProps) -> TokenStream {
+    if props.backend.supports_row_locking() {
+        quote!(conn.transaction)
+    } else {
+        quote!(conn.immediate_transaction)
+    }
+}
+
+/// Loads the row with the given `id` in a transaction from
+/// [derive_write_transaction], locking it until the transaction ends. Soft
+/// deleted rows are not found.
+pub(crate) fn derive_find_for_update(props: &CrudProps) -> TokenStream {
+    let not_deleted = derive_not_deleted(props).map(|condition| quote!(.filter(#condition)));
+    derive_find_locked(props, not_deleted)
+}
+
+/// Like [derive_find_for_update], but also finds soft deleted rows.
+pub(crate) fn derive_find_deleted_for_update(props: &CrudProps) -> TokenStream {
+    derive_find_locked(props, None)
+}
+
+fn derive_find_locked(props: &CrudProps, filter: Option<TokenStream>) -> TokenStream {
+    let CrudProps {
+        ident,
+        schema_path,
+        table_name,
+        ..
+    } = props;
+    let lock = if props.backend.supports_row_locking() {
+        Some(quote!(.for_update()))
+    } else {
+        None
+    };
+
+    quote! {
+        #schema_path::#table_name::table
+            .find(id.clone())
+            #filter
+            #lock
+            .first::<#ident>(conn)?
+    }
+}
+
+/// Derives the changeset of an update setting `value`, which also changes the
+/// version: an integer version is incremented, a timestamp is set to the
+/// current time.
+pub(crate) fn derive_changeset(props: &CrudProps, value: TokenStream) -> TokenStream {
+    let CrudProps {
+        schema_path,
+        table_name,
+        ..
+    } = props;
+
+    match props.version_field() {
+        Some(CrudField { ident, ty, .. }) => {
+            let column = quote!(#schema_path::#table_name::columns::#ident);
+            match integer_ty_name(ty) {
+                Some(ty_name) => {
+                    let one = LitInt::new(&format!("1{}", ty_name), Span::call_site());
+                    quote!((#value, #column.eq(#column + #one)))
+                }
+                None => quote!((#value, #column.eq(<#ty as ::rp1::VersionTimestamp>::now()))),
+            }
+        }
+        None => value,
+    }
+}
+
+/// Checks the version of the loaded `row` against the `If-Match` header.
+pub(crate) fn derive_version_check(props: &CrudProps) -> Option<TokenStream> {
+    let version = &props.version_field()?.ident;
+    Some(quote! {
+        if !if_match.matches(&::rp1::etag(&row.#version)) {
+            return Err(::rp1::CrudError::PreconditionFailed);
+        }
+    })
+}
+
+/// The `If-Match` guard of the routes changing an item with a version.
+pub(crate) fn derive_if_match_param(props: &CrudProps) -> Option<TokenStream> {
+    props
+        .version_field()
+        .map(|_| quote!(if_match: ::rp1::IfMatch,))
+}
+
+/// The response of a route returning a single item of type `item_type`, which
+/// also contains the `ETag` header if the item has a version.
+pub(crate) struct ItemResponse {
+    /// The return type of the route.
+    pub result_type: TokenStream,
+    /// Binds the `etag` of the `row`, before it is changed into the response.
+    pub etag: Option<TokenStream>,
+    /// Creates the response from the `row`.
+    pub response: TokenStream,
+}
+
+pub(crate) fn derive_item_response(props: &CrudProps, item_type: TokenStream) -> ItemResponse {
+    match props.version_field() {
+        Some(CrudField { ident, .. }) => ItemResponse {
+            result_type: quote!(::rp1::CrudResult<::rp1::Versioned<#item_type>>),
+            etag: Some(quote!(let etag = ::rp1::etag(&row.#ident);)),
+            response: quote!(::rp1::Versioned::new(row, etag)),
+        },
+        None => ItemResponse {
+            result_type: quote!(::rp1::CrudJsonResult<#item_type>),
+            etag: None,
+            response: quote!(::rocket::serde::json::Json(row)),
+        },
+    }
+}
+
+/// The condition matching the rows that are not soft deleted, if the struct
+/// has a field marked as `#[soft_delete]`.
+pub(crate) fn derive_not_deleted(props: &CrudProps) -> Option<TokenStream> {
+    let CrudProps {
+        schema_path,
+        table_name,
+        ..
+    } = props;
+    let CrudField {
+        ident, is_option, ..
+    } = props.soft_delete_field()?;
+    let column = quote!(#schema_path::#table_name::columns::#ident);
+
+    if *is_option {
+        Some(quote!(#column.is_null()))
+    } else {
+        Some(quote!(#column.eq(false)))
+    }
+}
+
+/// Derives the changeset setting the marker of a soft deleted item to the
+/// `deleted` or `restored` value of `SoftDelete`. The version is changed as
+/// well.
+pub(crate) fn derive_soft_delete_changeset(
+    props: &CrudProps,
+    value: TokenStream,
+) -> Option<TokenStream> {
+    let CrudProps {
+        schema_path,
+        table_name,
+        ..
+    } = props;
+    let CrudField { ident, ty, .. } = props.soft_delete_field()?;
+    Some(derive_changeset(
+        props,
+        quote!(#schema_path::#table_name::columns::#ident.eq(<#ty as ::rp1::SoftDelete>::#value())),
+    ))
+}
+
+/// The `with_deleted` parameter of the read and list routes, which includes
+/// soft deleted items if the user is allowed to read them.
+pub(crate) struct WithDeleted {
+    /// The query parameter of the route.
+    pub param: TokenStream,
+    /// The parameter passed on to the function loading the items.
+    pub item_param: TokenStream,
+    /// Checks whether the user is allowed to read deleted items.
+    pub check: Option<TokenStream>,
+    /// Excludes the deleted rows from the boxed `query`, unless requested.
+    pub filter: TokenStream,
+}
+
+pub(crate) fn derive_with_deleted(props: &CrudProps) -> Option<WithDeleted> {
+    let ident = &props.ident;
+    let not_deleted = derive_not_deleted(props)?;
+    let check = if props.auth {
+        Some(quote! {
+            if with_deleted && !<#ident as ::rp1::CheckPermissions>::allow_read_deleted(&auth_user) {
+                return Err(::rp1::CrudError::Forbidden);
+            }
+        })
+    } else {
+        None
+    };
+
+    Some(WithDeleted {
+        param: quote!(with_deleted: Option<bool>,),
+        item_param: quote!(with_deleted: bool,),
+        check,
+        filter: quote! {
+            if !with_deleted {
+                query = query.filter(#not_deleted);
+            }
+        },
+    })
+}
+
+/// The `Cache-Control` header of the read and list responses.
+pub(crate) fn derive_cache_control(props: &CrudProps) -> TokenStream {
+    match &props.cache_control {
+        Some(cache_control) => quote!(Some(#cache_control)),
+        None => quote!(None),
+    }
+}
+
+/// The `Last-Modified` time of the given `row`, if the struct has a field
+/// marked as `#[last_modified]`.
+pub(crate) fn derive_last_modified(props: &CrudProps, row: TokenStream) -> TokenStream {
+    match props.last_modified_field() {
+        Some(CrudField { ident, .. }) => quote!(::rp1::LastModified::last_modified(&#row.#ident)),
+        None => quote!(None),
+    }
+}
+
 pub(crate) fn derive_field_list(props: &CrudProps) -> TokenStream {
     let fields = &props
         .fields
@@ -23,7 +383,7 @@ pub(crate) fn derive_field_list(props: &CrudProps) -> TokenStream {
 
     quote! {
         #[allow(non_camel_case_types)]
-        #[derive(::rocket::FromFormField, Debug, PartialEq, Eq, Clone)]
+        #[derive(::rocket::FromFormField, ::serde::Deserialize, Debug, PartialEq, Eq, Clone)]
         pub enum Fields {
             #(#fields),*
         }
@@ -54,3 +414,25 @@ pub(crate) fn derive_field_list(props: &CrudProps) -> TokenStream {
         }
     }
 }
+
+/// Checks at compile time that the fields of a composite primary key are in
+/// the same order as the primary key of the diesel table, as `find` expects
+/// the values of the key in that order.
+pub(crate) fn derive_primary_key_check(props: &CrudProps) -> Option<TokenStream> {
+    if !props.has_composite_key() {
+        return None;
+    }
+    let CrudProps {
+        schema_path,
+        table_name,
+        ..
+    } = props;
+    let keys = props.primary_key_fields().map(|f| &f.ident);
+
+    Some(quote! {
+        #[allow(dead_code)]
+        fn primary_key_order() -> <#schema_path::#table_name::table as ::diesel::Table>::PrimaryKey {
+            (#(#schema_path::#table_name::columns::#keys),*)
+        }
+    })
+}
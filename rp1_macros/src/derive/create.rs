@@ -2,15 +2,21 @@ use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use syn::Ident;
 
-use crate::{derive::common::derive_auth_param, props::CrudProps};
+use crate::{
+    derive::{
+        audit::{derive_audit_actor, derive_audit_write},
+        common::{derive_auth_param, derive_insert_many_returning, derive_insert_returning},
+        events::{derive_events_param, derive_publish, EventsParam},
+        nested::{derive_parent_route, ParentRoute},
+    },
+    props::CrudProps,
+};
 
 pub(crate) fn derive_crud_create(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
     let CrudProps {
         database_struct,
         new_ident,
         ident,
-        table_name,
-        schema_path,
         ..
     } = props;
 
@@ -39,7 +45,128 @@ pub(crate) fn derive_crud_create(props: &CrudProps) -> (TokenStream, Vec<Ident>)
         None
     };
 
+    // like an update, a new item is validated after the hook changed it, and
+    // the permissions are checked again for the changed item
+    let (validate, hook_check) = if props.hooks {
+        (
+            None,
+            Some(quote! {
+                #auth_check
+                #validate
+            }),
+        )
+    } else {
+        (validate, None)
+    };
+    let hook_check_many = hook_check.as_ref().map(|check| {
+        quote! {
+            ::rp1::check_items(&values, |value| {
+                #check
+                Ok(())
+            })?;
+        }
+    });
+
+    let insert_returning = derive_insert_returning(props);
+    let insert_many_returning = derive_insert_many_returning(props);
+    // the hooks and the audit entries are written in the same transaction as
+    // the insert
+    let audit_actor = derive_audit_actor(props);
+    let (insert_returning, insert_many_returning) = if props.hooks || props.audit.is_some() {
+        let (before_hook, before_many_hook, after_hook) = if props.hooks {
+            (
+                Some(quote! {
+                    let mut value = value;
+                    <#ident as ::rp1::CrudHooks>::before_create(&mut value, conn)?;
+                    #hook_check
+                }),
+                Some(quote! {
+                    let mut values = values;
+                    for value in values.iter_mut() {
+                        <#ident as ::rp1::CrudHooks>::before_create(value, conn)?;
+                    }
+                    #hook_check_many
+                }),
+                Some(quote!(<#ident as ::rp1::CrudHooks>::after_create(row, conn)?;)),
+            )
+        } else {
+            (None, None, None)
+        };
+        let audit = derive_audit_write(
+            props,
+            "Create",
+            quote!(row),
+            quote!(None),
+            quote!(Some(row)),
+        );
+        (
+            quote! {
+                conn.transaction(|| -> ::rp1::CrudResult<_> {
+                    #before_hook
+                    let row: #ident = #insert_returning?;
+                    {
+                        let row = &row;
+                        #after_hook
+                        #audit
+                    }
+                    Ok(row)
+                })
+            },
+            quote! {
+                conn.transaction(|| -> ::rp1::CrudResult<_> {
+                    #before_many_hook
+                    let rows: Vec<#ident> = #insert_many_returning?;
+                    for row in rows.iter() {
+                        #after_hook
+                        #audit
+                    }
+                    Ok(rows)
+                })
+            },
+        )
+    } else {
+        (insert_returning, insert_many_returning)
+    };
+    // the created items are published once they are committed
+    let EventsParam {
+        route_param: events_route_param,
+        param: events_param,
+        arg: events_arg,
+    } = derive_events_param(props);
+    let publish = derive_publish(props, "Created", quote!(row.clone()));
+    let publish_many = publish
+        .as_ref()
+        .map(|publish| quote!(for row in rows.iter() { #publish }));
     let new_type_tokens = derive_new_type(&props);
+    // the foreign key of items created using the nested route is taken from
+    // the path, so it can be omitted from the body of each item
+    let nested_create = derive_parent_route(props).map(|ParentRoute { path, key, key_type, check }| {
+        quote! {
+            #[::rocket::post(#path, format = "json", data = "<value>")]
+            async fn nested_create_fn(
+                db: #database_struct,
+                #key: #key_type,
+                value: Result<::rocket::serde::json::Json<::serde_json::Value>, ::rocket::serde::json::Error<'_>>,
+                #events_route_param
+                #auth_param
+            ) -> ::rp1::CrudJsonResult<::rp1::OneOrMany<#ident>>
+            {
+                let mut value = ::rp1::json_body(value)?;
+                #check
+                let key = ::serde_json::to_value(&#key).map_err(|e| ::rp1::CrudError::InvalidItem(e.to_string()))?;
+                let items = match &mut value {
+                    ::serde_json::Value::Array(items) => items.iter_mut().collect(),
+                    item => vec![item],
+                };
+                for item in items {
+                    if let Some(item) = item.as_object_mut() {
+                        item.insert(stringify!(#key).to_owned(), key.clone());
+                    }
+                }
+                create_fn_value(db, value, #events_arg #auth_pass).await
+            }
+        }
+    });
 
     let tokens = quote! {
         #new_type_tokens
@@ -47,6 +174,7 @@ pub(crate) fn derive_crud_create(props: &CrudProps) -> (TokenStream, Vec<Ident>)
         async fn create_fn_help(
             db: #database_struct,
             value: #new_ident,
+            #events_param
             #auth_param
         ) -> ::rp1::CrudJsonResult<#ident>
         {
@@ -54,34 +182,87 @@ pub(crate) fn derive_crud_create(props: &CrudProps) -> (TokenStream, Vec<Ident>)
 
             #validate
 
-            Ok(::rocket::serde::json::Json(db.run(move |conn| {
-                diesel::insert_into(#schema_path::#table_name::table)
-                    .values(&value)
-                    .get_result(conn)
-            }).await?))
+            #audit_actor
+            let row = db.run(move |conn| {
+                #insert_returning
+            }).await?;
+            #publish
+            Ok(::rocket::serde::json::Json(row))
+        }
+
+        /// All items are checked before any of them is inserted, so either all
+        /// or none of the items are created.
+        async fn create_many_fn_help(
+            db: #database_struct,
+            values: Vec<#new_ident>,
+            #events_param
+            #auth_param
+        ) -> ::rp1::CrudResult<Vec<#ident>>
+        {
+            ::rp1::check_items(&values, |value| {
+                #auth_check
+
+                #validate
+
+                Ok(())
+            })?;
+            if values.is_empty() {
+                return Ok(vec![]);
+            }
+            #audit_actor
+
+            let rows = db.run(move |conn| {
+                #insert_many_returning
+            }).await?;
+            #publish_many
+            Ok(rows)
+        }
+
+        /// Creates a single item from an object, or all items of an array.
+        async fn create_fn_value(
+            db: #database_struct,
+            value: ::serde_json::Value,
+            #events_param
+            #auth_param
+        ) -> ::rp1::CrudJsonResult<::rp1::OneOrMany<#ident>>
+        {
+            let items = match ::rp1::OneOrMany::<#new_ident>::from_value(value)? {
+                ::rp1::OneOrMany::One(value) => {
+                    ::rp1::OneOrMany::One(create_fn_help(db, value, #events_arg #auth_pass).await?.into_inner())
+                }
+                ::rp1::OneOrMany::Many(values) => {
+                    ::rp1::OneOrMany::Many(create_many_fn_help(db, values, #events_arg #auth_pass).await?)
+                }
+            };
+
+            Ok(::rocket::serde::json::Json(items))
         }
 
         #[::rocket::post("/", format = "json", data = "<value>")]
         async fn create_fn_json(
             db: #database_struct,
-            value: ::rocket::serde::json::Json<#new_ident>,
+            value: Result<::rocket::serde::json::Json<::serde_json::Value>, ::rocket::serde::json::Error<'_>>,
+            #events_route_param
             #auth_param
-        ) -> ::rp1::CrudJsonResult<#ident>
+        ) -> ::rp1::CrudJsonResult<::rp1::OneOrMany<#ident>>
         {
-            let value = value.into_inner();
-            create_fn_help(db, value, #auth_pass).await
+            let value = ::rp1::json_body(value)?;
+            create_fn_value(db, value, #events_arg #auth_pass).await
         }
 
         #[::rocket::post("/", format = "form", data = "<value>")]
         async fn create_fn_form(
             db: #database_struct,
             value: ::rocket::form::Form<#new_ident>,
+            #events_route_param
             #auth_param
         ) -> ::rp1::CrudJsonResult<#ident>
         {
             let value = value.into_inner();
-            create_fn_help(db, value, #auth_pass).await
+            create_fn_help(db, value, #events_arg #auth_pass).await
         }
+
+        #nested_create
     };
 
     (
@@ -98,7 +279,7 @@ fn derive_new_type(props: &CrudProps) -> TokenStream {
         ident, new_ident, ..
     } = props;
     let table_name = props.table_name.to_string();
-    let fields = props.user_supplied_fields();
+    let fields = props.insertable_fields();
 
     // Only forward serde attributes for now
     let attrs = props.item.attrs
@@ -2,7 +2,19 @@ use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use syn::Ident;
 
-use crate::{derive::common::derive_auth_param, props::CrudProps};
+use crate::{
+    derive::{
+        audit::{derive_audit_actor, derive_audit_write},
+        common::{
+            derive_auth_param, derive_find_deleted_for_update, derive_find_for_update,
+            derive_id_binding, derive_id_params, derive_id_route, derive_if_match_param,
+            derive_item_response, derive_not_deleted, derive_soft_delete_changeset,
+            derive_version_check, derive_write_transaction, ItemResponse,
+        },
+        events::{derive_events_param, derive_publish, EventsParam},
+    },
+    props::CrudProps,
+};
 
 pub(crate) fn derive_crud_delete(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
     let CrudProps {
@@ -10,46 +22,225 @@ pub(crate) fn derive_crud_delete(props: &CrudProps) -> (TokenStream, Vec<Ident>)
         database_struct,
         table_name,
         schema_path,
-        primary_type,
         ..
     } = props;
 
     let auth_param = derive_auth_param(props);
+    let id_route = derive_id_route(props);
+    let id_params = derive_id_params(props);
+    let id_binding = derive_id_binding(props);
+    let transaction = derive_write_transaction(props);
+    let find_row = derive_find_for_update(props);
+    let if_match_param = derive_if_match_param(props);
+    let version_check = derive_version_check(props);
     let auth_check = if props.auth {
         Some(quote! {
-            let row = db.run(move |conn| {
-                #schema_path::#table_name::table
-                    .find(id)
-                    .first::<#ident>(conn)
-            })
-            .await?;
+            let row = #find_row;
             if !<#ident as ::rp1::CheckPermissions>::allow_delete(&row, &auth_user) {
                 return Err(::rp1::CrudError::NotFound);
             }
+            #version_check
+        })
+    } else if props.hooks || props.audit.is_some() || props.events {
+        // the hooks, the audit entry and the event are given the row that is
+        // deleted
+        Some(quote! {
+            let row = #find_row;
+            #version_check
         })
     } else {
-        None
+        // the row is only needed to check its version
+        version_check.map(|version_check| {
+            quote! {
+                if if_match.is_conditional() {
+                    let row = #find_row;
+                    #version_check
+                }
+            }
+        })
     };
 
+    // soft deleted items are marked instead of removed, and can be restored
+    let delete_row = match derive_soft_delete_changeset(props, quote!(deleted)) {
+        Some(changeset) => {
+            let not_deleted = derive_not_deleted(props);
+            quote! {
+                diesel::update(#schema_path::#table_name::table.find(id).filter(#not_deleted))
+                    .set(#changeset)
+                    .execute(conn)?
+            }
+        }
+        None => quote!(diesel::delete(#schema_path::#table_name::table.find(id)).execute(conn)?),
+    };
+    let (before_hook, after_hook) = if props.hooks {
+        (
+            Some(quote!(<#ident as ::rp1::CrudHooks>::before_delete(&row, conn)?;)),
+            Some(quote!(<#ident as ::rp1::CrudHooks>::after_delete(&row, conn)?;)),
+        )
+    } else {
+        (None, None)
+    };
+    let audit_actor = derive_audit_actor(props);
+    let audit = derive_audit_write(
+        props,
+        "Delete",
+        quote!(row),
+        quote!(Some(&row)),
+        quote!(None),
+    );
+    let EventsParam {
+        route_param: events_route_param,
+        ..
+    } = derive_events_param(props);
+    let (delete_result, publish) = match derive_publish(props, "Deleted", quote!(row)) {
+        Some(publish) => (
+            quote!((deleted, row)),
+            Some(quote! {
+                let (deleted, row) = deleted;
+                #publish
+            }),
+        ),
+        None => (quote!(deleted), None),
+    };
+    let restore = derive_restore(props);
+    let mut routes = vec![format_ident!("delete_fn")];
+    if restore.is_some() {
+        routes.push(format_ident!("restore_fn"));
+    }
+
     let tokens = quote! {
-        #[::rocket::delete("/<id>")]
+        #[::rocket::delete(#id_route)]
         async fn delete_fn(
             db: #database_struct,
-            id: #primary_type,
+            #id_params
+            #if_match_param
+            #events_route_param
             #auth_param
         ) -> ::rp1::CrudResult<::serde_json::Value>
         {
-            #auth_check
+            #id_binding
 
+            #audit_actor
             let deleted = db.run(move |conn| {
-                diesel::delete(#schema_path::#table_name::table.find(id)).execute(conn)
+                #transaction(|| -> ::rp1::CrudResult<_> {
+                    #auth_check
+                    #before_hook
+                    let deleted = #delete_row;
+                    // also when the row was not loaded before
+                    if deleted == 0 {
+                        return Err(::rp1::CrudError::NotFound);
+                    }
+                    #after_hook
+                    #audit
+                    Ok(#delete_result)
+                })
             })
             .await?;
+            #publish
             Ok(::serde_json::json!({
                 "deleted": deleted,
             }))
         }
+
+        #restore
+    };
+
+    (tokens, routes)
+}
+
+/// Derives the `POST /<id>/restore` route, which undoes the delete of a soft
+/// deleted item and returns the restored item. Items that are not deleted are
+/// returned unchanged.
+fn derive_restore(props: &CrudProps) -> Option<TokenStream> {
+    let CrudProps {
+        ident,
+        database_struct,
+        table_name,
+        schema_path,
+        ..
+    } = props;
+    let marker = &props.soft_delete_field()?.ident;
+    let changeset = derive_soft_delete_changeset(props, quote!(restored))?;
+
+    let auth_param = derive_auth_param(props);
+    let route = format!("{}/restore", derive_id_route(props));
+    let id_params = derive_id_params(props);
+    let id_binding = derive_id_binding(props);
+    let transaction = derive_write_transaction(props);
+    let find_row = derive_find_deleted_for_update(props);
+    let ItemResponse {
+        result_type,
+        etag,
+        response,
+    } = derive_item_response(props, quote!(#ident));
+    let audit_actor = derive_audit_actor(props);
+    let audit = derive_audit_write(
+        props,
+        "Restore",
+        quote!(row),
+        quote!(Some(&row)),
+        quote!(Some(&restored)),
+    );
+    // only items that were actually restored are published
+    let EventsParam {
+        route_param: events_route_param,
+        ..
+    } = derive_events_param(props);
+    let (unchanged, restored_result, publish) =
+        match derive_publish(props, "Updated", quote!(row.clone())) {
+            Some(publish) => (
+                quote!((row, false)),
+                quote!((restored, true)),
+                Some(quote! {
+                    let (row, restored) = row;
+                    if restored {
+                        #publish
+                    }
+                }),
+            ),
+            None => (quote!(row), quote!(restored), None),
+        };
+    let auth_check = if props.auth {
+        Some(quote! {
+            if !<#ident as ::rp1::CheckPermissions>::allow_restore(&row, &auth_user) {
+                return Err(::rp1::CrudError::NotFound);
+            }
+        })
+    } else {
+        None
     };
 
-    (tokens, vec![format_ident!("delete_fn")])
+    Some(quote! {
+        #[::rocket::post(#route)]
+        async fn restore_fn(
+            db: #database_struct,
+            #id_params
+            #events_route_param
+            #auth_param
+        ) -> #result_type
+        {
+            #id_binding
+
+            #audit_actor
+            let row = db.run(move |conn| {
+                #transaction(|| -> ::rp1::CrudResult<_> {
+                    let row = #find_row;
+                    #auth_check
+                    if !::rp1::SoftDelete::is_deleted(&row.#marker) {
+                        return Ok(#unchanged);
+                    }
+                    diesel::update(#schema_path::#table_name::table.find(id.clone()))
+                        .set(#changeset)
+                        .execute(conn)?;
+                    let restored = #schema_path::#table_name::table.find(id).first::<#ident>(conn)?;
+                    #audit
+                    Ok(#restored_result)
+                })
+            })
+            .await?;
+            #publish
+            #etag
+            Ok(#response)
+        }
+    })
 }
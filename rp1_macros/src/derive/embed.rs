@@ -0,0 +1,375 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::{
+    derive::common::derive_not_deleted,
+    props::{CrudProps, HasMany, Relation},
+};
+
+/// Derives the implementations loading the items of this struct when they are
+/// embedded in other structs, and the `load_embedded` function embedding the
+/// related items of this struct.
+pub(crate) fn derive_embed(props: &CrudProps) -> TokenStream {
+    let crud_embed = derive_crud_embed(props);
+    let belongs_to = props
+        .relations
+        .iter()
+        .map(|relation| derive_belongs_to(props, relation));
+    let load_embedded = derive_load_embedded(props);
+
+    quote! {
+        #crud_embed
+
+        #(#belongs_to)*
+
+        #load_embedded
+    }
+}
+
+/// The generic parameters, trait argument and user parameter of the embed
+/// implementations. Structs without authorization can be embedded for any
+/// user.
+fn derive_auth_generics(props: &CrudProps) -> (Option<TokenStream>, TokenStream, TokenStream) {
+    let ident = &props.ident;
+    if props.auth {
+        (
+            None,
+            quote!(<#ident as ::rp1::CheckPermissions>::AuthUser),
+            quote!(auth_user: &<#ident as ::rp1::CheckPermissions>::AuthUser),
+        )
+    } else {
+        (
+            Some(quote!(<AuthUser>)),
+            quote!(AuthUser),
+            quote!(_auth_user: &AuthUser),
+        )
+    }
+}
+
+/// Derives the statements applying the permission checks of this struct to
+/// the boxed `query`, resulting in the `rows` the user is allowed to read.
+/// Soft deleted rows are never embedded.
+fn derive_load_rows(props: &CrudProps) -> TokenStream {
+    let ident = &props.ident;
+    let not_deleted =
+        derive_not_deleted(props).map(|condition| quote!(let query = query.filter(#condition);));
+    if props.auth {
+        quote! {
+            #not_deleted
+            let query = match <#ident as ::rp1::CheckPermissions>::filter_list(auth_user).apply(query) {
+                Some(query) => query,
+                None => return Ok(vec![]),
+            };
+            let rows = query
+                .load::<#ident>(conn)?
+                .into_iter()
+                .filter(|row| <#ident as ::rp1::CheckPermissions>::allow_read(row, auth_user));
+        }
+    } else {
+        quote! {
+            #not_deleted
+            let rows = query.load::<#ident>(conn)?.into_iter();
+        }
+    }
+}
+
+/// Derives the implementation of `CrudEmbed`, which loads the items of this
+/// struct by their primary key. Structs with a composite primary key cannot
+/// be embedded.
+fn derive_crud_embed(props: &CrudProps) -> Option<TokenStream> {
+    if props.has_composite_key() {
+        return None;
+    }
+
+    let CrudProps {
+        ident,
+        schema_path,
+        table_name,
+        primary_type,
+        ..
+    } = props;
+    let backend = props.backend.diesel_type();
+    let pk = props.primary_key_fields().map(|f| &f.ident).next();
+    let (generics, auth_type, auth_param) = derive_auth_generics(props);
+    let load_rows = derive_load_rows(props);
+
+    Some(quote! {
+        impl #generics ::rp1::CrudEmbed<#auth_type> for #ident {
+            type Backend = #backend;
+            type Key = #primary_type;
+
+            fn load_embedded<C>(
+                conn: &C,
+                keys: Vec<#primary_type>,
+                #auth_param,
+            ) -> ::diesel::QueryResult<Vec<(#primary_type, #ident)>>
+            where
+                C: ::diesel::Connection<Backend = #backend>,
+            {
+                if keys.is_empty() {
+                    return Ok(vec![]);
+                }
+
+                let query = #schema_path::#table_name::table
+                    .filter(#schema_path::#table_name::columns::#pk.eq_any(keys))
+                    .into_boxed();
+                #load_rows
+
+                Ok(rows.map(|row| (row.#pk.clone(), row)).collect())
+            }
+        }
+    })
+}
+
+/// Derives the implementation of `CrudBelongsTo` for a `belongs_to`
+/// relation, which loads the items of this struct by their foreign key.
+fn derive_belongs_to(props: &CrudProps, relation: &Relation) -> TokenStream {
+    let CrudProps {
+        ident,
+        schema_path,
+        table_name,
+        ..
+    } = props;
+    let Relation {
+        path, foreign_key, ..
+    } = relation;
+    let backend = props.backend.diesel_type();
+    let field = props
+        .fields
+        .iter()
+        .find(|f| f.ident == *foreign_key)
+        .expect("foreign key fields are checked when parsing relations");
+    let key_type = field.inner_ty();
+    let (generics, auth_type, auth_param) = derive_auth_generics(props);
+    let load_rows = derive_load_rows(props);
+    let primary_key_names = props.primary_key_fields().map(|f| &f.ident);
+    // rows without a parent are never loaded, but the key is still optional
+    let keyed_rows = if field.is_option {
+        quote!(rows.filter_map(|row| Some((row.#foreign_key.clone()?, row))))
+    } else {
+        quote!(rows.map(|row| (row.#foreign_key.clone(), row)))
+    };
+
+    quote! {
+        impl #generics ::rp1::CrudBelongsTo<#path, #auth_type> for #ident {
+            type Backend = #backend;
+            type Key = #key_type;
+
+            fn load_belonging<C>(
+                conn: &C,
+                keys: Vec<#key_type>,
+                #auth_param,
+            ) -> ::diesel::QueryResult<Vec<(#key_type, #ident)>>
+            where
+                C: ::diesel::Connection<Backend = #backend>,
+            {
+                if keys.is_empty() {
+                    return Ok(vec![]);
+                }
+
+                let mut query = #schema_path::#table_name::table
+                    .filter(#schema_path::#table_name::columns::#foreign_key.eq_any(keys))
+                    .into_boxed();
+                #(
+                    query = query.then_order_by(#schema_path::#table_name::columns::#primary_key_names.asc());
+                )*
+                #load_rows
+
+                Ok(#keyed_rows.collect())
+            }
+        }
+    }
+}
+
+/// Derives the `Embed` enum of relations that can be embedded, the
+/// `EmbedKeys` struct with the keys of the related items of a single item,
+/// and the `load_embedded` function loading the related items for a list of
+/// items.
+fn derive_load_embedded(props: &CrudProps) -> Option<TokenStream> {
+    if !props.has_embeds() {
+        return None;
+    }
+
+    let CrudProps {
+        ident,
+        partial_ident,
+        primary_type,
+        ..
+    } = props;
+    let backend = props.backend.diesel_type();
+    let names = props.embed_names().collect::<Vec<_>>();
+    let pk = props.primary_key_fields().map(|f| &f.ident).next();
+    let auth_type = if props.auth {
+        quote!(<#ident as ::rp1::CheckPermissions>::AuthUser)
+    } else {
+        quote!(())
+    };
+
+    let mut key_fields = vec![];
+    let mut row_keys = vec![];
+    let mut partial_keys = vec![];
+    let mut loads = vec![];
+    for Relation {
+        name,
+        path,
+        foreign_key,
+    } in props.relations.iter()
+    {
+        let field = props
+            .fields
+            .iter()
+            .find(|f| f.ident == *foreign_key)
+            .expect("foreign key fields are checked when parsing relations");
+        let key_type = field.inner_ty();
+        key_fields.push(quote!(#name: Option<#key_type>));
+        row_keys.push(if field.is_option {
+            quote!(#name: row.#foreign_key.clone())
+        } else {
+            quote!(#name: Some(row.#foreign_key.clone()))
+        });
+        partial_keys.push(quote!(#name: row.#foreign_key.clone()));
+        loads.push(quote! {
+            Embed::#name => {
+                let keys = keys.iter().map(|k| k.#name.clone()).collect::<Vec<_>>();
+                let items = <#path as ::rp1::CrudEmbed<EmbedUser>>::load_embedded(
+                    conn,
+                    ::rp1::embed_keys(&keys),
+                    auth_user,
+                )?;
+                ::rp1::embed_one(&keys, items)?
+            }
+        });
+    }
+    for HasMany { name, path } in props.has_many.iter() {
+        key_fields.push(quote!(#name: Option<#primary_type>));
+        row_keys.push(quote!(#name: Some(row.#pk.clone())));
+        partial_keys.push(quote!(#name: row.#pk.clone()));
+        loads.push(quote! {
+            Embed::#name => {
+                let keys = keys.iter().map(|k| k.#name.clone()).collect::<Vec<_>>();
+                let items = <#path as ::rp1::CrudBelongsTo<#ident, EmbedUser>>::load_belonging(
+                    conn,
+                    ::rp1::embed_keys(&keys),
+                    auth_user,
+                )?;
+                ::rp1::embed_many(&keys, items)?
+            }
+        });
+    }
+    // the list endpoint loads partial rows, of which the key fields are
+    // always selected when embedding
+    let partial_keys = if props.list && props.partials {
+        Some(quote! {
+            impl From<&#partial_ident> for EmbedKeys {
+                fn from(row: &#partial_ident) -> EmbedKeys {
+                    EmbedKeys {
+                        #(#partial_keys,)*
+                    }
+                }
+            }
+        })
+    } else {
+        None
+    };
+    let key_field = if props.list && props.partials {
+        let key_field_names = props.relations.iter().map(|r| &r.foreign_key).chain(
+            props
+                .has_many
+                .iter()
+                .map(|_| pk.expect("has a primary key")),
+        );
+        Some(quote! {
+            impl Embed {
+                /// The field containing the key of the related items, which
+                /// is selected when embedding the related items.
+                fn key_field(&self) -> Fields {
+                    match self {
+                        #(Embed::#names => Fields::#key_field_names,)*
+                    }
+                }
+            }
+        })
+    } else {
+        None
+    };
+
+    Some(quote! {
+        /// The user for which the permission checks of embedded items are
+        /// applied.
+        type EmbedUser = #auth_type;
+
+        #[doc(hidden)]
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Embed {
+            #(#names),*
+        }
+
+        #key_field
+
+        impl ::std::fmt::Display for Embed {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                match self {
+                    #(Embed::#names => write!(f, stringify!(#names)),)*
+                }
+            }
+        }
+
+        impl ::std::str::FromStr for Embed {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    #(stringify!(#names) => Ok(Embed::#names),)*
+                    _ => Err(format!("Unknown relation '{}'", s)),
+                }
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for Embed {
+            fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let relation = String::deserialize(deserializer)?;
+                relation.parse().map_err(::serde::de::Error::custom)
+            }
+        }
+
+        #[doc(hidden)]
+        pub struct EmbedKeys {
+            #(#key_fields,)*
+        }
+
+        impl From<&#ident> for EmbedKeys {
+            fn from(row: &#ident) -> EmbedKeys {
+                EmbedKeys {
+                    #(#row_keys,)*
+                }
+            }
+        }
+
+        #partial_keys
+
+        /// Load the related items in `embed` for the items with the given
+        /// keys, resulting in the embedded items of every item.
+        fn load_embedded<C>(
+            conn: &C,
+            embed: &[Embed],
+            keys: &[EmbedKeys],
+            auth_user: &EmbedUser,
+        ) -> ::diesel::QueryResult<Vec<::serde_json::Map<String, ::serde_json::Value>>>
+        where
+            C: ::diesel::Connection<Backend = #backend>,
+        {
+            let mut embedded = vec![::serde_json::Map::new(); keys.len()];
+            for relation in embed {
+                let values = match relation {
+                    #(#loads)*
+                };
+                for (map, value) in embedded.iter_mut().zip(values) {
+                    map.insert(relation.to_string(), value);
+                }
+            }
+
+            Ok(embedded)
+        }
+    })
+}
@@ -0,0 +1,90 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::Ident;
+
+use crate::{derive::common::derive_auth_param, props::CrudProps};
+
+/// The `EventChannel` passed to the routes changing items, to which the
+/// changed items are published once the transaction is committed. All fields
+/// are `None` if events are disabled.
+pub(crate) struct EventsParam {
+    /// The state guard of the route.
+    pub route_param: Option<TokenStream>,
+    /// The parameter of the helper functions of the routes.
+    pub param: Option<TokenStream>,
+    /// The argument passing the channel on to a helper function.
+    pub arg: Option<TokenStream>,
+}
+
+pub(crate) fn derive_events_param(props: &CrudProps) -> EventsParam {
+    let ident = &props.ident;
+    if !props.events {
+        return EventsParam {
+            route_param: None,
+            param: None,
+            arg: None,
+        };
+    }
+
+    EventsParam {
+        route_param: Some(quote!(events: &::rocket::State<::rp1::EventChannel<#ident>>,)),
+        param: Some(quote!(events: &::rp1::EventChannel<#ident>,)),
+        arg: Some(quote!(events,)),
+    }
+}
+
+/// Publishes the `item` as an event of the given `kind`.
+pub(crate) fn derive_publish(
+    props: &CrudProps,
+    kind: &str,
+    item: TokenStream,
+) -> Option<TokenStream> {
+    if !props.events {
+        return None;
+    }
+    let kind = format_ident!("{}", kind);
+    Some(quote!(events.publish(::rp1::EventKind::#kind, #item);))
+}
+
+/// Derives the `GET /events` route streaming the changes of the items that
+/// the user is allowed to read, and the `events_fairing` managing the channel
+/// of the struct.
+pub(crate) fn derive_crud_events(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
+    let ident = &props.ident;
+    if !props.events {
+        return (quote!(), vec![]);
+    }
+
+    let auth_param = derive_auth_param(props);
+    let allow = if props.auth {
+        quote!(move |item: &#ident| <#ident as ::rp1::CheckPermissions>::allow_read(item, &auth_user))
+    } else {
+        quote!(|_: &#ident| true)
+    };
+    let fairing_name = format!("{} events", ident);
+
+    let tokens = quote! {
+        #[::rocket::get("/events")]
+        fn events_fn(
+            events: &::rocket::State<::rp1::EventChannel<#ident>>,
+            last_event_id: ::rp1::LastEventId,
+            #auth_param
+        ) -> ::rocket::response::stream::EventStream<
+            impl ::rocket::futures::Stream<Item = ::rocket::response::stream::Event>,
+        >
+        {
+            ::rp1::event_stream(events, last_event_id, #allow)
+        }
+
+        impl #ident {
+            /// Returns the fairing managing the channel to which the changes
+            /// of the items are published, which must be attached to use the
+            /// routes.
+            pub fn events_fairing() -> ::rocket::fairing::AdHoc {
+                ::rp1::EventChannel::<#ident>::fairing(#fairing_name)
+            }
+        }
+    };
+
+    (tokens, vec![format_ident!("events_fn")])
+}
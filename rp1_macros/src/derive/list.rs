@@ -2,7 +2,16 @@ use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use syn::{Field, Ident, ItemStruct};
 
-use crate::{derive::common::derive_auth_param, props::CrudProps};
+use crate::{
+    derive::{
+        common::{
+            derive_auth_param, derive_cache_control, derive_last_modified, derive_with_deleted,
+            WithDeleted,
+        },
+        nested::{derive_parent_route, ParentRoute},
+    },
+    props::{Backend, CrudProps, Relation},
+};
 
 pub(crate) fn derive_crud_list(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
     let CrudProps {
@@ -39,10 +48,56 @@ pub(crate) fn derive_crud_list(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
     } else {
         None
     };
-    let rocket_attr = if partials {
-        quote!(#[::rocket::get("/?<sort>&<offset>&<limit>&<filter>&<include>&<exclude>")])
+    let has_search = props.has_search();
+    let has_embeds = props.has_embeds();
+    let mut query_params = vec!["sort", "offset", "limit", "after", "filter"];
+    if partials {
+        query_params.extend(&["include", "exclude"]);
+    }
+    if has_search {
+        query_params.push("q");
+    }
+    if has_embeds {
+        query_params.push("embed");
+    }
+    // soft deleted items are only listed when they are explicitly requested
+    let with_deleted = derive_with_deleted(props);
+    if with_deleted.is_some() {
+        query_params.push("with_deleted");
+    }
+    let (
+        with_deleted_param,
+        with_deleted_item_param,
+        with_deleted_arg,
+        with_deleted_check,
+        deleted_filter,
+    ) = match with_deleted {
+        Some(WithDeleted {
+            param,
+            item_param,
+            check,
+            filter,
+        }) => (
+            Some(param),
+            Some(item_param),
+            Some(quote!(with_deleted.unwrap_or(false),)),
+            check,
+            Some(filter),
+        ),
+        None => (None, None, None, None, None),
+    };
+    let route = format!(
+        "/?{}",
+        query_params
+            .iter()
+            .map(|p| format!("<{}>", p))
+            .collect::<Vec<_>>()
+            .join("&")
+    );
+    let (search_param, search_arg) = if has_search {
+        (Some(quote!(q: Option<String>,)), Some(quote!(q,)))
     } else {
-        quote!(#[::rocket::get("/?<sort>&<offset>&<limit>&<filter>")])
+        (None, None)
     };
     let partial_params = if partials {
         Some(quote! {
@@ -52,13 +107,66 @@ pub(crate) fn derive_crud_list(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
     } else {
         None
     };
+    let partial_args = if partials {
+        Some(quote!(include, exclude,))
+    } else {
+        None
+    };
+    let auth_arg = if props.auth {
+        Some(quote!(auth_user,))
+    } else {
+        None
+    };
+    let (embed_param, embed_parse, embed_item_param, embed_arg) = if has_embeds {
+        (
+            Some(quote!(embed: Vec<String>,)),
+            Some(quote! {
+                let embed = ::rp1::parse_embed::<Embed>(&embed)?;
+            }),
+            Some(quote!(embed: Vec<Embed>,)),
+            Some(quote!(embed,)),
+        )
+    } else {
+        (None, None, None, None)
+    };
     let select_statements = if partials {
         derive_select_statement(&props)
     } else {
         quote! { #schema_path::#table_name::all_columns }
     };
+    let keyset_field_names = props
+        .keyset_fields()
+        .map(|f| f.ident.clone())
+        .collect::<Vec<_>>();
+    // the fields containing the keys of embedded items are selected as well
+    let embed_key_fields = if has_embeds {
+        Some(quote! {
+            for relation in embed.iter() {
+                let field = relation.key_field();
+                if !selected.contains(&field) {
+                    selected.push(field);
+                }
+            }
+        })
+    } else {
+        None
+    };
     let selected_fields_stmt = if partials {
-        Some(quote! { let selected = Fields::selected(include, exclude); let selected_out = selected.clone(); })
+        // the key fields are always selected so a cursor can be created
+        Some(quote! {
+            let mut selected = Fields::selected(include, exclude);
+            let selected_out = selected.clone();
+            for key in keys.iter() {
+                let field = match key.field.as_str() {
+                    #(stringify!(#keyset_field_names) => Fields::#keyset_field_names,)*
+                    _ => continue,
+                };
+                if !selected.contains(&field) {
+                    selected.push(field);
+                }
+            }
+            #embed_key_fields
+        })
     } else {
         None
     };
@@ -75,10 +183,11 @@ pub(crate) fn derive_crud_list(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
         None
     };
 
-    let sortable_field_names = props
-        .sortable_fields()
-        .map(|f| f.ident.clone())
-        .collect::<Vec<_>>();
+    let sort_orders = derive_sort_orders(
+        props,
+        |name| quote!(#schema_path::#table_name::columns::#name),
+        |order| quote!(query = query.then_order_by(#order);),
+    );
 
     let filterable_fields = props.filterable_fields().collect::<Vec<_>>();
     let filter_field_names = filterable_fields
@@ -107,74 +216,434 @@ pub(crate) fn derive_crud_list(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
         .iter()
         .map(|f| {
             let field_name = f.ident.clone();
-            // parse is not implemented for Option<T>, so we add a special case for it
-            if f.is_option {
+            // text fields support additional operators, which are parsed
+            // separately as they are not available for other types
+            if f.is_text {
+                let parse_fn = if f.is_option {
+                    quote!(try_parse_text_option)
+                } else {
+                    quote!(try_parse_text)
+                };
+                quote! {
+                    stringify!(#field_name) => {
+                        self.#field_name.push(::rp1::FilterOperator::#parse_fn(operator, value)?);
+                    }
+                }
+            } else if f.is_option {
+                // parse is not implemented for Option<T>, so we add a special case for it
                 quote! {
                     stringify!(#field_name) => {
                         if value == "" {
-                            match ::rp1::FilterOperator::from_none(field_operator) {
-                                Ok(v) => self.spec.#field_name.push(v),
-                                Err(e) => self.errors.push(::rocket::form::Error::custom(e)),
-                            }
+                            self.#field_name.push(::rp1::FilterOperator::from_none(operator)?);
                         } else {
-                            match ::rp1::FilterOperator::try_parse_option(field_operator, value) {
-                                Ok(v) => self.spec.#field_name.push(v),
-                                Err(e) => self.errors.push(::rocket::form::Error::custom(e)),
-                            }
+                            self.#field_name.push(::rp1::FilterOperator::try_parse_option(operator, value)?);
                         }
                     }
                 }
             } else {
                 quote! {
                     stringify!(#field_name) => {
-                        match ::rp1::FilterOperator::try_parse(field_operator, value) {
-                            Ok(v) => self.spec.#field_name.push(v),
-                            Err(e) => self.errors.push(::rocket::form::Error::custom(e)),
-                        }
+                        self.#field_name.push(::rp1::FilterOperator::try_parse(operator, value)?);
                     }
                 }
             }
         })
         .collect::<Vec<_>>();
 
-    let filter_apply_stmts = filterable_fields
+    let filter_condition_stmts = filterable_fields
         .iter()
         .map(|f| {
             let ident = &f.ident;
+            let text_filters = if f.is_text {
+                // `LIKE` ignores case on SQLite and MySQL, so these use a
+                // case-sensitive operator instead
+                let like = match props.backend {
+                    Backend::Postgres => quote!(columns::#ident.like(val.clone()).escape('\\')),
+                    Backend::Sqlite if f.is_option => quote! {
+                        ::rp1::helper::glob(columns::#ident, val.as_deref().map(::rp1::like_to_glob))
+                    },
+                    Backend::Sqlite => quote!(::rp1::helper::glob(columns::#ident, ::rp1::like_to_glob(val))),
+                    Backend::Mysql => quote!(::rp1::helper::like_binary(columns::#ident, val.clone())),
+                };
+                quote! {
+                    FilterOperator::Like(val) => Box::new(#like),
+                    FilterOperator::ILike(val) => Box::new(::rp1::helper::lower(columns::#ident).like(val.clone()).escape('\\')),
+                }
+            } else {
+                quote! {
+                    FilterOperator::Like(_) | FilterOperator::ILike(_) => {
+                        unreachable!("text filters are only parsed for text fields")
+                    }
+                }
+            };
+            // comparisons with `NULL` never match in SQL, so these are
+            // replaced by `IS NULL` and `IS NOT NULL` for nullable fields
+            let null_filters = if f.is_option {
+                quote! {
+                    FilterOperator::Eq(None) => Box::new(columns::#ident.is_null()),
+                    FilterOperator::Ne(None) => Box::new(columns::#ident.is_not_null()),
+                    FilterOperator::Ne(val) => Box::new(columns::#ident.ne(val.clone()).or(columns::#ident.is_null())),
+                    FilterOperator::EqAny(val) if val.contains(&None) => {
+                        Box::new(columns::#ident.eq_any(val.clone()).or(columns::#ident.is_null()))
+                    }
+                    FilterOperator::IsNull(true) => Box::new(columns::#ident.is_null()),
+                    FilterOperator::IsNull(false) => Box::new(columns::#ident.is_not_null()),
+                }
+            } else {
+                quote! {
+                    FilterOperator::IsNull(_) => {
+                        unreachable!("null filters are only parsed for nullable fields")
+                    }
+                }
+            };
 
             quote! {
-                for op in filter.#ident.iter() {
-                    use ::rp1::FilterOperator;
-                    use #schema_path::#table_name::columns;
-                    query = match op {
-                        FilterOperator::Eq(val) => query.filter(columns::#ident.eq(val)),
-                        FilterOperator::Ne(val) => query.filter(columns::#ident.ne(val)),
-                        FilterOperator::Gt(val) => query.filter(columns::#ident.gt(val)),
-                        FilterOperator::Ge(val) => query.filter(columns::#ident.ge(val)),
-                        FilterOperator::Lt(val) => query.filter(columns::#ident.lt(val)),
-                        FilterOperator::Le(val) => query.filter(columns::#ident.le(val)),
-                        FilterOperator::EqAny(val) => query.filter(columns::#ident.eq_any(val)),
-                    };
+                for op in self.#ident.iter() {
+                    conditions.push(match op {
+                        #null_filters
+                        FilterOperator::Eq(val) => Box::new(columns::#ident.eq(val.clone())),
+                        FilterOperator::Ne(val) => Box::new(columns::#ident.ne(val.clone())),
+                        FilterOperator::Gt(val) => Box::new(columns::#ident.gt(val.clone())),
+                        FilterOperator::Ge(val) => Box::new(columns::#ident.ge(val.clone())),
+                        FilterOperator::Lt(val) => Box::new(columns::#ident.lt(val.clone())),
+                        FilterOperator::Le(val) => Box::new(columns::#ident.le(val.clone())),
+                        FilterOperator::EqAny(val) => Box::new(columns::#ident.eq_any(val.clone())),
+                        #text_filters
+                    });
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let backend = props.backend.diesel_type();
+
+    let pagination_param = if props.pagination {
+        Some(quote! {
+            uri: &::rocket::http::uri::Origin<'_>,
+        })
+    } else {
+        None
+    };
+    // requests without a uri to link to, like a search, get an unlinked page
+    let pagination_item_param = if props.pagination {
+        Some(quote! {
+            uri: Option<&::rocket::http::uri::Origin<'_>>,
+        })
+    } else {
+        None
+    };
+    let pagination_arg = if props.pagination {
+        Some(quote!(Some(uri),))
+    } else {
+        None
+    };
+    let result_type = derive_result_type(props);
+    // when pagination is enabled we also count all rows matching the filters,
+    // the filters are applied in separate blocks as the auth filter shadows
+    // the `filter` variable
+    let search_filter = if has_search {
+        Some(quote! {
+            if let Some(condition) = q.as_deref().and_then(search_condition) {
+                query = query.filter(condition);
+            }
+        })
+    } else {
+        None
+    };
+    // the nested list route only lists the items belonging to the parent
+    let parent = derive_parent_route(props);
+    let (parent_item_param, parent_arg, parent_filter) = match &parent {
+        Some(ParentRoute { key, key_type, .. }) => (
+            Some(quote!(parent: Option<#key_type>,)),
+            Some(quote!(None,)),
+            Some(quote! {
+                if let Some(parent) = parent.clone() {
+                    query = query.filter(#schema_path::#table_name::columns::#key.eq(parent));
+                }
+            }),
+        ),
+        None => (None, None, None),
+    };
+    // items linked through a many-to-many relation are selected by a
+    // condition on their key, which is built by the struct declaring the
+    // relation
+    let (linked_item_param, linked_arg, linked_filter) = if props.has_composite_key() {
+        (None, None, None)
+    } else {
+        let pk = props.primary_key_fields().map(|f| &f.ident);
+        let table = quote!(#schema_path::#table_name::table);
+        (
+            Some(quote!(linked: Option<::rp1::LinkedCondition<#table, #backend>>,)),
+            Some(quote!(None,)),
+            Some(quote! {
+                if let Some(condition) = &linked {
+                    query = query.filter(condition(#schema_path::#table_name::columns::#(#pk)*));
+                }
+            }),
+        )
+    };
+    let nested_list = parent.map(|ParentRoute { path, key, key_type, check }| {
+        let route = format!("{}{}", path, &route[1..]);
+        quote! {
+            #[::rocket::get(#route)]
+            async fn nested_list_fn(
+                db: #database_struct,
+                #key: #key_type,
+                sort: Result<Vec<::rp1::SortSpec<SortableFields>>, ::rocket::form::Errors<'_>>,
+                filter: Result<::rp1::FilterExpression<#filter_ident>, ::rocket::form::Errors<'_>>,
+                offset: Option<i64>,
+                limit: Option<i64>,
+                after: Option<String>,
+                #partial_params
+                #search_param
+                #embed_param
+                #with_deleted_param
+                #pagination_param
+                #auth_param
+            ) -> #result_type
+            {
+                let sort = sort.map_err(|e| ::rp1::CrudError::InvalidSortSpec(e.to_string()))?;
+                let filter = filter.map_err(|e| ::rp1::CrudError::InvalidFilterSpec(e.to_string()))?;
+                #embed_parse
+                #check
+
+                list_items(db, sort, filter, offset, limit, after, #partial_args #search_arg Some(#key), #linked_arg #embed_arg #with_deleted_arg #pagination_arg #auth_arg).await
+            }
+        }
+    });
+    // the count query is built after the sort keys are consumed by the list
+    // query, so the conditions of related sort keys are built beforehand
+    let count_join_conditions = if props.pagination {
+        Some(quote! {
+            let join_conditions = sort
+                .iter()
+                .filter_map(|s| s.field.join_condition())
+                .collect::<Vec<_>>();
+        })
+    } else {
+        None
+    };
+    let load_stmts = if props.pagination {
+        quote! {
+            let count_query = {
+                let mut query = #schema_path::#table_name::table
+                    .select(::diesel::dsl::count_star())
+                    .into_boxed();
+                for condition in join_conditions {
+                    query = query.filter(condition);
+                }
+                if let Some(condition) = filter.condition() {
+                    query = query.filter(condition);
+                }
+                #search_filter
+                #parent_filter
+                #linked_filter
+                #deleted_filter
+
+                #auth_filter
+
+                query
+            };
+
+            query.zip(count_query).map(|(query, count_query)| -> ::diesel::QueryResult<_> {
+                Ok((query.load(conn)?, count_query.get_result::<i64>(conn)?))
+            })
+        }
+    } else {
+        quote! {
+            query.map(|q| q.load(conn))
+        }
+    };
+    let loaded_type = if props.pagination {
+        quote!((#partial_result_type, i64))
+    } else {
+        quote!(#partial_result_type)
+    };
+    let loaded_pattern = if props.pagination {
+        quote!((results, total))
+    } else {
+        quote!(results)
+    };
+    // the embedded items are loaded using the same connection, for the keys
+    // in the loaded rows
+    let (load_stmts, loaded_pattern) = if has_embeds {
+        let rows = if props.pagination {
+            quote!(loaded.0)
+        } else {
+            quote!(loaded)
+        };
+        let auth_user = if props.auth {
+            quote!(&auth_user)
+        } else {
+            quote!(&())
+        };
+        (
+            quote! {
+                let loaded: Option<::diesel::QueryResult<#loaded_type>> = { #load_stmts };
+                loaded.map(|loaded| -> ::diesel::QueryResult<_> {
+                    let loaded = loaded?;
+                    let keys = #rows.iter().map(EmbedKeys::from).collect::<Vec<_>>();
+                    let embedded = load_embedded(conn, &embed, &keys, #auth_user)?;
+                    Ok((loaded, embedded))
+                })
+            },
+            quote!((#loaded_pattern, embedded): (#loaded_type, Vec<::serde_json::Map<String, ::serde_json::Value>>)),
+        )
+    } else {
+        (load_stmts, quote!(#loaded_pattern: #loaded_type))
+    };
+    let embed_result_map = if has_embeds {
+        Some(quote! {
+            let results = results
+                .into_iter()
+                .zip(embedded)
+                .map(|(item, embedded)| ::rp1::Embedded::new(item, embedded))
+                .collect::<Vec<_>>();
+        })
+    } else {
+        None
+    };
+    let output_stmt = if props.pagination {
+        quote! {
+            let output = match uri {
+                Some(uri) => ::rp1::Page::new(results, total, offset, limit, cursor, uri),
+                None => ::rp1::Page::unlinked(results, total, offset, limit, cursor),
+            };
+            let etag = ::rp1::content_etag(&output);
+        }
+    } else {
+        quote! {
+            let etag = ::rp1::content_etag(&(&results, &cursor));
+            let output = ::rp1::WithCursor {
+                inner: ::rocket::serde::json::Json(results),
+                cursor,
+            };
+        }
+    };
+    let cache_control = derive_cache_control(props);
+    let result_stmt = quote! {
+        #output_stmt
+        Ok(::rp1::Cacheable::new(output, etag)
+            .last_modified(last_modified)
+            .cache_control(#cache_control))
+    };
+    // the most recent modification of the items in the list
+    let last_modified_row = derive_last_modified(props, quote!(row));
+    let last_modified_stmt = if props.last_modified_field().is_some() {
+        quote! {
+            let last_modified = results.iter().filter_map(|row| #last_modified_row).max();
+        }
+    } else {
+        quote!(let last_modified = None;)
+    };
+    let keyset = derive_keyset(props);
+    let linked = derive_linked(props);
+    let search = derive_search(props);
+    let full_text_search = derive_full_text_search(props);
+    let relevance_order = if has_search {
+        let order = derive_relevance_order(props);
+        Some(quote! {
+            SortableFields::relevance => {
+                if let Some(q) = q.as_deref() {
+                    #order
+                }
+            }
+        })
+    } else {
+        None
+    };
+    let sortable_fields = derive_sortable_fields(props);
+    let related = derive_related(props);
+    let default_sort = if props.default_sort.is_empty() {
+        None
+    } else {
+        let default_sort_specs = props.default_sort.iter().map(|key| {
+            let field = &key.field;
+            let direction = if key.descending {
+                quote!(::rp1::SortDirection::Desc)
+            } else {
+                quote!(::rp1::SortDirection::Asc)
+            };
+            let nulls = match key.nulls_first {
+                Some(true) => quote!(Some(::rp1::NullsOrder::First)),
+                Some(false) => quote!(Some(::rp1::NullsOrder::Last)),
+                None => quote!(None),
+            };
+            let case_insensitive = key.case_insensitive;
+            quote! {
+                ::rp1::SortSpec {
+                    field: SortableFields::#field,
+                    direction: #direction,
+                    nulls: #nulls,
+                    case_insensitive: #case_insensitive,
                 }
             }
+        });
+        Some(quote! {
+            let sort = if sort.is_empty() {
+                vec![#(#default_sort_specs),*]
+            } else {
+                sort
+            };
+        })
+    };
+    let primary_key_names = props
+        .primary_key_fields()
+        .map(|f| f.ident.clone())
+        .collect::<Vec<_>>();
+    let relation_names = props
+        .relations
+        .iter()
+        .map(|r| r.name.clone())
+        .collect::<Vec<_>>();
+    let relation_paths = props
+        .relations
+        .iter()
+        .map(|r| r.path.clone())
+        .collect::<Vec<_>>();
+    let relation_foreign_keys = props
+        .relations
+        .iter()
+        .map(|r| r.foreign_key.clone())
+        .collect::<Vec<_>>();
+    let relation_filter_fields = props
+        .relations
+        .iter()
+        .map(|Relation { name, path, .. }| {
+            quote! {
+                #name: Option<::rp1::FilterExpression<<#path as ::rp1::CrudFilterSpec>::FilterSpecType>>
+            }
         })
         .collect::<Vec<_>>();
+    let relation_filter_fns = if props.relations.is_empty() {
+        None
+    } else {
+        Some(quote! {
+            fn is_relation(name: &str) -> bool {
+                matches!(name, #(stringify!(#relation_names))|*)
+            }
+
+            fn push_related(&mut self, relation: &str, keys: &[&str], value: &str) -> Result<(), ::rp1::ParseError> {
+                match relation {
+                    #(stringify!(#relation_names) => self.#relation_names.get_or_insert_with(Default::default).push(keys, value),)*
+                    _ => Err(::rp1::ParseError::UnknownField(relation.to_owned())),
+                }
+            }
+        })
+    };
 
     let tokens = quote! {
         #partial_struct
 
-        #[doc(hidden)]
-        #[allow(non_camel_case_types)]
-        #[derive(::rocket::FromFormField, Debug)]
-        pub enum SortableFields {
-            #(#sortable_field_names),*
-        }
+        #sortable_fields
+
+        #keyset
 
         use ::rocket::request::{self, Request, FromRequest};
 
         #[derive(Debug)]
         pub struct #filter_ident {
-            #(#filter_fields),*
+            #(#filter_fields,)*
+            #(#relation_filter_fields,)*
         }
 
         impl ::rp1::CrudFilterSpec for #ident {
@@ -184,170 +653,787 @@ pub(crate) fn derive_crud_list(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
         impl Default for #filter_ident {
             fn default() -> #filter_ident {
                 #filter_ident {
-                    #(#filter_field_names: vec![]),*
+                    #(#filter_field_names: vec![],)*
+                    #(#relation_names: None,)*
                 }
             }
         }
 
-        #[doc(hidden)]
-        pub struct FilterSpecContext<'r> {
-            spec: #filter_ident,
-            errors: ::rocket::form::Errors<'r>,
-        }
-
-        impl<'r> FilterSpecContext<'r> {
-            fn push(&mut self, mut field_name: rocket::form::name::NameView<'r>, value: &'r str) {
-                use std::convert::{TryFrom, TryInto};
-
-                let field_filtered = match field_name.key() {
-                    Some(k) => k,
-                    None => {
-                        self.errors.push(::rocket::form::Error::custom(::rp1::ParseError::UnknownField(field_name.to_string())));
-                        return;
-                    },
-                };
-                field_name.shift();
-                let field_operator = field_name.key().map(|k| k.as_str()).unwrap_or("eq");
+        impl ::rp1::FilterSpec for #filter_ident {
+            type Table = #schema_path::#table_name::table;
+            type Backend = #backend;
 
-                match field_filtered.as_str() {
+            fn push(&mut self, field: &str, operator: &str, value: &str) -> Result<(), ::rp1::ParseError> {
+                match field {
                     #(#filter_parse_stmts,)*
-                    _ => {
-                        self.errors.push(::rocket::form::Error::custom(::rp1::ParseError::UnknownField(field_filtered.as_str().to_owned())));
-                    },
+                    _ => return Err(::rp1::ParseError::UnknownField(field.to_owned())),
                 };
-            }
-        }
-
-        #[rocket::async_trait]
-        impl<'r> ::rocket::form::FromForm<'r> for #filter_ident {
-            type Context = FilterSpecContext<'r>;
 
-            fn init(opts: ::rocket::form::Options) -> Self::Context {
-                FilterSpecContext {
-                    spec: Default::default(),
-                    errors: ::rocket::form::Errors::new(),
-                }
+                Ok(())
             }
 
-            fn push_value(ctxt: &mut Self::Context, field: ::rocket::form::ValueField<'r>) {
-                ctxt.push(field.name, field.value);
-            }
+            #relation_filter_fns
 
-            async fn push_data(ctxt: &mut Self::Context, field: ::rocket::form::DataField<'r, '_>) {
-                use rocket::data::ToByteUnit;
+            fn condition(&self) -> Option<::rp1::FilterCondition<Self::Table, Self::Backend>> {
+                use ::rp1::FilterOperator;
+                use #schema_path::#table_name::columns;
 
-                let limit = 256.kibibytes();
-                let bytes = match field.data.open(limit).into_bytes().await {
-                    Ok(b) => b,
-                    Err(e) => {
-                        ctxt.errors.push(e.into());
-                        return;
-                    },
-                };
-                if !bytes.is_complete() {
-                    ctxt.errors.push(rocket::form::error::ErrorKind::from((None, Some(limit))).into());
-                    return;
-                }
-                let bytes = bytes.into_inner();
-                let bytes = rocket::request::local_cache!(field.request, bytes);
-                let data = match std::str::from_utf8(bytes) {
-                    Ok(d) => d.into(),
-                    Err(e) => {
-                        ctxt.errors.push(e.into());
-                        return;
-                    },
-                };
-                ctxt.push(field.name, data);
-            }
+                let mut conditions: Vec<::rp1::FilterCondition<Self::Table, Self::Backend>> = vec![];
+                #(#filter_condition_stmts)*
+                #(
+                    if let Some(filter) = &self.#relation_names {
+                        conditions.extend(<#relation_paths as ::rp1::CrudRelated>::related_condition(
+                            columns::#relation_foreign_keys,
+                            filter,
+                        ));
+                    }
+                )*
 
-            fn finalize(ctxt: Self::Context) -> ::rocket::form::Result<'r, Self> {
-                if ctxt.errors.is_empty() {
-                    Ok(ctxt.spec)
-                } else {
-                    Err(ctxt.errors)
-                }
+                ::rp1::all_conditions(conditions)
             }
         }
 
+        #related
 
-        #rocket_attr
+        #[::rocket::get(#route)]
         async fn list_fn(
             db: #database_struct,
             sort: Result<Vec<::rp1::SortSpec<SortableFields>>, ::rocket::form::Errors<'_>>,
-            filter: Result<#filter_ident, ::rocket::form::Errors<'_>>,
+            filter: Result<::rp1::FilterExpression<#filter_ident>, ::rocket::form::Errors<'_>>,
             offset: Option<i64>,
             limit: Option<i64>,
+            after: Option<String>,
             #partial_params
+            #search_param
+            #embed_param
+            #with_deleted_param
+            #pagination_param
             #auth_param
-        ) -> ::rp1::CrudJsonResult<Vec<#output_ident>>
+        ) -> #result_type
         {
             let sort = sort.map_err(|e| ::rp1::CrudError::InvalidSortSpec(e.to_string()))?;
             let filter = filter.map_err(|e| ::rp1::CrudError::InvalidFilterSpec(e.to_string()))?;
+            #embed_parse
+
+            list_items(db, sort, filter, offset, limit, after, #partial_args #search_arg #parent_arg #linked_arg #embed_arg #with_deleted_arg #pagination_arg #auth_arg).await
+        }
+
+        #nested_list
+
+        #linked
+
+        #search
+
+        #full_text_search
+
+        /// Load the items matching the filters, shared by the list and search
+        /// routes.
+        #[allow(clippy::too_many_arguments)]
+        async fn list_items(
+            db: #database_struct,
+            sort: Vec<::rp1::SortSpec<SortableFields>>,
+            filter: ::rp1::FilterExpression<#filter_ident>,
+            offset: Option<i64>,
+            limit: Option<i64>,
+            after: Option<String>,
+            #partial_params
+            #search_param
+            #parent_item_param
+            #linked_item_param
+            #embed_item_param
+            #with_deleted_item_param
+            #pagination_item_param
+            #auth_param
+        ) -> #result_type
+        {
+            #with_deleted_check
+            #default_sort
+            if let Some(spec) = sort.iter().find(|s| s.case_insensitive && !s.field.is_text()) {
+                return Err(::rp1::CrudError::InvalidSortSpec(format!(
+                    "Field '{}' cannot be sorted case-insensitively",
+                    spec.field
+                )));
+            }
+            // the relevance of a row and the fields of related rows are not
+            // stored in the row, so these can not be used in a cursor
+            let keyset_sort = sort.iter().all(|s| s.field.is_keyset());
+            if let (Some(spec), Some(_)) = (sort.iter().find(|s| !s.field.is_keyset()), &after) {
+                return Err(::rp1::CrudError::InvalidCursor(format!(
+                    "cursors cannot be used when sorting on {}",
+                    spec.field
+                )));
+            }
+
+            // the primary key is added to the sort keys as a tiebreaker, so
+            // every row has a unique position for keyset pagination
+            let mut keys = sort
+                .iter()
+                .map(|s| s.map(|field| field.to_string()))
+                .collect::<Vec<_>>();
+            #(
+                if !keys.iter().any(|key| key.field == stringify!(#primary_key_names)) {
+                    keys.push(::rp1::SortSpec::new(stringify!(#primary_key_names).to_owned(), ::rp1::SortDirection::Asc));
+                }
+            )*
+            let tiebreak = keys[sort.len()..]
+                .iter()
+                .map(|key| key.field.clone())
+                .collect::<Vec<_>>();
+            let after = match after {
+                Some(after) => {
+                    let cursor = ::rp1::Cursor::decode(&after)?;
+                    cursor.check(&keys)?;
+                    Some(KeysetValue::from_cursor(&cursor)?)
+                }
+                None => None,
+            };
+
             #selected_fields_stmt
             let offset = i64::max(0, offset.unwrap_or(0));
             let limit = i64::max(1, i64::min(#max_limit, limit.unwrap_or(#max_limit)));
-            let results: #partial_result_type = db.run(move |conn| {
+            let #loaded_pattern = db.run(move |conn| {
                 use ::rp1::SortDirection;
                 use ::diesel::expression::Expression;
-                let mut query = #schema_path::#table_name::table
-                    .select(#select_statements)
-                    .offset(offset)
-                    .limit(limit)
-                    .into_boxed();
-                for sort_spec in sort {
-                    match sort_spec.field {
-                        #(SortableFields::#sortable_field_names => {
-                            query = if sort_spec.direction == SortDirection::Asc {
-                                query.then_order_by(#schema_path::#table_name::columns::#sortable_field_names.asc())
-                            } else {
-                                query.then_order_by(#schema_path::#table_name::columns::#sortable_field_names.desc())
-                            };
-                        }),*
+                #count_join_conditions
+                let query = {
+                    let mut query = #schema_path::#table_name::table
+                        .select(#select_statements)
+                        .offset(offset)
+                        .limit(limit)
+                        .into_boxed();
+                    for condition in sort.iter().filter_map(|s| s.field.join_condition()) {
+                        query = query.filter(condition);
                     }
-                }
-                #(#filter_apply_stmts)*
+                    for sort_spec in sort {
+                        match sort_spec.field {
+                            #(#sort_orders)*
+                            #relevance_order
+                        }
+                    }
+                    for field in tiebreak {
+                        match field.as_str() {
+                            #(stringify!(#primary_key_names) => {
+                                query = query.then_order_by(#schema_path::#table_name::columns::#primary_key_names.asc());
+                            })*
+                            _ => {}
+                        }
+                    }
+                    if let Some(after) = after {
+                        query = query.filter(::rp1::keyset_condition(&after, |(value, spec)| {
+                            value.condition(spec)
+                        }));
+                    }
+                    if let Some(condition) = filter.condition() {
+                        query = query.filter(condition);
+                    }
+                    #search_filter
+                    #parent_filter
+                    #linked_filter
+                    #deleted_filter
 
-                #auth_filter
+                    #auth_filter
+
+                    query
+                };
 
-                query.map(|q| q.load(conn))
+                #load_stmts
             })
             .await
             .ok_or_else(|| ::rp1::CrudError::Forbidden)??;
 
+            let cursor = match results.last() {
+                Some(row) if keyset_sort && results.len() as i64 == limit => {
+                    Some(KeysetValue::cursor(row, &keys)?.encode())
+                }
+                _ => None,
+            };
+            #last_modified_stmt
             #partial_result_map
-            Ok(::rocket::serde::json::Json(results))
+            #embed_result_map
+            #result_stmt
         }
     };
 
-    (tokens, vec![format_ident!("list_fn")])
+    let mut routes = vec![format_ident!("list_fn")];
+    if props.search {
+        routes.push(format_ident!("search_fn"));
+    }
+
+    (tokens, routes)
 }
 
-fn derive_partial_result_struct(props: &CrudProps) -> TokenStream {
-    let partial_fields = props
-        .fields
-        .iter()
-        .map(|f| f.ensure_option())
-        .collect::<Vec<_>>();
-    let partial_output_fields = props
-        .fields
-        .iter()
-        .map(|f| f.with_wrapped_option())
-        .collect::<Vec<_>>();
-    let field_maps = props
-        .fields
-        .iter()
-        .map(|f| {
-            let ident = &f.ident;
-            if f.is_option {
-                quote! {
-                    #ident: if fields.contains(&Fields::#ident) {
-                        Some(partial.#ident)
-                    } else {
-                        None
-                    }
-                }
-            } else {
-                quote! {
+/// Derives the `POST /search` route, which takes the same parameters as the
+/// list route in a JSON body.
+fn derive_search(props: &CrudProps) -> Option<TokenStream> {
+    if !props.search {
+        return None;
+    }
+
+    let CrudProps {
+        database_struct,
+        filter_ident,
+        ..
+    } = props;
+    let auth_param = derive_auth_param(props);
+    let (partial_fields, partial_args) = if props.partials {
+        (
+            Some(quote! {
+                #[serde(default)]
+                include: Vec<Fields>,
+                #[serde(default)]
+                exclude: Vec<Fields>,
+            }),
+            Some(quote!(query.include, query.exclude,)),
+        )
+    } else {
+        (None, None)
+    };
+    let (search_field, search_arg) = if props.has_search() {
+        (Some(quote!(q: Option<String>,)), Some(quote!(query.q,)))
+    } else {
+        (None, None)
+    };
+    let pagination_arg = if props.pagination {
+        Some(quote!(None,))
+    } else {
+        None
+    };
+    let parent_arg = if props.parent.is_some() {
+        Some(quote!(None,))
+    } else {
+        None
+    };
+    let linked_arg = if props.has_composite_key() {
+        None
+    } else {
+        Some(quote!(None,))
+    };
+    let auth_arg = if props.auth {
+        Some(quote!(auth_user,))
+    } else {
+        None
+    };
+    let (embed_field, embed_arg) = if props.has_embeds() {
+        (
+            Some(quote! {
+                #[serde(default)]
+                embed: Vec<Embed>,
+            }),
+            Some(quote!(query.embed,)),
+        )
+    } else {
+        (None, None)
+    };
+    let (with_deleted_field, with_deleted_arg) = if props.soft_delete_field().is_some() {
+        (
+            Some(quote! {
+                #[serde(default)]
+                with_deleted: bool,
+            }),
+            Some(quote!(query.with_deleted,)),
+        )
+    } else {
+        (None, None)
+    };
+    let result_type = derive_result_type(props);
+
+    Some(quote! {
+        #[doc(hidden)]
+        #[derive(::serde::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        pub struct SearchQuery {
+            #[serde(default)]
+            filter: ::rp1::FilterExpression<#filter_ident>,
+            #[serde(default)]
+            sort: Vec<::rp1::SortSpec<SortableFields>>,
+            offset: Option<i64>,
+            limit: Option<i64>,
+            after: Option<String>,
+            #partial_fields
+            #search_field
+            #embed_field
+            #with_deleted_field
+        }
+
+        #[::rocket::post("/search", data = "<query>")]
+        async fn search_fn(
+            db: #database_struct,
+            query: Result<::rocket::serde::json::Json<SearchQuery>, ::rocket::serde::json::Error<'_>>,
+            #auth_param
+        ) -> #result_type
+        {
+            let query = match query {
+                Ok(query) => query.into_inner(),
+                Err(::rocket::serde::json::Error::Parse(_, e)) => {
+                    return Err(::rp1::CrudError::InvalidSearchQuery(e.to_string()))
+                }
+                Err(::rocket::serde::json::Error::Io(e)) => {
+                    return Err(::rp1::CrudError::InvalidSearchQuery(e.to_string()))
+                }
+            };
+
+            list_items(db, query.sort, query.filter, query.offset, query.limit, query.after, #partial_args #search_arg #parent_arg #linked_arg #embed_arg #with_deleted_arg #pagination_arg #auth_arg).await
+        }
+    })
+}
+
+/// Derives the implementation of `CrudLinked`, which lists the items linked
+/// to an item of another struct using the parameters of the list route. The
+/// parameters are parsed from the query of the request, as they are not known
+/// to the route of the other struct.
+fn derive_linked(props: &CrudProps) -> Option<TokenStream> {
+    if props.has_composite_key() {
+        return None;
+    }
+
+    let CrudProps {
+        ident,
+        database_struct,
+        schema_path,
+        table_name,
+        filter_ident,
+        ..
+    } = props;
+    let backend = props.backend.diesel_type();
+    let output_type = derive_output_type(props);
+    let (generics, auth_type, auth_user, auth_arg) = if props.auth {
+        (
+            None,
+            quote!(<#ident as ::rp1::CheckPermissions>::AuthUser),
+            quote!(auth_user),
+            Some(quote!(auth_user,)),
+        )
+    } else {
+        (
+            Some(quote!(<AuthUser: Send + 'static>)),
+            quote!(AuthUser),
+            quote!(_auth_user),
+            None,
+        )
+    };
+    let (partial_fields, partial_args) = if props.partials {
+        (
+            Some(quote! {
+                include: Vec<Fields>,
+                exclude: Vec<Fields>,
+            }),
+            Some(quote!(query.include, query.exclude,)),
+        )
+    } else {
+        (None, None)
+    };
+    let (search_field, search_arg) = if props.has_search() {
+        (Some(quote!(q: Option<String>,)), Some(quote!(query.q,)))
+    } else {
+        (None, None)
+    };
+    let parent_arg = if props.parent.is_some() {
+        Some(quote!(None,))
+    } else {
+        None
+    };
+    let (embed_field, embed_parse, embed_arg) = if props.has_embeds() {
+        (
+            Some(quote!(embed: Vec<String>,)),
+            Some(quote! {
+                let embed = ::rp1::parse_embed::<Embed>(&query.embed)?;
+            }),
+            Some(quote!(embed,)),
+        )
+    } else {
+        (None, None, None)
+    };
+    let pagination_arg = if props.pagination {
+        Some(quote!(Some(uri),))
+    } else {
+        None
+    };
+    let (relation_param, allow_link) = if props.auth {
+        (
+            quote!(relation),
+            quote!(<#ident as ::rp1::CheckPermissions>::allow_link(self, relation, auth_user)),
+        )
+    } else {
+        (quote!(_relation), quote!(true))
+    };
+    // deleted items are never linked
+    let with_deleted_arg = if props.soft_delete_field().is_some() {
+        Some(quote!(false,))
+    } else {
+        None
+    };
+
+    Some(quote! {
+        #[doc(hidden)]
+        #[derive(::rocket::form::FromForm)]
+        pub struct LinkedQuery<'r> {
+            sort: ::rocket::form::Result<'r, Vec<::rp1::SortSpec<SortableFields>>>,
+            filter: ::rocket::form::Result<'r, ::rp1::FilterExpression<#filter_ident>>,
+            offset: Option<i64>,
+            limit: Option<i64>,
+            after: Option<String>,
+            #partial_fields
+            #search_field
+            #embed_field
+        }
+
+        #[::rocket::async_trait]
+        impl #generics ::rp1::CrudLinked<#auth_type, #database_struct> for #ident {
+            type Output = #output_type;
+            type Table = #schema_path::#table_name::table;
+
+            async fn list_linked(
+                db: #database_struct,
+                linked: ::rp1::LinkedCondition<Self::Table, #backend>,
+                uri: &::rocket::http::uri::Origin<'_>,
+                #auth_user: #auth_type,
+            ) -> ::rp1::CrudResult<Self::Output> {
+                let fields = uri
+                    .query()
+                    .map(|query| query.segments().map(::rocket::form::ValueField::from))
+                    .into_iter()
+                    .flatten();
+                let query = ::rocket::form::Form::<LinkedQuery<'_>>::parse_iter(fields)
+                    .map_err(|e| ::rp1::CrudError::InvalidQuery(e.to_string()))?;
+                let sort = query.sort.map_err(|e| ::rp1::CrudError::InvalidSortSpec(e.to_string()))?;
+                let filter = query.filter.map_err(|e| ::rp1::CrudError::InvalidFilterSpec(e.to_string()))?;
+                #embed_parse
+
+                list_items(db, sort, filter, query.offset, query.limit, query.after, #partial_args #search_arg #parent_arg Some(linked), #embed_arg #with_deleted_arg #pagination_arg #auth_arg).await
+            }
+
+            fn allow_link(&self, #relation_param: &str, #auth_user: &#auth_type) -> bool {
+                #allow_link
+            }
+        }
+    })
+}
+
+/// Derives the output of the list and search routes, a page or a list of
+/// items of which the related items are embedded if the struct has any
+/// relations.
+fn derive_output_type(props: &CrudProps) -> TokenStream {
+    let output_ident = if props.partials {
+        &props.partial_output_ident
+    } else {
+        &props.ident
+    };
+    let item_type = if props.has_embeds() {
+        quote!(::rp1::Embedded<#output_ident>)
+    } else {
+        quote!(#output_ident)
+    };
+
+    if props.pagination {
+        quote!(::rp1::Cacheable<::rp1::Page<#item_type>>)
+    } else {
+        quote!(::rp1::Cacheable<::rp1::WithCursor<::rocket::serde::json::Json<Vec<#item_type>>>>)
+    }
+}
+
+/// Derives the result type of the list and search routes.
+fn derive_result_type(props: &CrudProps) -> TokenStream {
+    let output_type = derive_output_type(props);
+    quote!(::rp1::CrudResult<#output_type>)
+}
+
+/// Derives the `search_condition` function, which returns the condition for
+/// the rows matching the `q` parameter. On Postgres the searchable fields are
+/// combined into a single document for a full-text search, on other backends
+/// every word in the search query should occur in one of the searchable
+/// fields.
+fn derive_full_text_search(props: &CrudProps) -> Option<TokenStream> {
+    if !props.has_search() {
+        return None;
+    }
+
+    let CrudProps {
+        schema_path,
+        table_name,
+        ..
+    } = props;
+    let backend = props.backend.diesel_type();
+    let condition = if props.backend == Backend::Postgres {
+        let document = derive_search_document(props);
+        quote! {
+            use ::rp1::helper::full_text::{plainto_tsquery, to_tsvector, Matches};
+
+            if q.trim().is_empty() {
+                return None;
+            }
+
+            Some(Box::new(Matches::new(to_tsvector(#document), plainto_tsquery(q.to_owned()))))
+        }
+    } else {
+        let field_names = props.searchable_fields().map(|f| &f.ident);
+        quote! {
+            let conditions = ::rp1::helper::search_patterns(q)
+                .into_iter()
+                .flat_map(|pattern| {
+                    ::rp1::any_condition(vec![
+                        #(Box::new(::rp1::helper::lower(columns::#field_names).like(pattern.clone()).escape('\\')),)*
+                    ])
+                })
+                .collect();
+
+            ::rp1::all_conditions(conditions)
+        }
+    };
+
+    Some(quote! {
+        fn search_condition(q: &str) -> Option<::rp1::FilterCondition<#schema_path::#table_name::table, #backend>> {
+            use #schema_path::#table_name::columns;
+
+            #condition
+        }
+    })
+}
+
+/// Derives the full-text search document, in which all searchable fields are
+/// concatenated.
+fn derive_search_document(props: &CrudProps) -> TokenStream {
+    let mut fields = props.searchable_fields().map(|f| {
+        let name = &f.ident;
+        if f.is_option {
+            quote!(::rp1::helper::coalesce(columns::#name, ""))
+        } else {
+            quote!(columns::#name)
+        }
+    });
+    let first = fields.next();
+
+    fields.fold(
+        quote!(#first),
+        |document, field| quote!(#document.concat(" ").concat(#field)),
+    )
+}
+
+/// Derives the statements ordering the query on relevance for the search
+/// query in `q`. As there is no ranking on backends without full-text search,
+/// rows are ordered by the first searchable field containing all words in the
+/// search query instead.
+fn derive_relevance_order(props: &CrudProps) -> TokenStream {
+    let CrudProps {
+        schema_path,
+        table_name,
+        ..
+    } = props;
+    if props.backend == Backend::Postgres {
+        let document = derive_search_document(props);
+        quote! {
+            use ::rp1::helper::full_text::{plainto_tsquery, to_tsvector, ts_rank};
+            use #schema_path::#table_name::columns;
+
+            let rank = ts_rank(to_tsvector(#document), plainto_tsquery(q.to_owned()));
+            query = if sort_spec.direction == SortDirection::Asc {
+                query.then_order_by(rank.desc())
+            } else {
+                query.then_order_by(rank.asc())
+            };
+        }
+    } else {
+        let field_names = props.searchable_fields().map(|f| &f.ident);
+        quote! {
+            use #schema_path::#table_name::columns;
+
+            let patterns = ::rp1::helper::search_patterns(q);
+            #(
+                let matches = ::rp1::all_conditions(
+                    patterns
+                        .iter()
+                        .map(|pattern| -> ::rp1::FilterCondition<_, _> {
+                            Box::new(::rp1::helper::lower(columns::#field_names).like(pattern.clone()).escape('\\'))
+                        })
+                        .collect(),
+                );
+                if let Some(matches) = matches {
+                    query = if sort_spec.direction == SortDirection::Asc {
+                        query.then_order_by(matches.desc())
+                    } else {
+                        query.then_order_by(matches.asc())
+                    };
+                }
+            )*
+        }
+    }
+}
+
+/// Derives the `KeysetValue` enum, which contains the typed value of a single
+/// key in a keyset cursor, and the functions to convert from and to cursors.
+fn derive_keyset(props: &CrudProps) -> TokenStream {
+    let CrudProps {
+        ident,
+        schema_path,
+        table_name,
+        partial_ident,
+        ..
+    } = props;
+    let backend = props.backend.diesel_type();
+    let nulls_sort_high = props.backend.nulls_sort_high();
+    let row_ident = if props.partials { partial_ident } else { ident };
+
+    let fields = props.keyset_fields().collect::<Vec<_>>();
+    let names = fields.iter().map(|f| &f.ident).collect::<Vec<_>>();
+    let types = fields.iter().map(|f| &f.ty).collect::<Vec<_>>();
+    let conditions = fields
+        .iter()
+        .map(|f| {
+            let name = &f.ident;
+            // case-insensitive keys are compared in lowercase, just like they
+            // are sorted
+            let compare = if f.is_text {
+                quote! {
+                    let (eq, gt, lt): (KeysetCondition, KeysetCondition, KeysetCondition) = if spec.case_insensitive {
+                        use ::rp1::helper::lower;
+                        (
+                            Box::new(lower(column).eq(lower(value.clone()))),
+                            Box::new(lower(column).gt(lower(value.clone()))),
+                            Box::new(lower(column).lt(lower(value.clone()))),
+                        )
+                    } else {
+                        (
+                            Box::new(column.eq(value.clone())),
+                            Box::new(column.gt(value.clone())),
+                            Box::new(column.lt(value.clone())),
+                        )
+                    };
+                }
+            } else {
+                quote! {
+                    let (eq, gt, lt): (KeysetCondition, KeysetCondition, KeysetCondition) = (
+                        Box::new(column.eq(value.clone())),
+                        Box::new(column.gt(value.clone())),
+                        Box::new(column.lt(value.clone())),
+                    );
+                }
+            };
+            if f.is_option {
+                quote! {
+                    KeysetValue::#name(value) => {
+                        let column = columns::#name;
+                        // whether rows with a NULL value come after all other
+                        // rows in the current sort direction
+                        let null_after = match spec.nulls {
+                            Some(NullsOrder::First) => false,
+                            Some(NullsOrder::Last) => true,
+                            None => #nulls_sort_high == (spec.direction == SortDirection::Asc),
+                        };
+                        match value {
+                            Some(value) => {
+                                #compare
+                                let after = if spec.direction == SortDirection::Asc { gt } else { lt };
+                                let after: KeysetCondition = if null_after {
+                                    Box::new(after.or(column.is_null()))
+                                } else {
+                                    after
+                                };
+                                (eq, after)
+                            }
+                            None => {
+                                let after: KeysetCondition = if null_after {
+                                    Box::new(::diesel::dsl::sql::<::diesel::sql_types::Bool>("1 = 0"))
+                                } else {
+                                    Box::new(column.is_not_null())
+                                };
+                                (Box::new(column.is_null()), after)
+                            }
+                        }
+                    }
+                }
+            } else {
+                quote! {
+                    KeysetValue::#name(value) => {
+                        let column = columns::#name;
+                        #compare
+                        let after = if spec.direction == SortDirection::Asc { gt } else { lt };
+                        (eq, after)
+                    }
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    quote! {
+        type KeysetCondition = ::rp1::KeysetCondition<#schema_path::#table_name::table, #backend>;
+
+        #[doc(hidden)]
+        #[allow(non_camel_case_types)]
+        pub enum KeysetValue {
+            #(#names(#types)),*
+        }
+
+        impl KeysetValue {
+            fn from_cursor(cursor: &::rp1::Cursor) -> ::rp1::CrudResult<Vec<(KeysetValue, ::rp1::SortSpec<String>)>> {
+                cursor
+                    .keys()
+                    .iter()
+                    .map(|(spec, value)| {
+                        let invalid = |e: ::serde_json::Error| ::rp1::CrudError::InvalidCursor(e.to_string());
+                        let value = match spec.field.as_str() {
+                            #(stringify!(#names) => KeysetValue::#names(
+                                ::serde_json::from_value(value.clone()).map_err(invalid)?
+                            ),)*
+                            _ => return Err(::rp1::CrudError::InvalidCursor(format!("unknown field '{}'", spec.field))),
+                        };
+                        Ok((value, spec.clone()))
+                    })
+                    .collect()
+            }
+
+            fn cursor(row: &#row_ident, keys: &[::rp1::SortSpec<String>]) -> ::rp1::CrudResult<::rp1::Cursor> {
+                let keys = keys
+                    .iter()
+                    .map(|spec| {
+                        let value = match spec.field.as_str() {
+                            #(stringify!(#names) => ::serde_json::to_value(&row.#names),)*
+                            _ => return Err(::rp1::CrudError::DbValueError),
+                        };
+                        let value = value.map_err(|_| ::rp1::CrudError::DbValueError)?;
+                        Ok((spec.clone(), value))
+                    })
+                    .collect::<::rp1::CrudResult<Vec<_>>>()?;
+
+                Ok(::rp1::Cursor::new(keys))
+            }
+
+            fn condition(&self, spec: &::rp1::SortSpec<String>) -> (KeysetCondition, KeysetCondition) {
+                use ::rp1::{NullsOrder, SortDirection};
+                use #schema_path::#table_name::columns;
+
+                match self {
+                    #(#conditions)*
+                }
+            }
+        }
+    }
+}
+
+fn derive_partial_result_struct(props: &CrudProps) -> TokenStream {
+    let partial_fields = props
+        .fields
+        .iter()
+        .map(|f| f.ensure_option())
+        .collect::<Vec<_>>();
+    let partial_output_fields = props
+        .fields
+        .iter()
+        .map(|f| f.with_wrapped_option())
+        .collect::<Vec<_>>();
+    let field_maps = props
+        .fields
+        .iter()
+        .map(|f| {
+            let ident = &f.ident;
+            if f.is_option {
+                quote! {
+                    #ident: if fields.contains(&Fields::#ident) {
+                        Some(partial.#ident)
+                    } else {
+                        None
+                    }
+                }
+            } else {
+                quote! {
                     #ident: if fields.contains(&Fields::#ident) {
                         Some(partial.#ident.ok_or(::rp1::CrudError::DbValueError)?)
                     } else {
@@ -386,26 +1472,371 @@ fn derive_partial_result_struct(props: &CrudProps) -> TokenStream {
 }
 
 fn derive_select_statement(props: &CrudProps) -> TokenStream {
+    let CrudProps {
+        schema_path,
+        table_name,
+        ..
+    } = props;
     let fields = props.fields.iter().map(|f| {
         let name = &f.ident;
-        if f.is_option {
+        quote! {
+            ::rp1::helper::partial_column(
+                #schema_path::#table_name::columns::#name,
+                selected.contains(&Fields::#name),
+            )
+        }
+    });
+
+    quote! { (#(#fields,)*) }
+}
+
+/// Derives the match arms ordering by a sortable field (including the fields
+/// of related structs) for a `sort_spec`. The `column` function gives the
+/// expression of a column of the table to order by, and `apply` the statement
+/// adding an order expression to the result.
+fn derive_sort_orders(
+    props: &CrudProps,
+    column: impl Fn(&Ident) -> TokenStream,
+    apply: impl Fn(TokenStream) -> TokenStream,
+) -> Vec<TokenStream> {
+    let mut sort_orders = props
+        .sortable_fields()
+        .map(|f| {
+            let name = &f.ident;
+            let column = column(name);
+            // `NULL` values are placed using an `IS NULL` sort key, as not all
+            // backends support `NULLS FIRST` and `NULLS LAST`
+            let nulls_order = if f.is_option {
+                let first = apply(quote!(#column.is_null().desc()));
+                let last = apply(quote!(#column.is_null().asc()));
+                Some(quote! {
+                    match sort_spec.nulls {
+                        Some(::rp1::NullsOrder::First) => {
+                            #first
+                        }
+                        Some(::rp1::NullsOrder::Last) => {
+                            #last
+                        }
+                        None => {}
+                    }
+                })
+            } else {
+                None
+            };
+            let order = |value: TokenStream| {
+                let asc = apply(quote!(#value.asc()));
+                let desc = apply(quote!(#value.desc()));
+                quote! {
+                    if sort_spec.direction == SortDirection::Asc {
+                        #asc
+                    } else {
+                        #desc
+                    }
+                }
+            };
+            let order = if f.is_text {
+                let case_sensitive = order(column.clone());
+                let case_insensitive = order(quote!(::rp1::helper::lower(#column)));
+                quote! {
+                    if sort_spec.case_insensitive {
+                        #case_insensitive
+                    } else {
+                        #case_sensitive
+                    }
+                }
+            } else {
+                order(column)
+            };
+
             quote! {
-                if selected.contains(&Fields::#name) {
-                    diesel::dsl::sql(stringify!(#name))
-                } else {
-                    diesel::dsl::sql("null")
+                SortableFields::#name => {
+                    #nulls_order
+                    #order
                 }
             }
-        } else {
-            quote! {
-                if selected.contains(&Fields::#name) {
-                    diesel::dsl::sql(stringify!(#name))
-                } else {
-                    diesel::dsl::sql("null")
+        })
+        .collect::<Vec<_>>();
+
+    sort_orders.extend(props.relations.iter().map(|relation| {
+        let Relation {
+            name,
+            path,
+            foreign_key,
+        } = relation;
+        let foreign_key = column(foreign_key);
+        let order = apply(quote!(order));
+        quote! {
+            SortableFields::#name(field) => {
+                let sort_spec = ::rp1::SortSpec {
+                    field,
+                    direction: sort_spec.direction,
+                    nulls: sort_spec.nulls,
+                    case_insensitive: sort_spec.case_insensitive,
+                };
+                for order in <#path as ::rp1::CrudRelated>::related_order(#foreign_key, sort_spec) {
+                    #order
                 }
             }
         }
+    }));
+
+    sort_orders
+}
+
+/// Derives the enum of sortable fields, which next to the fields of the
+/// struct contains the relevance of search results and the sortable fields of
+/// related structs, written as `relation.field`.
+fn derive_sortable_fields(props: &CrudProps) -> TokenStream {
+    let names = props
+        .sortable_fields()
+        .map(|f| f.ident.clone())
+        .collect::<Vec<_>>();
+    let text_fields = props
+        .sortable_fields()
+        .map(|f| f.is_text)
+        .collect::<Vec<_>>();
+    let mut variants = names.iter().map(|name| quote!(#name)).collect::<Vec<_>>();
+    let mut is_text = vec![];
+    let mut display = vec![];
+    let mut from_str = vec![];
+    let mut not_keyset = vec![];
+    if props.has_search() {
+        variants.push(quote!(relevance));
+        is_text.push(quote!(SortableFields::relevance => false));
+        display.push(quote!(SortableFields::relevance => write!(f, "relevance")));
+        from_str.push(quote!("relevance" => Ok(SortableFields::relevance)));
+        not_keyset.push(quote!(SortableFields::relevance));
+    }
+    for Relation { name, path, .. } in props.relations.iter() {
+        variants.push(quote!(#name(<#path as ::rp1::CrudRelated>::SortableFields)));
+        is_text.push(quote!(SortableFields::#name(field) => field.is_text()));
+        display.push(
+            quote!(SortableFields::#name(field) => write!(f, "{}.{}", stringify!(#name), field)),
+        );
+        not_keyset.push(quote!(SortableFields::#name(_)));
+    }
+    let relation_names = props.relations.iter().map(|r| &r.name);
+    // the relevance of a related row is not known, as the search query only
+    // applies to this struct
+    let relation_from_str = if props.relations.is_empty() {
+        quote!(_ => Err(format!("Unknown sort field '{}'", s)))
+    } else {
+        quote! {
+            _ => match s.split_once('.') {
+                #(Some((stringify!(#relation_names), field)) if field != "relevance" => {
+                    field.parse().map(SortableFields::#relation_names)
+                })*
+                _ => Err(format!("Unknown sort field '{}'", s)),
+            }
+        }
+    };
+    // sorting on a related field only includes the rows of which the related
+    // rows exist, as an inner join on the related tables would
+    let schema_path = &props.schema_path;
+    let table_name = &props.table_name;
+    let backend = props.backend.diesel_type();
+    let join_conditions = props.relations.iter().map(|relation| {
+        let Relation {
+            name,
+            path,
+            foreign_key,
+        } = relation;
+        quote! {
+            SortableFields::#name(field) => Some(<#path as ::rp1::CrudRelated>::related_sort_condition::<#schema_path::#table_name::table, _>(
+                #schema_path::#table_name::columns::#foreign_key,
+                field,
+            )),
+        }
     });
+    let is_keyset = if not_keyset.is_empty() {
+        quote!(true)
+    } else {
+        quote!(!matches!(self, #(#not_keyset)|*))
+    };
 
-    quote! { (#(#fields,)*) }
+    quote! {
+        #[doc(hidden)]
+        #[allow(non_camel_case_types)]
+        #[derive(Debug)]
+        pub enum SortableFields {
+            #(#variants),*
+        }
+
+        impl SortableFields {
+            /// Whether the field can be sorted case-insensitively.
+            pub fn is_text(&self) -> bool {
+                match self {
+                    #(SortableFields::#names => #text_fields,)*
+                    #(#is_text,)*
+                }
+            }
+
+            /// Whether the value of the field is stored in the row, so it can
+            /// be used in a cursor.
+            fn is_keyset(&self) -> bool {
+                #is_keyset
+            }
+
+            /// The condition matching the rows that have the related rows
+            /// the field refers to, if it is a field of a related struct.
+            fn join_condition(&self) -> Option<::rp1::FilterCondition<#schema_path::#table_name::table, #backend>> {
+                match self {
+                    #(#join_conditions)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl ::std::fmt::Display for SortableFields {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                match self {
+                    #(SortableFields::#names => write!(f, stringify!(#names)),)*
+                    #(#display,)*
+                }
+            }
+        }
+
+        impl ::std::str::FromStr for SortableFields {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    #(stringify!(#names) => Ok(SortableFields::#names),)*
+                    #(#from_str,)*
+                    #relation_from_str
+                }
+            }
+        }
+
+        impl<'v> ::rocket::form::FromFormField<'v> for SortableFields {
+            fn from_value(field: ::rocket::form::ValueField<'v>) -> ::rocket::form::Result<'v, Self> {
+                field
+                    .value
+                    .parse()
+                    .map_err(|e: String| ::rocket::form::Error::validation(e).into())
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for SortableFields {
+            fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let field = String::deserialize(deserializer)?;
+                field.parse().map_err(::serde::de::Error::custom)
+            }
+        }
+    }
+}
+
+/// Derives the implementation of `CrudRelated`, which allows other structs
+/// to filter and sort on the fields of this struct using a `belongs_to`
+/// relation. Relations to a struct with a composite primary key are not
+/// supported.
+fn derive_related(props: &CrudProps) -> Option<TokenStream> {
+    if props.has_composite_key() {
+        return None;
+    }
+
+    let CrudProps {
+        ident,
+        schema_path,
+        table_name,
+        filter_ident,
+        ..
+    } = props;
+    let backend = props.backend.diesel_type();
+    let sort_orders = derive_sort_orders(
+        props,
+        |name| {
+            quote! {
+                ::rp1::RelatedValue::new(
+                    #schema_path::#table_name::table,
+                    foreign_key.clone(),
+                    #schema_path::#table_name::columns::#name,
+                )
+            }
+        },
+        |order| quote!(orders.push(Box::new(#order));),
+    );
+    let relevance_order = if props.has_search() {
+        Some(quote!(SortableFields::relevance => {}))
+    } else {
+        None
+    };
+    // the fields of the relations of this struct also require the rows of
+    // the related tables to exist
+    let sort_conditions = props.relations.iter().map(|relation| {
+        let Relation {
+            name,
+            path,
+            foreign_key,
+        } = relation;
+        quote! {
+            SortableFields::#name(field) => Box::new(::rp1::RelatedCondition::new(
+                #schema_path::#table_name::table,
+                foreign_key,
+                <#path as ::rp1::CrudRelated>::related_sort_condition::<#schema_path::#table_name::table, _>(
+                    #schema_path::#table_name::columns::#foreign_key,
+                    field,
+                ),
+            )),
+        }
+    });
+
+    Some(quote! {
+        impl ::rp1::CrudRelated for #ident {
+            type Backend = #backend;
+            type SortableFields = SortableFields;
+
+            fn related_condition<QS, FK>(
+                foreign_key: FK,
+                filter: &::rp1::FilterExpression<#filter_ident>,
+            ) -> Option<::rp1::FilterCondition<QS, #backend>>
+            where
+                QS: 'static,
+                FK: ::rp1::ForeignKey<QS, #backend>,
+            {
+                let condition = filter.condition()?;
+                Some(Box::new(::rp1::RelatedCondition::new(
+                    #schema_path::#table_name::table,
+                    foreign_key,
+                    condition,
+                )))
+            }
+
+            fn related_order<QS, FK>(
+                foreign_key: FK,
+                sort_spec: ::rp1::SortSpec<SortableFields>,
+            ) -> Vec<::rp1::OrderExpression<QS, #backend>>
+            where
+                QS: 'static,
+                FK: ::rp1::ForeignKey<QS, #backend>,
+            {
+                use ::rp1::SortDirection;
+
+                let mut orders: Vec<::rp1::OrderExpression<QS, #backend>> = vec![];
+                match sort_spec.field {
+                    #(#sort_orders)*
+                    #relevance_order
+                }
+
+                orders
+            }
+
+            fn related_sort_condition<QS, FK>(
+                foreign_key: FK,
+                field: &SortableFields,
+            ) -> ::rp1::FilterCondition<QS, #backend>
+            where
+                QS: 'static,
+                FK: ::rp1::ForeignKey<QS, #backend>,
+            {
+                match field {
+                    #(#sort_conditions)*
+                    _ => Box::new(::rp1::RelatedCondition::exists(
+                        #schema_path::#table_name::table,
+                        foreign_key,
+                    )),
+                }
+            }
+        }
+    })
 }
@@ -0,0 +1,281 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::Ident;
+
+use crate::{
+    derive::common::{derive_auth_param, derive_not_deleted},
+    props::{Backend, CrudProps, ManyToMany},
+};
+
+/// Derives the routes of the `many_to_many` relations, which list the linked
+/// items and change the rows in the join table.
+pub(crate) fn derive_many_to_many(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
+    if props.many_to_many.is_empty() {
+        return (quote!(), vec![]);
+    }
+
+    let ident = &props.ident;
+    let auth_type = if props.auth {
+        quote!(<#ident as ::rp1::CheckPermissions>::AuthUser)
+    } else {
+        quote!(())
+    };
+    let mut tokens = vec![quote! {
+        /// The user for which the permission checks of linked items are
+        /// applied.
+        type LinkUser = #auth_type;
+    }];
+    let mut routes = vec![];
+    for relation in props.many_to_many.iter() {
+        let (toks, mut funcs) = derive_relation_routes(props, relation);
+        tokens.push(toks);
+        routes.append(&mut funcs);
+    }
+
+    (quote!(#(#tokens)*), routes)
+}
+
+fn derive_relation_routes(props: &CrudProps, relation: &ManyToMany) -> (TokenStream, Vec<Ident>) {
+    let CrudProps {
+        ident,
+        database_struct,
+        schema_path,
+        table_name,
+        primary_type,
+        ..
+    } = props;
+    let ManyToMany {
+        name,
+        path,
+        through,
+        foreign_key,
+        related_key,
+    } = relation;
+    let list_fn = format_ident!("{}_list_fn", name);
+    let link_fn = format_ident!("{}_link_fn", name);
+    let unlink_fn = format_ident!("{}_unlink_fn", name);
+    let set_fn = format_ident!("{}_set_fn", name);
+    let list_route = format!("/<id>/{}", name);
+    let item_route = format!("/<id>/{}/<related_id>", name);
+    let related_key_type = quote!(<#path as ::rp1::CrudEmbed<LinkUser>>::Key);
+
+    let auth_param = derive_auth_param(props);
+    let auth_user = if props.auth {
+        None
+    } else {
+        Some(quote!(let auth_user = ();))
+    };
+    let (read_check, link_check) = if props.auth {
+        (
+            Some(quote! {
+                if !<#ident as ::rp1::CheckPermissions>::allow_read(&row, &auth_user) {
+                    return Err(::rp1::CrudError::NotFound);
+                }
+            }),
+            Some(quote! {
+                if !<#ident as ::rp1::CheckPermissions>::allow_read(&row, &auth_user)
+                    || !<#ident as ::rp1::CheckPermissions>::allow_link(&row, stringify!(#name), &auth_user)
+                {
+                    return Err(::rp1::CrudError::NotFound);
+                }
+            }),
+        )
+    } else {
+        (None, None)
+    };
+    let row = if props.auth {
+        Some(quote!(let row =))
+    } else {
+        None
+    };
+    // the links of deleted items are not available
+    let not_deleted = derive_not_deleted(props).map(|condition| quote!(.filter(#condition)));
+    let find_row = quote! {
+        #row #schema_path::#table_name::table
+            .find(id.clone())
+            #not_deleted
+            .first::<#ident>(conn)?;
+    };
+    // the related items should exist, be readable and allow to be linked to
+    // change their links
+    let linked_type = quote!(<#path as ::rp1::CrudLinked<LinkUser, #database_struct>>);
+    let related_check = quote! {
+        if !related.iter().all(|item| #linked_type::allow_link(item, stringify!(#name), &auth_user)) {
+            return Err(::rp1::CrudError::NotFound);
+        }
+    };
+    let find_related = quote! {
+        let (_, related) = <#path as ::rp1::CrudEmbed<LinkUser>>::load_embedded(
+            conn,
+            vec![related_id.clone()],
+            &auth_user,
+        )?
+        .pop()
+        .ok_or(::rp1::CrudError::NotFound)?;
+        if !#linked_type::allow_link(&related, stringify!(#name), &auth_user) {
+            return Err(::rp1::CrudError::NotFound);
+        }
+    };
+    let join_table = quote!(#schema_path::#through::table);
+    let foreign_key = quote!(#schema_path::#through::columns::#foreign_key);
+    let related_key = quote!(#schema_path::#through::columns::#related_key);
+    // links are inserted with a single statement ignoring existing links, so
+    // concurrent requests cannot insert the same link twice
+    let insert_link = |key: TokenStream| {
+        let values = quote!((#foreign_key.eq(id.clone()), #related_key.eq(#key)));
+        match props.backend {
+            Backend::Postgres => quote! {
+                ::diesel::insert_into(#join_table)
+                    .values(#values)
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+            },
+            Backend::Sqlite | Backend::Mysql => quote! {
+                ::diesel::insert_or_ignore_into(#join_table)
+                    .values(#values)
+                    .execute(conn)?;
+            },
+        }
+    };
+    let link_related = insert_link(quote!(related_id));
+    let link_key = insert_link(quote!(key));
+
+    let tokens = quote! {
+        #[::rocket::get(#list_route)]
+        async fn #list_fn(
+            db: #database_struct,
+            id: #primary_type,
+            uri: &::rocket::http::uri::Origin<'_>,
+            #auth_param
+        ) -> ::rp1::CrudResult<<#path as ::rp1::CrudLinked<LinkUser, #database_struct>>::Output>
+        {
+            #auth_user
+            let linked_id = id.clone();
+            let auth_user = db.run(move |conn| -> ::rp1::CrudResult<_> {
+                #find_row
+                #read_check
+                Ok(auth_user)
+            })
+            .await?;
+            // the linked items are selected using a subquery on the join table
+            let linked: ::rp1::LinkedCondition<_, _> = Box::new(
+                move |key: <<#path as ::rp1::CrudLinked<LinkUser, #database_struct>>::Table as ::diesel::Table>::PrimaryKey| {
+                    Box::new(key.eq_any(
+                        #join_table
+                            .filter(#foreign_key.eq(linked_id.clone()))
+                            .select(#related_key),
+                    ))
+                },
+            );
+
+            <#path as ::rp1::CrudLinked<LinkUser, #database_struct>>::list_linked(db, linked, uri, auth_user).await
+        }
+
+        #[::rocket::put(#item_route)]
+        async fn #link_fn(
+            db: #database_struct,
+            id: #primary_type,
+            related_id: #related_key_type,
+            #auth_param
+        ) -> ::rp1::CrudJsonResult<#path>
+        {
+            #auth_user
+            let related = db.run(move |conn| {
+                conn.transaction(|| -> ::rp1::CrudResult<_> {
+                    #find_row
+                    #link_check
+                    #find_related
+                    #link_related
+                    Ok(related)
+                })
+            })
+            .await?;
+
+            Ok(::rocket::serde::json::Json(related))
+        }
+
+        #[::rocket::delete(#item_route)]
+        async fn #unlink_fn(
+            db: #database_struct,
+            id: #primary_type,
+            related_id: #related_key_type,
+            #auth_param
+        ) -> ::rp1::CrudResult<::serde_json::Value>
+        {
+            #auth_user
+            let deleted = db.run(move |conn| {
+                conn.transaction(|| -> ::rp1::CrudResult<_> {
+                    #find_row
+                    #link_check
+                    let related = <#path as ::rp1::CrudEmbed<LinkUser>>::load_embedded(
+                        conn,
+                        vec![related_id.clone()],
+                        &auth_user,
+                    )?;
+                    if related.is_empty() {
+                        return Err(::rp1::CrudError::NotFound);
+                    }
+                    let related = related.into_iter().map(|(_, item)| item).collect::<Vec<_>>();
+                    #related_check
+                    let deleted = ::diesel::delete(
+                        #join_table
+                            .filter(#foreign_key.eq(id))
+                            .filter(#related_key.eq(related_id)),
+                    )
+                    .execute(conn)?;
+                    Ok(deleted)
+                })
+            })
+            .await?;
+
+            Ok(::serde_json::json!({
+                "deleted": deleted,
+            }))
+        }
+
+        #[::rocket::put(#list_route, format = "json", data = "<keys>")]
+        async fn #set_fn(
+            db: #database_struct,
+            id: #primary_type,
+            keys: ::rocket::serde::json::Json<Vec<#related_key_type>>,
+            #auth_param
+        ) -> ::rp1::CrudJsonResult<Vec<#path>>
+        {
+            #auth_user
+            let keys = ::rp1::distinct_keys(keys.into_inner());
+            let related = db.run(move |conn| {
+                conn.transaction(|| -> ::rp1::CrudResult<_> {
+                    #find_row
+                    #link_check
+                    let related = <#path as ::rp1::CrudEmbed<LinkUser>>::load_embedded(
+                        conn,
+                        keys.clone(),
+                        &auth_user,
+                    )?;
+                    let related = ::rp1::linked_items(&keys, related).ok_or(::rp1::CrudError::NotFound)?;
+                    #related_check
+
+                    ::diesel::delete(
+                        #join_table
+                            .filter(#foreign_key.eq(id.clone()))
+                            .filter(#related_key.ne_all(keys.clone())),
+                    )
+                    .execute(conn)?;
+                    let linked: Vec<#related_key_type> = #join_table
+                        .filter(#foreign_key.eq(id.clone()))
+                        .select(#related_key)
+                        .load(conn)?;
+                    for key in keys.into_iter().filter(|key| !linked.contains(key)) {
+                        #link_key
+                    }
+                    Ok(related)
+                })
+            })
+            .await?;
+
+            Ok(::rocket::serde::json::Json(related))
+        }
+    };
+
+    (tokens, vec![list_fn, link_fn, unlink_fn, set_fn])
+}
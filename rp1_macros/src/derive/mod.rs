@@ -1,6 +1,13 @@
+pub(crate) mod audit;
+pub(crate) mod bulk;
 pub(crate) mod common;
 pub(crate) mod create;
 pub(crate) mod delete;
+pub(crate) mod embed;
+pub(crate) mod events;
 pub(crate) mod list;
+pub(crate) mod many_to_many;
+pub(crate) mod nested;
+pub(crate) mod openapi;
 pub(crate) mod read;
 pub(crate) mod update;
@@ -0,0 +1,98 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Ident, Type};
+
+use crate::{derive::common::derive_auth_param, props::CrudProps};
+
+/// The parts of the nested routes shared by the list, create and read
+/// routes, for a struct with a `parent`.
+pub(crate) struct ParentRoute {
+    /// Path of the nested routes, i.e. `/<post_id>/comments`.
+    pub(crate) path: String,
+    /// The foreign key referring to the parent, which is also the name of the
+    /// path parameter.
+    pub(crate) key: Ident,
+    /// The type of the primary key of the parent.
+    pub(crate) key_type: Type,
+    /// The statement returning a not found error if the parent does not exist
+    /// or cannot be read by the user.
+    pub(crate) check: TokenStream,
+}
+
+pub(crate) fn derive_parent_route(props: &CrudProps) -> Option<ParentRoute> {
+    let parent = props.parent.as_ref()?;
+    let key = parent.foreign_key.clone();
+    let field = props
+        .fields
+        .iter()
+        .find(|f| f.ident == key)
+        .expect("foreign key fields are checked when parsing relations");
+    let check = if props.auth {
+        quote!(let auth_user = find_parent(&db, #key.clone(), auth_user).await?;)
+    } else {
+        quote!(find_parent(&db, #key.clone()).await?;)
+    };
+
+    Some(ParentRoute {
+        path: format!("/<{}>/{}", key, props.table_name),
+        key,
+        key_type: field.inner_ty().clone(),
+        check,
+    })
+}
+
+/// Derives the `find_parent` function used by the nested routes, which checks
+/// whether the parent exists and can be read by the user. The user is
+/// returned again, as it is moved into the database closure.
+pub(crate) fn derive_find_parent(props: &CrudProps) -> Option<TokenStream> {
+    let parent = props.parent.as_ref()?;
+    let ParentRoute { key_type, .. } = derive_parent_route(props)?;
+    let database_struct = &props.database_struct;
+    let path = &parent.path;
+    let auth_param = derive_auth_param(props);
+    let auth_user = if props.auth {
+        None
+    } else {
+        Some(quote!(let auth_user = ();))
+    };
+
+    Some(quote! {
+        /// Returns a not found error if the parent of the nested routes does
+        /// not exist, or the user is not allowed to read it.
+        async fn find_parent(
+            db: &#database_struct,
+            key: #key_type,
+            #auth_param
+        ) -> ::rp1::CrudResult<EmbedUser> {
+            #auth_user
+            db.run(move |conn| {
+                let parents = <#path as ::rp1::CrudEmbed<EmbedUser>>::load_embedded(conn, vec![key], &auth_user)?;
+                if parents.is_empty() {
+                    Err(::rp1::CrudError::NotFound)
+                } else {
+                    Ok(auth_user)
+                }
+            })
+            .await
+        }
+    })
+}
+
+/// The names of the nested routes, which are returned by `get_nested_routes`.
+pub(crate) fn nested_routes(props: &CrudProps) -> Vec<Ident> {
+    let mut routes = vec![];
+    if props.parent.is_none() {
+        return routes;
+    }
+    if props.create {
+        routes.push(format_ident!("nested_create_fn"));
+    }
+    if props.read {
+        routes.push(format_ident!("nested_read_fn"));
+    }
+    if props.list {
+        routes.push(format_ident!("nested_list_fn"));
+    }
+
+    routes
+}
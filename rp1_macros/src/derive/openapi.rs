@@ -0,0 +1,1170 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{GenericArgument, Lit, Meta, NestedMeta, PathArguments, Type};
+
+use crate::props::{CrudField, CrudProps};
+
+/// Filter operators of `FilterOperator` with a description of the value they
+/// expect. The `eq` operator is the default and can be left off.
+const FILTER_OPERATORS: &[(&str, &str)] = &[
+    ("eq", "Only include items where the field equals the value."),
+    (
+        "ne",
+        "Only include items where the field does not equal the value.",
+    ),
+    (
+        "gt",
+        "Only include items where the field is greater than the value.",
+    ),
+    (
+        "ge",
+        "Only include items where the field is greater than or equal to the value.",
+    ),
+    (
+        "lt",
+        "Only include items where the field is less than the value.",
+    ),
+    (
+        "le",
+        "Only include items where the field is less than or equal to the value.",
+    ),
+    (
+        "in",
+        "Only include items where the field equals one of the comma separated values.",
+    ),
+];
+
+/// Filter operators that are only available for nullable fields, see
+/// `FilterOperator::IsNull`.
+const NULL_FILTER_OPERATORS: &[(&str, &str)] = &[
+    (
+        "isnull",
+        "Only include items where the field is null (`true`) or not null (`false`).",
+    ),
+    (
+        "notnull",
+        "Only include items where the field is not null (`true`) or null (`false`).",
+    ),
+];
+
+/// Filter operators that are only available for text fields, see
+/// `FilterOperator::Like`.
+const TEXT_FILTER_OPERATORS: &[(&str, &str)] = &[
+    (
+        "like",
+        "Only include items where the field matches the LIKE pattern.",
+    ),
+    (
+        "ilike",
+        "Only include items where the field matches the LIKE pattern, ignoring case.",
+    ),
+    (
+        "contains",
+        "Only include items where the field contains the value.",
+    ),
+    (
+        "icontains",
+        "Only include items where the field contains the value, ignoring case.",
+    ),
+    (
+        "startswith",
+        "Only include items where the field starts with the value.",
+    ),
+    (
+        "istartswith",
+        "Only include items where the field starts with the value, ignoring case.",
+    ),
+    (
+        "endswith",
+        "Only include items where the field ends with the value.",
+    ),
+    (
+        "iendswith",
+        "Only include items where the field ends with the value, ignoring case.",
+    ),
+];
+
+/// Description of the `q` search parameter.
+const SEARCH_DESCRIPTION: &str =
+    "Only include items where the searchable fields contain all words in the search query.";
+
+/// Description of the `with_deleted` parameter.
+const WITH_DELETED_DESCRIPTION: &str = "Also include items that have been deleted.";
+
+/// Derives the `openapi()` function that describes all enabled routes.
+pub(crate) fn derive_openapi(props: &CrudProps) -> TokenStream {
+    let ident = &props.ident;
+    let name = ident.to_string();
+    let module_name = props.module_name.to_string();
+    let item_schema = derive_object_schema(props.fields.iter(), |_| true);
+    let mut schemas = vec![quote!((#name, #item_schema))];
+    let mut root_operations = vec![];
+    let mut item_operations = vec![];
+
+    let forbidden = if props.auth {
+        Some(quote! {
+            "403": ::rp1::openapi::error_response("Not allowed"),
+        })
+    } else {
+        None
+    };
+    // a single item that the user is not allowed to access is reported as not
+    // found instead of forbidden, so its existence is not revealed
+    let not_found = if props.auth {
+        quote!("404": ::rp1::openapi::error_response("Item not found, or not allowed"),)
+    } else {
+        quote!("404": ::rp1::openapi::error_response("Item not found"),)
+    };
+    let id_parameters = props
+        .primary_key_fields()
+        .map(|f| {
+            let name = f.ident.to_string();
+            let schema = type_schema(&f.ty);
+            quote!(::rp1::openapi::parameter(#name, "path", true, #schema))
+        })
+        .collect::<Vec<_>>();
+    let item_ref = quote!(::rp1::openapi::schema_ref(#name));
+
+    // items with a version are returned with an `ETag`, which can be used in
+    // the `If-Match` header of requests changing the item
+    let (item_response, if_match, precondition_failed) = if props.version_field().is_some() {
+        (
+            quote!(::rp1::openapi::with_etag),
+            Some(quote! {
+                ::rp1::openapi::parameter("If-Match", "header", false, ::serde_json::json!({ "type": "string" })),
+            }),
+            Some(quote! {
+                "412": ::rp1::openapi::error_response("The item has been changed"),
+            }),
+        )
+    } else {
+        (quote!(::std::convert::identity), None, None)
+    };
+
+    if props.create {
+        let new_name = props.new_ident.to_string();
+        let operation_id = format!("{}_create", module_name);
+        let new_schema = derive_object_schema(props.insertable_fields(), is_required);
+        schemas.push(quote!((#new_name, #new_schema)));
+        // JSON bodies can also contain an array of items to create
+        root_operations.push(quote! {
+            ("post", ::serde_json::json!({
+                "operationId": #operation_id,
+                "tags": [#name],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/json": {
+                            "schema": ::rp1::openapi::one_or_many(::rp1::openapi::schema_ref(#new_name)),
+                        },
+                        "application/x-www-form-urlencoded": {
+                            "schema": ::rp1::openapi::schema_ref(#new_name),
+                        },
+                    },
+                },
+                "responses": {
+                    "200": ::rp1::openapi::json_response(
+                        "The created item, or the created items for an array",
+                        ::rp1::openapi::one_or_many(#item_ref),
+                    ),
+                    "400": ::rp1::openapi::error_response("Invalid item"),
+                    #forbidden
+                },
+            }))
+        });
+    }
+
+    if props.read {
+        let operation_id = format!("{}_read", module_name);
+        // the parameter is a block, which `json!` would take for an object
+        let embed_parameter = derive_embed_parameter(props).map(|p| quote!((#p)));
+        let invalid_embed = if props.has_embeds() {
+            Some(quote! {
+                "400": ::rp1::openapi::error_response("Invalid embed"),
+            })
+        } else {
+            None
+        };
+        let with_deleted_parameter = derive_with_deleted_parameter(props).map(|p| quote!((#p),));
+        // only users that are allowed to read deleted items can request them
+        let forbidden_deleted = if with_deleted_parameter.is_some() {
+            forbidden.clone()
+        } else {
+            None
+        };
+        let response = derive_cache_headers(
+            props,
+            quote!(::rp1::openapi::json_response("The item", #item_ref)),
+        );
+        item_operations.push(quote! {
+            ("get", ::serde_json::json!({
+                "operationId": #operation_id,
+                "tags": [#name],
+                "parameters": [#(#id_parameters,)* #with_deleted_parameter #embed_parameter],
+                "responses": {
+                    "200": #response,
+                    "304": ::rp1::openapi::not_modified_response(),
+                    #invalid_embed
+                    #forbidden_deleted
+                    #not_found
+                },
+            }))
+        });
+    }
+
+    if props.update {
+        let patch_name = props.patch_ident.to_string();
+        let put_name = props.put_ident.to_string();
+        let patch_schema = derive_object_schema(props.user_supplied_fields(), |_| false);
+        let put_schema = derive_object_schema(props.put_fields(), is_required);
+        schemas.push(quote!((#patch_name, #patch_schema)));
+        schemas.push(quote!((#put_name, #put_schema)));
+        for (method, schema_name) in &[("patch", &patch_name), ("put", &put_name)] {
+            let operation_id = format!("{}_update_{}", module_name, method);
+            item_operations.push(quote! {
+                (#method, ::serde_json::json!({
+                    "operationId": #operation_id,
+                    "tags": [#name],
+                    "parameters": [#(#id_parameters,)* #if_match],
+                    "requestBody": ::rp1::openapi::request_body(::rp1::openapi::schema_ref(#schema_name)),
+                    "responses": {
+                        "200": #item_response(::rp1::openapi::json_response("The updated item", #item_ref)),
+                        "400": ::rp1::openapi::error_response("Invalid item"),
+                        #not_found
+                        #precondition_failed
+                    },
+                }))
+            });
+        }
+    }
+
+    if props.delete {
+        let operation_id = format!("{}_delete", module_name);
+        item_operations.push(quote! {
+            ("delete", ::serde_json::json!({
+                "operationId": #operation_id,
+                "tags": [#name],
+                "parameters": [#(#id_parameters,)* #if_match],
+                "responses": {
+                    "200": ::rp1::openapi::json_response(
+                        "The number of deleted items",
+                        ::rp1::openapi::object_schema(
+                            vec![("deleted", ::serde_json::json!({ "type": "integer" }))],
+                            vec!["deleted"],
+                        ),
+                    ),
+                    #not_found
+                    #precondition_failed
+                },
+            }))
+        });
+    }
+
+    let mut restore_operations = vec![];
+    if props.delete && props.soft_delete_field().is_some() {
+        let operation_id = format!("{}_restore", module_name);
+        restore_operations.push(quote! {
+            ("post", ::serde_json::json!({
+                "operationId": #operation_id,
+                "description": "Restores a deleted item.",
+                "tags": [#name],
+                "parameters": [#(#id_parameters,)*],
+                "responses": {
+                    "200": #item_response(::rp1::openapi::json_response("The restored item", #item_ref)),
+                    #not_found
+                },
+            }))
+        });
+    }
+
+    let mut history_operations = vec![];
+    if props.audit.is_some() {
+        let operation_id = format!("{}_history", module_name);
+        schemas.push(quote!(("AuditEntry", ::rp1::openapi::audit_entry_schema())));
+        history_operations.push(quote! {
+            ("get", ::serde_json::json!({
+                "operationId": #operation_id,
+                "description": "Returns the recorded changes of an item, oldest first.",
+                "tags": [#name],
+                "parameters": [#(#id_parameters,)*],
+                "responses": {
+                    "200": ::rp1::openapi::json_response(
+                        "The changes of the item",
+                        ::serde_json::json!({
+                            "type": "array",
+                            "items": ::rp1::openapi::schema_ref("AuditEntry"),
+                        }),
+                    ),
+                    #not_found
+                },
+            }))
+        });
+    }
+
+    let mut events_operations = vec![];
+    if props.events {
+        let operation_id = format!("{}_events", module_name);
+        events_operations.push(quote! {
+            ("get", ::serde_json::json!({
+                "operationId": #operation_id,
+                "description": "Streams the created, updated and deleted items as server-sent events, \
+                    named after the change and containing the item as JSON. A `reset` event is sent first \
+                    when the events after the Last-Event-ID are no longer available.",
+                "tags": [#name],
+                "parameters": [
+                    ::rp1::openapi::parameter(
+                        "Last-Event-ID",
+                        "header",
+                        false,
+                        ::serde_json::json!({ "type": "integer" }),
+                    ),
+                ],
+                "responses": {
+                    "200": {
+                        "description": "The stream of changes",
+                        "content": {
+                            "text/event-stream": { "schema": #item_ref },
+                        },
+                    },
+                },
+            }))
+        });
+    }
+
+    let mut search_operations = vec![];
+    if props.list {
+        let (list_operation, list_schemas) = derive_list_operation(props);
+        root_operations.push(list_operation);
+        schemas.extend(list_schemas);
+        if props.search {
+            search_operations.push(derive_search_operation(props));
+        }
+        if props.bulk {
+            root_operations.extend(derive_bulk_operations(props));
+        }
+    }
+
+    let item_path = props
+        .primary_key_fields()
+        .map(|f| format!("/{{{}}}", f.ident))
+        .collect::<String>();
+    let restore_path = format!("{}/restore", item_path);
+    let history_path = format!("{}/history", item_path);
+    let nested_openapi = derive_nested_openapi(props, &item_path);
+    let many_to_many_operations = derive_many_to_many_openapi(props, &item_path);
+
+    quote! {
+        impl #ident {
+            /// Returns the OpenAPI description of the routes returned by
+            /// `get_routes`.
+            pub fn openapi() -> ::rp1::openapi::Resource {
+                let mut resource = ::rp1::openapi::Resource::default();
+                let schemas: Vec<(&str, ::serde_json::Value)> = vec![#(#schemas),*];
+                for (name, schema) in schemas {
+                    resource.schemas.insert(name.to_owned(), schema);
+                }
+
+                let paths: Vec<(&str, Vec<(&str, ::serde_json::Value)>)> = vec![
+                    ("/", vec![#(#root_operations),*]),
+                    (#item_path, vec![#(#item_operations),*]),
+                    (#restore_path, vec![#(#restore_operations),*]),
+                    (#history_path, vec![#(#history_operations),*]),
+                    ("/search", vec![#(#search_operations),*]),
+                    ("/events", vec![#(#events_operations),*]),
+                ];
+                for (path, operations) in paths {
+                    if !operations.is_empty() {
+                        let operations = operations
+                            .into_iter()
+                            .map(|(method, operation)| (method.to_owned(), operation))
+                            .collect::<::serde_json::Map<_, _>>();
+                        resource.paths.insert(path.to_owned(), ::serde_json::Value::Object(operations));
+                    }
+                }
+                #many_to_many_operations
+
+                resource
+            }
+
+            #nested_openapi
+        }
+    }
+}
+
+/// Derives the `nested_openapi` function describing the nested routes of a
+/// struct with a `parent`.
+fn derive_nested_openapi(props: &CrudProps, item_path: &str) -> Option<TokenStream> {
+    let parent = props.parent.as_ref()?;
+    let key = parent.foreign_key.to_string();
+    let field = props
+        .fields
+        .iter()
+        .find(|f| f.ident == parent.foreign_key)
+        .expect("foreign key fields are checked when parsing relations");
+    let schema = type_schema(field.inner_ty());
+    let prefix = format!("/{{{}}}/{}", key, props.table_name);
+    let mut operations = vec![];
+    if props.create {
+        operations.push(quote!(("/", "post")));
+    }
+    if props.read {
+        operations.push(quote!((#item_path, "get")));
+    }
+    if props.list {
+        operations.push(quote!(("/", "get")));
+    }
+
+    Some(quote! {
+        /// Returns the OpenAPI description of the routes returned by
+        /// `get_nested_routes`, to be added at the base of the parent.
+        pub fn nested_openapi() -> ::rp1::openapi::Resource {
+            Self::openapi().nested(
+                #prefix,
+                ::rp1::openapi::parameter(#key, "path", true, #schema),
+                &[#(#operations),*],
+                "nested",
+            )
+        }
+    })
+}
+
+/// Derives the statements adding the routes of the `many_to_many` relations
+/// to the resource. The list operation is taken from the related struct, which
+/// also provides the schemas of the related items.
+fn derive_many_to_many_openapi(props: &CrudProps, item_path: &str) -> TokenStream {
+    let name = props.ident.to_string();
+    let module_name = props.module_name.to_string();
+    let id_parameter = props.primary_key_fields().next().map(|f| {
+        let name = f.ident.to_string();
+        let schema = type_schema(&f.ty);
+        quote!(::rp1::openapi::parameter(#name, "path", true, #schema))
+    });
+    let relations = props.many_to_many.iter().map(|relation| {
+        let path = &relation.path;
+        let related_name = path
+            .segments
+            .last()
+            .map(|s| s.ident.to_string())
+            .unwrap_or_default();
+        let list_path = format!("{}/{}", item_path, relation.name);
+        let link_path = format!("{}/{{related_id}}", list_path);
+        let link_id = format!("{}_link_{}", module_name, relation.name);
+        let unlink_id = format!("{}_unlink_{}", module_name, relation.name);
+        let set_id = format!("{}_set_{}", module_name, relation.name);
+
+        quote! {
+            {
+                let related = #path::openapi();
+                let key_schema = related.key_schema();
+                let related_ref = ::rp1::openapi::schema_ref(#related_name);
+                let linked = related.nested(#list_path, #id_parameter, &[("/", "get")], #module_name);
+                resource.schemas.extend(linked.schemas);
+                resource.paths.extend(linked.paths);
+
+                resource.paths[#list_path]["put"] = ::serde_json::json!({
+                    "operationId": #set_id,
+                    "tags": [#name],
+                    "parameters": [#id_parameter],
+                    "requestBody": ::rp1::openapi::request_body(::serde_json::json!({
+                        "type": "array",
+                        "items": key_schema.clone(),
+                    })),
+                    "responses": {
+                        "200": ::rp1::openapi::json_response(
+                            "The linked items",
+                            ::serde_json::json!({ "type": "array", "items": related_ref.clone() }),
+                        ),
+                        "404": ::rp1::openapi::error_response("Item not found"),
+                    },
+                });
+                let parameters = ::serde_json::json!([
+                    #id_parameter,
+                    ::rp1::openapi::parameter("related_id", "path", true, key_schema),
+                ]);
+                resource.paths.insert(#link_path.to_owned(), ::serde_json::json!({
+                    "put": {
+                        "operationId": #link_id,
+                        "tags": [#name],
+                        "parameters": parameters.clone(),
+                        "responses": {
+                            "200": ::rp1::openapi::json_response("The linked item", related_ref),
+                            "404": ::rp1::openapi::error_response("Item not found"),
+                        },
+                    },
+                    "delete": {
+                        "operationId": #unlink_id,
+                        "tags": [#name],
+                        "parameters": parameters,
+                        "responses": {
+                            "200": ::rp1::openapi::json_response(
+                                "The number of deleted links",
+                                ::rp1::openapi::object_schema(
+                                    vec![("deleted", ::serde_json::json!({ "type": "integer" }))],
+                                    vec!["deleted"],
+                                ),
+                            ),
+                            "404": ::rp1::openapi::error_response("Item not found"),
+                        },
+                    },
+                }));
+            }
+        }
+    });
+
+    quote!(#(#relations)*)
+}
+
+fn derive_list_operation(props: &CrudProps) -> (TokenStream, Vec<TokenStream>) {
+    let name = props.ident.to_string();
+    let operation_id = format!("{}_list", props.module_name);
+    let max_limit = props.max_limit;
+    let mut schemas = vec![];
+
+    if props.partials {
+        let partial_name = props.partial_output_ident.to_string();
+        let partial_schema = derive_object_schema(props.fields.iter(), |_| false);
+        schemas.push(quote!((#partial_name, #partial_schema)));
+    }
+
+    let mut sort_fields = props
+        .sortable_fields()
+        .map(|f| f.ident.to_string())
+        .collect::<Vec<_>>();
+    if props.has_search() {
+        sort_fields.push("relevance".to_owned());
+    }
+    let sort_pattern = format!(
+        "^[-+]?({})(:(nullsfirst|nullslast|ci))*$",
+        sort_fields
+            .iter()
+            .cloned()
+            .chain(related_sort_fields(props))
+            .collect::<Vec<_>>()
+            .join("|")
+    );
+    let default_sort = props.default_sort.iter().map(|key| {
+        let mut value = format!("{}{}", if key.descending { "-" } else { "" }, key.field);
+        match key.nulls_first {
+            Some(true) => value.push_str(":nullsfirst"),
+            Some(false) => value.push_str(":nullslast"),
+            None => {}
+        }
+        if key.case_insensitive {
+            value.push_str(":ci");
+        }
+        value
+    });
+    let mut parameters = vec![
+        quote! {
+            {
+                let mut param = ::rp1::openapi::parameter("sort", "query", false, ::serde_json::json!({
+                    "type": "array",
+                    "items": { "type": "string", "pattern": #sort_pattern },
+                    "default": [#(#default_sort),*],
+                }));
+                param["description"] = ::serde_json::json!(
+                    "Fields to sort on, prefix a field with `-` to sort in descending order. Append `:nullsfirst` or `:nullslast` to control where null values are placed and `:ci` to sort text fields case-insensitively."
+                );
+                param
+            }
+        },
+        quote! {
+            ::rp1::openapi::parameter("offset", "query", false, ::serde_json::json!({
+                "type": "integer", "minimum": 0,
+            }))
+        },
+        quote! {
+            ::rp1::openapi::parameter("limit", "query", false, ::serde_json::json!({
+                "type": "integer", "minimum": 1, "maximum": #max_limit,
+            }))
+        },
+        quote! {
+            {
+                let mut param = ::rp1::openapi::parameter("after", "query", false, ::serde_json::json!({
+                    "type": "string",
+                }));
+                param["description"] = ::serde_json::json!(
+                    "Cursor from the `X-Next-Cursor` header of a previous response."
+                );
+                param
+            }
+        },
+    ];
+
+    parameters.extend(derive_filter_parameters(props));
+
+    if props.has_search() {
+        parameters.push(quote! {
+            {
+                let mut param = ::rp1::openapi::parameter("q", "query", false, ::serde_json::json!({
+                    "type": "string",
+                }));
+                param["description"] = ::serde_json::json!(#SEARCH_DESCRIPTION);
+                param
+            }
+        });
+    }
+
+    if props.partials {
+        let field_names = props
+            .fields
+            .iter()
+            .map(|f| f.ident.to_string())
+            .collect::<Vec<_>>();
+        for partial_param in &["include", "exclude"] {
+            parameters.push(quote! {
+                ::rp1::openapi::parameter(#partial_param, "query", false, ::serde_json::json!({
+                    "type": "array",
+                    "items": { "type": "string", "enum": [#(#field_names),*] },
+                }))
+            });
+        }
+    }
+
+    parameters.extend(derive_embed_parameter(props));
+    parameters.extend(derive_with_deleted_parameter(props));
+
+    let response = derive_list_response(props);
+    let forbidden = if props.auth {
+        Some(quote! {
+            "403": ::rp1::openapi::error_response("Not allowed"),
+        })
+    } else {
+        None
+    };
+
+    let mut description = "Filters can be combined using nested `filter[or][label]`, `filter[and][label]` and `filter[not]` groups, e.g. `filter[or][0][field]=a&filter[or][1][field]=b`.".to_owned();
+    if !props.relations.is_empty() {
+        let relations = props
+            .relations
+            .iter()
+            .map(|r| format!("`{}`", r.name))
+            .collect::<Vec<_>>();
+        description.push_str(&format!(
+            " The filterable and sortable fields of related items can be used by prefixing them with the name of the relation, e.g. `filter[relation.field]=a` or `sort=relation.field`. Available relations: {}.",
+            relations.join(", ")
+        ));
+    }
+
+    let operation = quote! {
+        ("get", {
+            let response = #response;
+            let parameters: Vec<::serde_json::Value> = vec![#(#parameters),*];
+
+            ::serde_json::json!({
+                "operationId": #operation_id,
+                "description": #description,
+                "tags": [#name],
+                "parameters": parameters,
+                "responses": {
+                    "200": response,
+                    "304": ::rp1::openapi::not_modified_response(),
+                    "400": ::rp1::openapi::error_response("Invalid sort, filter or cursor"),
+                    #forbidden
+                },
+            })
+        })
+    };
+
+    (operation, schemas)
+}
+
+/// Derives the `PATCH /` and `DELETE /` operations changing all items that
+/// match the filters.
+fn derive_bulk_operations(props: &CrudProps) -> Vec<TokenStream> {
+    let name = props.ident.to_string();
+    let module_name = props.module_name.to_string();
+    let filter_parameters = derive_filter_parameters(props);
+    let mut description =
+        "Changes all items matching the filters, at least one filter is required.".to_owned();
+    if let Some(bulk_limit) = props.bulk_limit {
+        description.push_str(&format!(
+            " Nothing is changed if more than {} items match.",
+            bulk_limit
+        ));
+    }
+    let forbidden = if props.auth {
+        Some(quote! {
+            "403": ::rp1::openapi::error_response("Not allowed"),
+        })
+    } else {
+        None
+    };
+    let count_response = |count: &str| {
+        let description = format!("The number of {} items", count);
+        quote! {
+            ::rp1::openapi::json_response(
+                #description,
+                ::rp1::openapi::object_schema(
+                    vec![(#count, ::serde_json::json!({ "type": "integer" }))],
+                    vec![#count],
+                ),
+            )
+        }
+    };
+
+    let mut operations = vec![];
+    if props.update {
+        let operation_id = format!("{}_bulk_update", module_name);
+        let patch_name = props.patch_ident.to_string();
+        let response = count_response("updated");
+        operations.push(quote! {
+            ("patch", {
+                let parameters: Vec<::serde_json::Value> = vec![#(#filter_parameters),*];
+                ::serde_json::json!({
+                    "operationId": #operation_id,
+                    "description": #description,
+                    "tags": [#name],
+                    "parameters": parameters,
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": ::rp1::openapi::schema_ref(#patch_name),
+                            },
+                        },
+                    },
+                    "responses": {
+                        "200": #response,
+                        "400": ::rp1::openapi::error_response("Invalid filter or item, or too many items"),
+                        #forbidden
+                    },
+                })
+            })
+        });
+    }
+    if props.delete {
+        let operation_id = format!("{}_bulk_delete", module_name);
+        let response = count_response("deleted");
+        operations.push(quote! {
+            ("delete", {
+                let parameters: Vec<::serde_json::Value> = vec![#(#filter_parameters),*];
+                ::serde_json::json!({
+                    "operationId": #operation_id,
+                    "description": #description,
+                    "tags": [#name],
+                    "parameters": parameters,
+                    "responses": {
+                        "200": #response,
+                        "400": ::rp1::openapi::error_response("Invalid filter, or too many items"),
+                        #forbidden
+                    },
+                })
+            })
+        });
+    }
+
+    operations
+}
+
+/// Derives the `filter` query parameters of every operator of the filterable
+/// fields.
+fn derive_filter_parameters(props: &CrudProps) -> Vec<TokenStream> {
+    let mut parameters = vec![];
+    for field in props.filterable_fields() {
+        let inner_ty = option_inner_type(&field.ty).unwrap_or(&field.ty);
+        let schema = type_schema(inner_ty);
+        let text_operators = if field.is_text {
+            TEXT_FILTER_OPERATORS
+        } else {
+            &[]
+        };
+        let null_operators = if field.is_option {
+            NULL_FILTER_OPERATORS
+        } else {
+            &[]
+        };
+        let operators = FILTER_OPERATORS
+            .iter()
+            .chain(text_operators)
+            .chain(null_operators);
+        for (operator, description) in operators {
+            let param_name = if *operator == "eq" {
+                format!("filter[{}]", field.ident)
+            } else {
+                format!("filter[{}]{}", field.ident, operator)
+            };
+            let schema = if *operator == "in" {
+                quote!(::serde_json::json!({ "type": "string" }))
+            } else if null_operators.iter().any(|(op, _)| op == operator) {
+                quote!(::serde_json::json!({ "type": "boolean" }))
+            } else {
+                schema.clone()
+            };
+            parameters.push(quote! {
+                {
+                    let mut param = ::rp1::openapi::parameter(#param_name, "query", false, #schema);
+                    param["description"] = ::serde_json::json!(#description);
+                    param
+                }
+            });
+        }
+    }
+
+    parameters
+}
+
+/// Description of the `embed` parameter.
+const EMBED_DESCRIPTION: &str =
+    "Related items to embed in every item, under the name of the relation.";
+
+/// Derives the `embed` query parameter of the read and list operations, if
+/// the struct has any relations.
+fn derive_embed_parameter(props: &CrudProps) -> Option<TokenStream> {
+    if !props.has_embeds() {
+        return None;
+    }
+
+    let names = props.embed_names().map(|n| n.to_string());
+    let description = format!(
+        "{} Multiple relations can be separated by commas.",
+        EMBED_DESCRIPTION
+    );
+    Some(quote! {
+        {
+            let mut param = ::rp1::openapi::parameter("embed", "query", false, ::serde_json::json!({
+                "type": "array",
+                "items": { "type": "string", "enum": [#(#names),*] },
+            }));
+            param["style"] = ::serde_json::json!("form");
+            param["explode"] = ::serde_json::json!(false);
+            param["description"] = ::serde_json::json!(#description);
+            param
+        }
+    })
+}
+
+/// Derives the `with_deleted` parameter of the read and list operations, for
+/// a struct with a `#[soft_delete]` field.
+fn derive_with_deleted_parameter(props: &CrudProps) -> Option<TokenStream> {
+    props.soft_delete_field()?;
+    Some(quote! {
+        {
+            let mut param = ::rp1::openapi::parameter("with_deleted", "query", false, ::serde_json::json!({
+                "type": "boolean",
+                "default": false,
+            }));
+            param["description"] = ::serde_json::json!(#WITH_DELETED_DESCRIPTION);
+            param
+        }
+    })
+}
+
+/// Patterns matching the sortable fields of related structs, which are not
+/// known when deriving the schema of this struct.
+fn related_sort_fields(props: &CrudProps) -> impl Iterator<Item = String> + '_ {
+    props
+        .relations
+        .iter()
+        .map(|r| format!("{}\\.[A-Za-z0-9_.]+", r.name))
+}
+
+/// Derives the search operation, which accepts the list parameters as a JSON
+/// body.
+fn derive_search_operation(props: &CrudProps) -> TokenStream {
+    let name = props.ident.to_string();
+    let operation_id = format!("{}_search", props.module_name);
+    let max_limit = props.max_limit;
+    let mut sort_fields = props
+        .sortable_fields()
+        .map(|f| f.ident.to_string())
+        .collect::<Vec<_>>();
+    let search_property = if props.has_search() {
+        sort_fields.push("relevance".to_owned());
+        Some(quote! {
+            ("q", ::serde_json::json!({ "type": "string", "description": #SEARCH_DESCRIPTION })),
+        })
+    } else {
+        None
+    };
+    // the fields of related items are not known here, so these are
+    // described using a pattern
+    let sort_field_schema = if props.relations.is_empty() {
+        quote!(::serde_json::json!({ "type": "string", "enum": [#(#sort_fields),*] }))
+    } else {
+        let pattern = format!(
+            "^({})$",
+            sort_fields
+                .iter()
+                .cloned()
+                .chain(related_sort_fields(props))
+                .collect::<Vec<_>>()
+                .join("|")
+        );
+        quote!(::serde_json::json!({ "type": "string", "pattern": #pattern }))
+    };
+    let partial_properties = if props.partials {
+        let field_names = props
+            .fields
+            .iter()
+            .map(|f| f.ident.to_string())
+            .collect::<Vec<_>>();
+        Some(quote! {
+            ("include", ::serde_json::json!({
+                "type": "array",
+                "items": { "type": "string", "enum": [#(#field_names),*] },
+            })),
+            ("exclude", ::serde_json::json!({
+                "type": "array",
+                "items": { "type": "string", "enum": [#(#field_names),*] },
+            })),
+        })
+    } else {
+        None
+    };
+    let embed_property = if props.has_embeds() {
+        let names = props.embed_names().map(|n| n.to_string());
+        Some(quote! {
+            ("embed", ::serde_json::json!({
+                "type": "array",
+                "items": { "type": "string", "enum": [#(#names),*] },
+                "description": #EMBED_DESCRIPTION,
+            })),
+        })
+    } else {
+        None
+    };
+    let with_deleted_property = if props.soft_delete_field().is_some() {
+        Some(quote! {
+            ("with_deleted", ::serde_json::json!({ "type": "boolean", "description": #WITH_DELETED_DESCRIPTION })),
+        })
+    } else {
+        None
+    };
+    let response = derive_list_response(props);
+    let forbidden = if props.auth {
+        Some(quote! {
+            "403": ::rp1::openapi::error_response("Not allowed"),
+        })
+    } else {
+        None
+    };
+
+    quote! {
+        ("post", {
+            let query_schema = ::rp1::openapi::object_schema(
+                vec![
+                    ("filter", ::serde_json::json!({
+                        "type": "object",
+                        "description": "Filters using the same structure as the `filter` query parameters of the list operation, e.g. `{ \"or\": [{ \"field\": \"a\" }, { \"field\": { \"in\": [\"b\", \"c\"] } }] }`.",
+                    })),
+                    ("sort", ::serde_json::json!({
+                        "type": "array",
+                        "items": ::rp1::openapi::object_schema(
+                            vec![
+                                ("field", #sort_field_schema),
+                                ("direction", ::serde_json::json!({ "type": "string", "enum": ["asc", "desc"] })),
+                                ("nulls", ::serde_json::json!({ "type": "string", "enum": ["first", "last"] })),
+                                ("case_insensitive", ::serde_json::json!({ "type": "boolean" })),
+                            ],
+                            vec!["field", "direction"],
+                        ),
+                    })),
+                    ("offset", ::serde_json::json!({ "type": "integer", "minimum": 0 })),
+                    ("limit", ::serde_json::json!({ "type": "integer", "minimum": 1, "maximum": #max_limit })),
+                    ("after", ::serde_json::json!({ "type": "string" })),
+                    #partial_properties
+                    #search_property
+                    #embed_property
+                    #with_deleted_property
+                ],
+                vec![],
+            );
+            let response = #response;
+
+            ::serde_json::json!({
+                "operationId": #operation_id,
+                "tags": [#name],
+                "requestBody": ::rp1::openapi::request_body(query_schema),
+                "responses": {
+                    "200": response,
+                    "400": ::rp1::openapi::error_response("Invalid search query"),
+                    #forbidden
+                },
+            })
+        })
+    }
+}
+
+/// Derives the response of the list and search operations.
+fn derive_list_response(props: &CrudProps) -> TokenStream {
+    let output_name = if props.partials {
+        props.partial_output_ident.to_string()
+    } else {
+        props.ident.to_string()
+    };
+    let items_schema = quote! {
+        ::serde_json::json!({
+            "type": "array",
+            "items": ::rp1::openapi::schema_ref(#output_name),
+        })
+    };
+    let (response_schema, pagination_headers) = if props.pagination {
+        let schema = quote! {
+            ::rp1::openapi::object_schema(
+                vec![
+                    ("items", #items_schema),
+                    ("total", ::serde_json::json!({ "type": "integer" })),
+                    ("offset", ::serde_json::json!({ "type": "integer" })),
+                    ("limit", ::serde_json::json!({ "type": "integer" })),
+                    ("next", ::serde_json::json!({ "type": "string", "nullable": true })),
+                    ("prev", ::serde_json::json!({ "type": "string", "nullable": true })),
+                    ("cursor", ::serde_json::json!({ "type": "string", "nullable": true })),
+                ],
+                vec!["items", "total", "offset", "limit", "next", "prev", "cursor"],
+            )
+        };
+        let headers = quote! {
+            "X-Total-Count": { "schema": { "type": "integer" } },
+            "Link": { "schema": { "type": "string" } },
+        };
+        (schema, Some(headers))
+    } else {
+        (items_schema, None)
+    };
+    let cache_headers = derive_cache_headers(props, quote!(response));
+
+    quote! {
+        {
+            let mut response = ::rp1::openapi::json_response("A list of items", #response_schema);
+            response["headers"] = ::serde_json::json!({
+                "X-Next-Cursor": { "schema": { "type": "string" } },
+                #pagination_headers
+            });
+            #cache_headers
+        }
+    }
+}
+
+/// Adds the headers of the read and list responses to the `response`, see
+/// `rp1::Cacheable`.
+fn derive_cache_headers(props: &CrudProps, response: TokenStream) -> TokenStream {
+    let last_modified = props.last_modified_field().is_some();
+    let cache_control = props.cache_control.is_some();
+    quote!(::rp1::openapi::with_cache_headers(#response, #last_modified, #cache_control))
+}
+
+/// Derives an object schema for the given fields.
+fn derive_object_schema<'a>(
+    fields: impl Iterator<Item = &'a CrudField>,
+    required: impl Fn(&CrudField) -> bool,
+) -> TokenStream {
+    let mut properties = vec![];
+    let mut required_names = vec![];
+    for field in fields {
+        let (name, _) = serde_field_options(field);
+        let schema = type_schema(&field.ty);
+        if required(field) {
+            required_names.push(name.clone());
+        }
+        properties.push(quote!((#name, #schema)));
+    }
+
+    quote! {
+        ::rp1::openapi::object_schema(vec![#(#properties),*], vec![#(#required_names),*])
+    }
+}
+
+/// Fields must be given in a request body, unless they are optional or have a
+/// default value.
+fn is_required(field: &CrudField) -> bool {
+    let (_, has_default) = serde_field_options(field);
+    !field.is_option && !has_default
+}
+
+/// Returns the name of the field as it is (de)serialized and whether the field
+/// has a default value, based on the serde attributes of the field.
+fn serde_field_options(field: &CrudField) -> (String, bool) {
+    let mut name = field.ident.to_string();
+    let mut has_default = false;
+    for attr in field.attrs.iter().filter(|a| a.path.is_ident("serde")) {
+        if let Ok(Meta::List(list)) = attr.parse_meta() {
+            for nested in list.nested.iter() {
+                match nested {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("default") => {
+                        has_default = true;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("default") => {
+                        has_default = true;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename") => {
+                        if let Lit::Str(rename) = &nv.lit {
+                            name = rename.value();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    (name, has_default)
+}
+
+fn option_inner_type(ty: &Type) -> Option<&Type> {
+    generic_argument(ty, "Option")
+}
+
+/// Returns the first generic argument of `ty` if the last segment of its path
+/// is `name`, e.g. `T` for `Option<T>`.
+fn generic_argument<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    match ty {
+        Type::Path(path) => {
+            let segment = path.path.segments.last()?;
+            match &segment.arguments {
+                PathArguments::AngleBracketed(args) if segment.ident == name => {
+                    args.args.iter().find_map(|arg| match arg {
+                        GenericArgument::Type(ty) => Some(ty),
+                        _ => None,
+                    })
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Derives the JSON schema for a rust type. The type is only known by name
+/// here, so types that are not recognized will allow any value.
+fn type_schema(ty: &Type) -> TokenStream {
+    if let Some(inner) = option_inner_type(ty) {
+        let inner = type_schema(inner);
+        return quote!(::rp1::openapi::nullable(#inner));
+    }
+    if let Some(inner) = generic_argument(ty, "Vec") {
+        let inner = type_schema(inner);
+        return quote!(::serde_json::json!({ "type": "array", "items": #inner }));
+    }
+
+    let type_name = match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|s| s.ident.to_string())
+            .unwrap_or_default(),
+        Type::Reference(reference) => return type_schema(&reference.elem),
+        _ => String::new(),
+    };
+    let (json_type, format) = match type_name.as_str() {
+        "i8" | "i16" | "i32" | "u8" | "u16" | "u32" => ("integer", Some("int32")),
+        "i64" | "u64" | "isize" | "usize" => ("integer", Some("int64")),
+        "f32" => ("number", Some("float")),
+        "f64" => ("number", Some("double")),
+        "bool" => ("boolean", None),
+        "String" | "str" | "char" => ("string", None),
+        "Date" | "NaiveDate" => ("string", Some("date")),
+        "Time" | "NaiveTime" => ("string", Some("time")),
+        "PrimitiveDateTime" | "OffsetDateTime" | "NaiveDateTime" | "DateTime" => {
+            ("string", Some("date-time"))
+        }
+        "Uuid" => ("string", Some("uuid")),
+        _ => return quote!(::serde_json::json!({})),
+    };
+
+    match format {
+        Some(format) => quote!(::serde_json::json!({ "type": #json_type, "format": #format })),
+        None => quote!(::serde_json::json!({ "type": #json_type })),
+    }
+}
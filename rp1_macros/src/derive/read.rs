@@ -2,7 +2,16 @@ use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use syn::Ident;
 
-use crate::{derive::common::derive_auth_param, props::CrudProps};
+use crate::{
+    derive::{
+        common::{
+            derive_auth_param, derive_cache_control, derive_id_binding, derive_id_params,
+            derive_id_route, derive_last_modified, derive_with_deleted, WithDeleted,
+        },
+        nested::{derive_parent_route, ParentRoute},
+    },
+    props::CrudProps,
+};
 
 pub(crate) fn derive_crud_read(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
     let CrudProps {
@@ -15,6 +24,9 @@ pub(crate) fn derive_crud_read(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
     } = props;
 
     let auth_param = derive_auth_param(props);
+    let id_route = derive_id_route(props);
+    let id_params = derive_id_params(props);
+    let id_binding = derive_id_binding(props);
     let auth_check = if props.auth {
         quote! {
             if <#ident as ::rp1::CheckPermissions>::allow_read(&row, &auth_user) {
@@ -27,23 +39,215 @@ pub(crate) fn derive_crud_read(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
         quote!(row)
     };
 
+    let auth_arg = if props.auth {
+        Some(quote!(auth_user,))
+    } else {
+        None
+    };
+    let (embed_param, embed_parse, embed_item_param, embed_arg, result_type, embed_stmt) =
+        if props.has_embeds() {
+            let auth_arg = if props.auth {
+                quote!(&auth_user)
+            } else {
+                quote!(&())
+            };
+            (
+                Some(quote!(embed: Vec<String>,)),
+                Some(quote! {
+                    let embed = ::rp1::parse_embed::<Embed>(&embed)?;
+                }),
+                Some(quote!(embed: Vec<Embed>,)),
+                Some(quote!(embed,)),
+                quote!(::rp1::Embedded<#ident>),
+                quote! {
+                    let embedded = if embed.is_empty() {
+                        ::serde_json::Map::new()
+                    } else {
+                        let keys = vec![EmbedKeys::from(&row)];
+                        db.run(move |conn| load_embedded(conn, &embed, &keys, #auth_arg))
+                            .await?
+                            .pop()
+                            .unwrap_or_default()
+                    };
+                    let row = ::rp1::Embedded::new(row, embedded);
+                },
+            )
+        } else {
+            (None, None, None, None, quote!(#ident), quote!())
+        };
+
+    // soft deleted items are only found when they are explicitly requested
+    let with_deleted = derive_with_deleted(props);
+    let (
+        with_deleted_param,
+        with_deleted_item_param,
+        with_deleted_arg,
+        with_deleted_check,
+        load_row,
+    ) = match with_deleted {
+        Some(WithDeleted {
+            param,
+            item_param,
+            check,
+            filter,
+        }) => (
+            Some(param),
+            Some(item_param),
+            Some(quote!(with_deleted.unwrap_or(false),)),
+            check,
+            quote! {
+                let mut query = #schema_path::#table_name::table
+                    .find(id)
+                    .into_boxed();
+                #filter
+                query.first::<#ident>(conn)
+            },
+        ),
+        None => (
+            None,
+            None,
+            None,
+            None,
+            quote! {
+                #schema_path::#table_name::table
+                    .find(id)
+                    .first::<#ident>(conn)
+            },
+        ),
+    };
+    let mut query_params = vec![];
+    if props.has_embeds() {
+        query_params.push("<embed>");
+    }
+    if with_deleted_param.is_some() {
+        query_params.push("<with_deleted>");
+    }
+    let route = if query_params.is_empty() {
+        id_route
+    } else {
+        format!("{}?{}", id_route, query_params.join("&"))
+    };
+
+    // the version of an item is used as its `ETag`, unless related items are
+    // embedded in the response, which can change without changing the version
+    let etag = match props.version_field() {
+        Some(version) if props.has_embeds() => {
+            let version = &version.ident;
+            quote! {
+                let version = if embed.is_empty() {
+                    Some(::rp1::etag(&row.#version))
+                } else {
+                    None
+                };
+            }
+        }
+        Some(version) => {
+            let version = &version.ident;
+            quote!(let version = Some(::rp1::etag(&row.#version));)
+        }
+        None => quote!(let version: Option<String> = None;),
+    };
+    let last_modified = derive_last_modified(props, quote!(row));
+    let cache_control = derive_cache_control(props);
+    let result_type =
+        quote!(::rp1::CrudResult<::rp1::Cacheable<::rocket::serde::json::Json<#result_type>>>);
+
+    // items of the nested read route should belong to the parent in the path
+    let parent = derive_parent_route(props);
+    let (parent_item_param, parent_arg, parent_check) = match &parent {
+        Some(ParentRoute { key, key_type, .. }) => {
+            let field = props
+                .fields
+                .iter()
+                .find(|f| f.ident == *key)
+                .expect("foreign key fields are checked when parsing relations");
+            let row_key = if field.is_option {
+                quote!(row.#key.as_ref())
+            } else {
+                quote!(Some(&row.#key))
+            };
+            (
+                Some(quote!(parent: Option<#key_type>,)),
+                Some(quote!(None,)),
+                Some(quote! {
+                    if let Some(parent) = parent {
+                        if #row_key != Some(&parent) {
+                            return Err(::rp1::CrudError::NotFound);
+                        }
+                    }
+                }),
+            )
+        }
+        None => (None, None, None),
+    };
+    let nested_read = parent.map(
+        |ParentRoute {
+             path,
+             key,
+             key_type,
+             check,
+         }| {
+            let route = format!("{}{}", path, route);
+            quote! {
+                #[::rocket::get(#route)]
+                async fn nested_read_fn(
+                    db: #database_struct,
+                    #key: #key_type,
+                    id: #primary_type,
+                    #embed_param
+                    #with_deleted_param
+                    #auth_param
+                ) -> #result_type
+                {
+                    #embed_parse
+                    #check
+                    read_fn_help(db, id, Some(#key), #embed_arg #with_deleted_arg #auth_arg).await
+                }
+            }
+        },
+    );
+
     let tokens = quote! {
 
-        #[::rocket::get("/<id>")]
+        #[::rocket::get(#route)]
         async fn read_fn(
+            db: #database_struct,
+            #id_params
+            #embed_param
+            #with_deleted_param
+            #auth_param
+        ) -> #result_type
+        {
+            #id_binding
+            #embed_parse
+            read_fn_help(db, id, #parent_arg #embed_arg #with_deleted_arg #auth_arg).await
+        }
+
+        #nested_read
+
+        async fn read_fn_help(
             db: #database_struct,
             id: #primary_type,
+            #parent_item_param
+            #embed_item_param
+            #with_deleted_item_param
             #auth_param
-        ) -> ::rp1::CrudJsonResult<#ident>
+        ) -> #result_type
         {
+            #with_deleted_check
             let row = db.run(move |conn| {
-                #schema_path::#table_name::table
-                    .find(id)
-                    .first::<#ident>(conn)
+                #load_row
             })
             .await?;
+            #parent_check
             let row = #auth_check;
-            Ok(::rocket::serde::json::Json(row))
+            #etag
+            let last_modified = #last_modified;
+            #embed_stmt
+            let etag = version.unwrap_or_else(|| ::rp1::content_etag(&row));
+            Ok(::rp1::Cacheable::new(::rocket::serde::json::Json(row), etag)
+                .last_modified(last_modified)
+                .cache_control(#cache_control))
         }
     };
 
@@ -2,7 +2,18 @@ use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use syn::Ident;
 
-use crate::{derive::common::derive_auth_param, props::CrudProps};
+use crate::{
+    derive::{
+        audit::{derive_audit_actor, derive_audit_write},
+        common::{
+            derive_auth_param, derive_changeset, derive_find_for_update, derive_id_binding,
+            derive_id_params, derive_id_route, derive_if_match_param, derive_item_response,
+            derive_update_returning, derive_version_check, derive_write_transaction, ItemResponse,
+        },
+        events::{derive_events_param, derive_publish, EventsParam},
+    },
+    props::CrudProps,
+};
 
 pub(crate) fn derive_crud_update(props: &CrudProps) -> (TokenStream, Vec<Ident>) {
     let CrudProps {
@@ -10,15 +21,101 @@ pub(crate) fn derive_crud_update(props: &CrudProps) -> (TokenStream, Vec<Ident>)
         ident,
         patch_ident,
         put_ident,
+        primary_type,
         schema_path,
         table_name,
-        primary_type,
         ..
     } = props;
 
     let update_types = derive_update_types(props);
+    let transaction = derive_write_transaction(props);
+    let find_row = derive_find_for_update(props);
+    let ItemResponse {
+        result_type,
+        etag,
+        response,
+    } = derive_item_response(props, quote!(#ident));
+
+    // an item with a version is only updated if the version is unchanged
+    let if_match_param = derive_if_match_param(props);
+    let if_match_arg = if_match_param.as_ref().map(|_| quote!(if_match,));
+    let version_check = derive_version_check(props);
+    let update_row = match props.version_field() {
+        Some(version) => {
+            let version = &version.ident;
+            let changeset = derive_changeset(props, quote!(&value));
+            quote! {
+                let updated = diesel::update(
+                    #schema_path::#table_name::table
+                        .find(id.clone())
+                        .filter(#schema_path::#table_name::columns::#version.eq(row.#version.clone())),
+                )
+                .set(#changeset)
+                .execute(conn)?;
+                if updated == 0 {
+                    return Err(::rp1::CrudError::PreconditionFailed);
+                }
+                let new_row = #schema_path::#table_name::table.find(id).first::<#ident>(conn)?;
+            }
+        }
+        None => {
+            let update_returning = derive_update_returning(props);
+            quote!(let new_row = #update_returning?;)
+        }
+    };
+
+    // the hooks are given the complete new item, also for a partial update,
+    // which is then updated as a whole. The permissions are checked again for
+    // the item as changed by the hook.
+    let hook_auth_check = if props.auth {
+        Some(quote! {
+            if !<#ident as ::rp1::CheckPermissions>::allow_update(&row, &value, &auth_user) {
+                return Err(::rp1::CrudError::NotFound);
+            }
+        })
+    } else {
+        None
+    };
+    let (before_put_hook, before_patch_hook, after_hook) = if props.hooks {
+        (
+            Some(quote! {
+                let mut value = value;
+                <#ident as ::rp1::CrudHooks>::before_update(&row, &mut value, conn)?;
+                #hook_auth_check
+            }),
+            Some(quote! {
+                let mut value = #put_ident::create(&row, &value);
+                <#ident as ::rp1::CrudHooks>::before_update(&row, &mut value, conn)?;
+                #hook_auth_check
+                value.validate_update(&row)?;
+            }),
+            Some(quote! {
+                <#ident as ::rp1::CrudHooks>::after_update(&new_row, conn)?;
+            }),
+        )
+    } else {
+        (None, None, None)
+    };
+    let audit_actor = derive_audit_actor(props);
+    let audit = derive_audit_write(
+        props,
+        "Update",
+        quote!(row),
+        quote!(Some(&row)),
+        quote!(Some(&new_row)),
+    );
+
+    let EventsParam {
+        route_param: events_route_param,
+        param: events_param,
+        arg: events_arg,
+    } = derive_events_param(props);
+    let publish = derive_publish(props, "Updated", quote!(row.clone()));
 
     let auth_param = derive_auth_param(props);
+    let id_route = derive_id_route(props);
+    let id_params = derive_id_params(props);
+    let id_binding = derive_id_binding(props);
     let auth_pass = if props.auth {
         Some(quote!(auth_user,))
     } else {
@@ -33,14 +130,14 @@ pub(crate) fn derive_crud_update(props: &CrudProps) -> (TokenStream, Vec<Ident>)
     } else {
         None
     };
+    let load_patch_row =
+        if props.auth || props.hooks || props.audit.is_some() || props.version_field().is_some() {
+            Some(quote!(let row = #find_row;))
+        } else {
+            None
+        };
     let auth_patch_check = if props.auth {
         Some(quote! {
-            let row = db.run(move |conn| {
-                #schema_path::#table_name::table
-                    .find(id)
-                    .first::<#ident>(conn)
-            })
-            .await?;
             let put_value = #put_ident::create(&row, &value);
             if !<#ident as ::rp1::CheckPermissions>::allow_update(&row, &put_value, &auth_user) {
                 return Err(::rp1::CrudError::NotFound);
@@ -66,95 +163,127 @@ pub(crate) fn derive_crud_update(props: &CrudProps) -> (TokenStream, Vec<Ident>)
             db: #database_struct,
             id: #primary_type,
             value: #put_ident,
+            #if_match_param
+            #events_param
             #auth_param
-        ) -> ::rp1::CrudJsonResult<#ident>
+        ) -> #result_type
         {
+            #audit_actor
+            // the row is checked and updated in a single transaction, so it
+            // cannot be changed by another request in between
             let row = db.run(move |conn| {
-                #schema_path::#table_name::table
-                    .find(id)
-                    .first::<#ident>(conn)
-            })
-            .await?;
+                #transaction(|| -> ::rp1::CrudResult<_> {
+                    let row = #find_row;
 
-            #auth_put_check
+                    #auth_put_check
+                    #version_check
+                    #before_put_hook
 
-            value.validate_update(&row)?;
+                    value.validate_update(&row)?;
 
-            #validate
+                    #validate
 
-            Ok(::rocket::serde::json::Json(db.run(move |conn| {
-                diesel::update(#schema_path::#table_name::table.find(id))
-                    .set(&value)
-                    .get_result(conn)
+                    #update_row
+                    #after_hook
+                    #audit
+                    Ok(new_row)
+                })
             })
-            .await?))
+            .await?;
+            #publish
+            #etag
+            Ok(#response)
         }
 
         async fn update_patch_fn_help(
             db: #database_struct,
             id: #primary_type,
             value: #patch_ident,
+            #if_match_param
+            #events_param
             #auth_param
-        ) -> ::rp1::CrudJsonResult<#ident>
+        ) -> #result_type
         {
-            #auth_patch_check
+            #audit_actor
+            let row = db.run(move |conn| {
+                #transaction(|| -> ::rp1::CrudResult<_> {
+                    #load_patch_row
+                    #auth_patch_check
+                    #version_check
+                    #before_patch_hook
 
-            #validate
+                    #validate
 
-            Ok(::rocket::serde::json::Json(db.run(move |conn| {
-                diesel::update(#schema_path::#table_name::table.find(id))
-                    .set(&value)
-                    .get_result(conn)
+                    #update_row
+                    #after_hook
+                    #audit
+                    Ok(new_row)
+                })
             })
-            .await?))
+            .await?;
+            #publish
+            #etag
+            Ok(#response)
         }
 
-        #[::rocket::patch("/<id>", format = "json", data = "<value>")]
+        #[::rocket::patch(#id_route, format = "json", data = "<value>")]
         async fn update_patch_fn_json(
             db: #database_struct,
-            id: #primary_type,
+            #id_params
             value: ::rocket::serde::json::Json<#patch_ident>,
+            #if_match_param
+            #events_route_param
             #auth_param
-        ) -> ::rp1::CrudJsonResult<#ident>
+        ) -> #result_type
         {
+            #id_binding
             let value = value.into_inner();
-            update_patch_fn_help(db, id, value, #auth_pass).await
+            update_patch_fn_help(db, id, value, #if_match_arg #events_arg #auth_pass).await
         }
 
-        #[::rocket::patch("/<id>", format = "form", data = "<value>")]
+        #[::rocket::patch(#id_route, format = "form", data = "<value>")]
         async fn update_patch_fn_form(
             db: #database_struct,
-            id: #primary_type,
+            #id_params
             value: ::rocket::form::Form<#patch_ident>,
+            #if_match_param
+            #events_route_param
             #auth_param
-        ) -> ::rp1::CrudJsonResult<#ident>
+        ) -> #result_type
         {
+            #id_binding
             let value = value.into_inner();
-            update_patch_fn_help(db, id, value, #auth_pass).await
+            update_patch_fn_help(db, id, value, #if_match_arg #events_arg #auth_pass).await
         }
 
-        #[::rocket::put("/<id>", format = "json", data = "<value>")]
+        #[::rocket::put(#id_route, format = "json", data = "<value>")]
         async fn update_put_fn_json(
             db: #database_struct,
-            id: #primary_type,
+            #id_params
             value: ::rocket::serde::json::Json<#put_ident>,
+            #if_match_param
+            #events_route_param
             #auth_param
-        ) -> ::rp1::CrudJsonResult<#ident>
+        ) -> #result_type
         {
+            #id_binding
             let value = value.into_inner();
-            update_put_fn_help(db, id, value, #auth_pass).await
+            update_put_fn_help(db, id, value, #if_match_arg #events_arg #auth_pass).await
         }
 
-        #[::rocket::put("/<id>", format = "form", data = "<value>")]
+        #[::rocket::put(#id_route, format = "form", data = "<value>")]
         async fn update_put_fn_form(
             db: #database_struct,
-            id: #primary_type,
+            #id_params
             value: ::rocket::serde::json::Json<#put_ident>,
+            #if_match_param
+            #events_route_param
             #auth_param
-        ) -> ::rp1::CrudJsonResult<#ident>
+        ) -> #result_type
         {
+            #id_binding
             let value = value.into_inner();
-            update_put_fn_help(db, id, value, #auth_pass).await
+            update_put_fn_help(db, id, value, #if_match_arg #events_arg #auth_pass).await
         }
     };
     (
@@ -180,6 +309,31 @@ fn derive_update_types(props: &CrudProps) -> TokenStream {
         .non_user_supplied_fields()
         .map(|f| f.ident.clone())
         .collect::<Vec<_>>();
+    let unchangeable_fields = props
+        .non_user_supplied_fields()
+        .filter(|f| !f.is_version)
+        .map(|f| f.ident.clone())
+        .collect::<Vec<_>>();
+
+    // a changed version in the body of a put request means that the item was
+    // changed since it was retrieved
+    let (version_check, put_primary_key) = match props.version_field() {
+        Some(version) => {
+            let version = &version.ident;
+            // diesel never sets the primary key of a changeset, which keeps
+            // the version out of it, as it is changed separately
+            let keys = props.primary_key_fields().map(|f| &f.ident);
+            (
+                Some(quote! {
+                    if self.#version != base.#version {
+                        return Err(::rp1::CrudError::PreconditionFailed);
+                    }
+                }),
+                Some(quote!(#[primary_key(#(#keys,)* #version)])),
+            )
+        }
+        None => (None, None),
+    };
 
     // Only forward serde attributes for now
     let attrs = props.item.attrs
@@ -222,6 +376,7 @@ fn derive_update_types(props: &CrudProps) -> TokenStream {
         #derive_validate
         #(#attrs)*
         #[table_name = #table_name]
+        #put_primary_key
         pub struct #put_ident {
             #(#put_fields),*
         }
@@ -243,9 +398,10 @@ fn derive_update_types(props: &CrudProps) -> TokenStream {
             }
 
             pub fn validate_update(&self, base: &#ident) -> ::rp1::CrudResult<()> {
+                #version_check
                 #(
-                    if self.#non_patch_fields != base.#non_patch_fields {
-                        return Err(::rp1::CrudError::UnchangeableField(stringify!(#non_patch_fields).to_owned()));
+                    if self.#unchangeable_fields != base.#unchangeable_fields {
+                        return Err(::rp1::CrudError::UnchangeableField(stringify!(#unchangeable_fields).to_owned()));
                     }
                 )*
 
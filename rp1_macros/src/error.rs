@@ -4,7 +4,6 @@ pub enum Error {
     SynError(syn::Error),
     Darling(darling::Error),
     MissingPrimaryKey,
-    AggregatePrimaryKeyNotSupported,
     UnnamedFieldsNotSupported,
 }
 
@@ -26,7 +25,6 @@ impl Error {
             Error::SynError(e) => e.to_compile_error(),
             Error::Darling(e) => e.write_errors(),
             Error::MissingPrimaryKey => todo!(),
-            Error::AggregatePrimaryKeyNotSupported => todo!(),
             Error::UnnamedFieldsNotSupported => todo!(),
         }
     }
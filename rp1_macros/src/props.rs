@@ -3,11 +3,12 @@ use std::convert::{TryFrom, TryInto};
 use crate::{Error, Result};
 use darling::FromMeta;
 use inflector::cases::snakecase::to_snake_case;
+use inflector::string::pluralize::to_plural;
 use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote, ToTokens, TokenStreamExt};
 use syn::{
-    token::Bracket, AttrStyle, Attribute, Field, GenericArgument, Ident, ItemStruct, Path, Token,
-    Type, Visibility,
+    token::Bracket, AttrStyle, Attribute, Field, GenericArgument, Ident, ItemStruct, Lit, LitStr,
+    Meta, MetaNameValue, NestedMeta, Path, Token, Type, Visibility,
 };
 
 /// Helper for deserializing macro props when the default is true
@@ -20,6 +21,36 @@ fn is_option_ty(ty: &Type) -> bool {
     option_ty_arg(ty).is_some()
 }
 
+/// Whether the type is a (possibly optional) string type, for which the text
+/// filter operators are available.
+fn is_text_ty(ty: &Type) -> bool {
+    match option_ty_arg(ty).unwrap_or(ty) {
+        Type::Path(ty) => matches!(ty.path.segments.last(), Some(s) if s.ident == "String"),
+        _ => false,
+    }
+}
+
+fn is_bool_ty(ty: &Type) -> bool {
+    match ty {
+        Type::Path(ty) => ty.path.is_ident("bool"),
+        _ => false,
+    }
+}
+
+/// The name of the integer type of a field, used as the suffix of integer
+/// literals of the same type.
+pub(crate) fn integer_ty_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(ty) => ty
+            .path
+            .segments
+            .last()
+            .map(|s| s.ident.to_string())
+            .filter(|name| matches!(name.as_str(), "i16" | "i32" | "i64")),
+        _ => None,
+    }
+}
+
 fn option_ty_arg(ty: &Type) -> Option<&Type> {
     use syn::PathArguments::AngleBracketed;
 
@@ -51,6 +82,11 @@ pub struct CrudField {
     pub is_sortable: bool,
     pub is_filterable: bool,
     pub is_option: bool,
+    pub is_text: bool,
+    pub is_searchable: bool,
+    pub is_version: bool,
+    pub is_last_modified: bool,
+    pub is_soft_delete: bool,
 }
 
 impl CrudField {
@@ -83,6 +119,11 @@ impl CrudField {
         cloned
     }
 
+    /// The type of the field, without the `Option` for nullable fields.
+    pub fn inner_ty(&self) -> &Type {
+        option_ty_arg(&self.ty).unwrap_or(&self.ty)
+    }
+
     pub fn ensure_option(&self) -> CrudField {
         if self.is_option {
             self.clone()
@@ -123,6 +164,10 @@ impl TryFrom<&Field> for CrudField {
         let mut is_primary_key = false;
         let mut is_sortable = true;
         let mut is_filterable = true;
+        let mut is_searchable = false;
+        let mut is_version = false;
+        let mut is_last_modified = false;
+        let mut is_soft_delete = false;
         for attr in value.attrs.iter() {
             if attr.path.is_ident("generated") {
                 is_generated = true;
@@ -139,6 +184,46 @@ impl TryFrom<&Field> for CrudField {
             if attr.path.is_ident("not_filterable") {
                 is_filterable = false;
             }
+
+            if attr.path.is_ident("searchable") {
+                if !is_text_ty(&value.ty) {
+                    return Err(syn::Error::new_spanned(
+                        attr,
+                        "Only text fields can be searchable",
+                    )
+                    .into());
+                }
+                is_searchable = true;
+            }
+
+            // the version is changed with every update, never by the user
+            if attr.path.is_ident("version") {
+                if is_option_ty(&value.ty) {
+                    return Err(
+                        syn::Error::new_spanned(attr, "The version cannot be optional").into(),
+                    );
+                }
+                is_version = true;
+                is_generated = true;
+            }
+
+            if attr.path.is_ident("last_modified") {
+                is_last_modified = true;
+            }
+
+            // the marker of a deleted item is only changed by the delete and
+            // restore routes
+            if attr.path.is_ident("soft_delete") {
+                if !is_option_ty(&value.ty) && !is_bool_ty(&value.ty) {
+                    return Err(syn::Error::new_spanned(
+                        attr,
+                        "The `soft_delete` field must be a `bool` or an `Option`",
+                    )
+                    .into());
+                }
+                is_soft_delete = true;
+                is_generated = true;
+            }
         }
 
         let attrs = value
@@ -149,11 +234,16 @@ impl TryFrom<&Field> for CrudField {
                     && !a.path.is_ident("primary_key")
                     && !a.path.is_ident("not_sortable")
                     && !a.path.is_ident("not_filterable")
+                    && !a.path.is_ident("searchable")
+                    && !a.path.is_ident("version")
+                    && !a.path.is_ident("last_modified")
+                    && !a.path.is_ident("soft_delete")
             })
             .cloned()
             .collect();
 
         let is_option = is_option_ty(&value.ty);
+        let is_text = is_text_ty(&value.ty);
 
         Ok(CrudField {
             ident,
@@ -165,10 +255,66 @@ impl TryFrom<&Field> for CrudField {
             is_sortable,
             is_filterable,
             is_option,
+            is_text,
+            is_searchable,
+            is_version,
+            is_last_modified,
+            is_soft_delete,
         })
     }
 }
 
+/// Database backends for which handlers can be generated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromMeta)]
+pub enum Backend {
+    #[default]
+    #[darling(rename = "postgres")]
+    Postgres,
+    #[darling(rename = "sqlite")]
+    Sqlite,
+    #[darling(rename = "mysql")]
+    Mysql,
+}
+
+impl Backend {
+    /// Whether the backend supports a `RETURNING` clause for inserts and
+    /// updates.
+    pub fn supports_returning(self) -> bool {
+        self == Backend::Postgres
+    }
+
+    /// Whether the backend supports locking the rows that are read in a
+    /// transaction using `SELECT ... FOR UPDATE`.
+    pub fn supports_row_locking(self) -> bool {
+        self != Backend::Sqlite
+    }
+
+    /// The diesel backend type.
+    pub fn diesel_type(self) -> TokenStream {
+        match self {
+            Backend::Postgres => quote!(::diesel::pg::Pg),
+            Backend::Sqlite => quote!(::diesel::sqlite::Sqlite),
+            Backend::Mysql => quote!(::diesel::mysql::Mysql),
+        }
+    }
+
+    /// Whether `NULL` values are sorted after all other values when sorting in
+    /// ascending order.
+    pub fn nulls_sort_high(self) -> bool {
+        self == Backend::Postgres
+    }
+
+    /// SQL function returning the id of the last inserted row on backends
+    /// that do not support `RETURNING`.
+    pub fn last_insert_id(self) -> &'static str {
+        match self {
+            Backend::Postgres => "lastval()",
+            Backend::Sqlite => "last_insert_rowid()",
+            Backend::Mysql => "LAST_INSERT_ID()",
+        }
+    }
+}
+
 /// This struct is a deserialization of all properties that the macro accepts.
 ///
 /// This struct should immediately be converted to [CrudProps].
@@ -178,6 +324,8 @@ pub struct CrudPropsBuilder {
     database_struct: Option<Path>,
     #[darling(default, rename = "schema")]
     schema_path: Option<Path>,
+    #[darling(default)]
+    backend: Backend,
     #[darling(default = "enabled")]
     create: bool,
     #[darling(default = "enabled")]
@@ -190,16 +338,335 @@ pub struct CrudPropsBuilder {
     list: bool,
     #[darling(default = "enabled")]
     partials: bool,
+    #[darling(default)]
+    pagination: bool,
+    #[darling(default)]
+    search: bool,
+    #[darling(default)]
+    bulk: bool,
+    #[darling(default)]
+    bulk_limit: Option<i64>,
+    #[darling(default)]
+    cache_control: Option<String>,
+    #[darling(default)]
+    hooks: bool,
+    #[darling(default)]
+    audit: Option<Ident>,
+    #[darling(default)]
+    events: bool,
     #[darling(default, rename = "module")]
     module_name: Option<Ident>,
     #[darling(default, rename = "table")]
     table_name: Option<Ident>,
     #[darling(default)]
     max_limit: Option<i64>,
+    #[darling(default)]
+    default_sort: Option<LitStr>,
+    #[darling(default)]
+    parent: Option<Path>,
     #[darling(default = "enabled")]
     auth: bool,
 }
 
+/// A sort key of the `default_sort` property, using the same syntax as the
+/// `sort` query parameter, e.g. `-created_at:nullslast`.
+#[derive(Debug, Clone)]
+pub struct DefaultSortKey {
+    pub field: Ident,
+    pub descending: bool,
+    /// Whether null values come first (`Some(true)`) or last (`Some(false)`).
+    pub nulls_first: Option<bool>,
+    pub case_insensitive: bool,
+}
+
+/// A relation to another crud struct, declared using a `belongs_to`
+/// attribute on the struct, e.g. `#[belongs_to(Post, foreign_key = "post_id")]`.
+#[derive(Debug, Clone)]
+pub struct Relation {
+    /// Name of the relation in filter and sort parameters, the snake case
+    /// variant of the name of the related struct.
+    pub name: Ident,
+    /// The related struct.
+    pub path: Path,
+    /// The field referring to the primary key of the related struct.
+    pub foreign_key: Ident,
+}
+
+/// Parse the `belongs_to` attributes of the struct, these are removed from
+/// the struct afterwards.
+fn parse_relations(item: &mut ItemStruct, fields: &[CrudField]) -> Result<Vec<Relation>> {
+    let mut relations = vec![];
+    for attr in item.attrs.iter().filter(|a| a.path.is_ident("belongs_to")) {
+        let error = |message: &str| Error::from(syn::Error::new_spanned(attr, message));
+        let nested = match attr.parse_meta()? {
+            Meta::List(list) => list.nested,
+            _ => {
+                return Err(error(
+                    "Expected `belongs_to(Struct, foreign_key = \"field\")`",
+                ))
+            }
+        };
+        let mut nested = nested.into_iter();
+        let path = match nested.next() {
+            Some(NestedMeta::Meta(Meta::Path(path))) => path,
+            _ => return Err(error("Expected the related struct as the first argument")),
+        };
+        let related = path
+            .segments
+            .last()
+            .map(|s| s.ident.to_string())
+            .unwrap_or_default();
+        let mut foreign_key = format_ident!("{}_id", to_snake_case(&related));
+        for meta in nested {
+            match meta {
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("foreign_key") => {
+                    foreign_key = lit.parse()?;
+                }
+                _ => {
+                    return Err(error(
+                        "Unknown argument, expected `foreign_key = \"field\"`",
+                    ))
+                }
+            }
+        }
+        let name = format_ident!("{}", to_snake_case(&related));
+        if !fields.iter().any(|f| f.ident == foreign_key) {
+            return Err(error(&format!(
+                "Unknown foreign key field '{}'",
+                foreign_key
+            )));
+        }
+        if fields.iter().any(|f| f.ident == name) {
+            return Err(error(&format!(
+                "Relation '{}' has the same name as a field",
+                name
+            )));
+        }
+        relations.push(Relation {
+            name,
+            path,
+            foreign_key,
+        });
+    }
+    item.attrs.retain(|a| !a.path.is_ident("belongs_to"));
+
+    Ok(relations)
+}
+
+/// A relation to the items of another crud struct that refer to this struct,
+/// declared using a `has_many` attribute on the struct, e.g.
+/// `#[has_many(Comment)]`. The other struct should declare a `belongs_to`
+/// relation to this struct.
+#[derive(Debug, Clone)]
+pub struct HasMany {
+    /// Name of the relation in the `embed` parameter, by default the plural
+    /// snake case variant of the name of the related struct.
+    pub name: Ident,
+    /// The related struct.
+    pub path: Path,
+}
+
+/// Parse the `has_many` attributes of the struct, these are removed from the
+/// struct afterwards.
+fn parse_has_many(
+    item: &mut ItemStruct,
+    fields: &[CrudField],
+    relations: &[Relation],
+) -> Result<Vec<HasMany>> {
+    let mut has_many: Vec<HasMany> = vec![];
+    for attr in item.attrs.iter().filter(|a| a.path.is_ident("has_many")) {
+        let error = |message: &str| Error::from(syn::Error::new_spanned(attr, message));
+        let nested = match attr.parse_meta()? {
+            Meta::List(list) => list.nested,
+            _ => return Err(error("Expected `has_many(Struct, name = \"relation\")`")),
+        };
+        let mut nested = nested.into_iter();
+        let path = match nested.next() {
+            Some(NestedMeta::Meta(Meta::Path(path))) => path,
+            _ => return Err(error("Expected the related struct as the first argument")),
+        };
+        let related = path
+            .segments
+            .last()
+            .map(|s| s.ident.to_string())
+            .unwrap_or_default();
+        let mut name = format_ident!("{}", to_snake_case(&to_plural(&related)));
+        for meta in nested {
+            match meta {
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) if path.is_ident("name") => {
+                    name = lit.parse()?;
+                }
+                _ => return Err(error("Unknown argument, expected `name = \"relation\"`")),
+            }
+        }
+        if fields.iter().any(|f| f.ident == name) {
+            return Err(error(&format!(
+                "Relation '{}' has the same name as a field",
+                name
+            )));
+        }
+        if relations.iter().any(|r| r.name == name) || has_many.iter().any(|r| r.name == name) {
+            return Err(error(&format!("Duplicate relation '{}'", name)));
+        }
+        has_many.push(HasMany { name, path });
+    }
+    item.attrs.retain(|a| !a.path.is_ident("has_many"));
+
+    Ok(has_many)
+}
+
+/// A many-to-many relation to another crud struct through a join table,
+/// declared using a `many_to_many` attribute on the struct, e.g.
+/// `#[many_to_many(Tag, through = "post_tags")]`.
+#[derive(Debug, Clone)]
+pub struct ManyToMany {
+    /// Name of the relation in the path of the generated routes, by default
+    /// the plural snake case variant of the name of the related struct.
+    pub name: Ident,
+    /// The related struct.
+    pub path: Path,
+    /// The join table in the schema.
+    pub through: Ident,
+    /// The column of the join table referring to this struct.
+    pub foreign_key: Ident,
+    /// The column of the join table referring to the related struct.
+    pub related_key: Ident,
+}
+
+/// Parse the `many_to_many` attributes of the struct, these are removed from
+/// the struct afterwards.
+fn parse_many_to_many(
+    item: &mut ItemStruct,
+    fields: &[CrudField],
+    relations: &[Relation],
+    has_many: &[HasMany],
+) -> Result<Vec<ManyToMany>> {
+    let mut many_to_many: Vec<ManyToMany> = vec![];
+    for attr in item
+        .attrs
+        .iter()
+        .filter(|a| a.path.is_ident("many_to_many"))
+    {
+        let error = |message: &str| Error::from(syn::Error::new_spanned(attr, message));
+        let nested = match attr.parse_meta()? {
+            Meta::List(list) => list.nested,
+            _ => {
+                return Err(error(
+                    "Expected `many_to_many(Struct, through = \"join_table\")`",
+                ))
+            }
+        };
+        let mut nested = nested.into_iter();
+        let path = match nested.next() {
+            Some(NestedMeta::Meta(Meta::Path(path))) => path,
+            _ => return Err(error("Expected the related struct as the first argument")),
+        };
+        let related = path
+            .segments
+            .last()
+            .map(|s| s.ident.to_string())
+            .unwrap_or_default();
+        let mut name = format_ident!("{}", to_snake_case(&to_plural(&related)));
+        let mut through = None;
+        let mut foreign_key = format_ident!("{}_id", to_snake_case(&item.ident.to_string()));
+        let mut related_key = format_ident!("{}_id", to_snake_case(&related));
+        for meta in nested {
+            match meta {
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                    path,
+                    lit: Lit::Str(lit),
+                    ..
+                })) => {
+                    if path.is_ident("name") {
+                        name = lit.parse()?;
+                    } else if path.is_ident("through") {
+                        through = Some(lit.parse()?);
+                    } else if path.is_ident("foreign_key") {
+                        foreign_key = lit.parse()?;
+                    } else if path.is_ident("related_key") {
+                        related_key = lit.parse()?;
+                    } else {
+                        return Err(error("Unknown argument, expected `name`, `through`, `foreign_key` or `related_key`"));
+                    }
+                }
+                _ => return Err(error("Expected arguments like `through = \"join_table\"`")),
+            }
+        }
+        let through = through
+            .ok_or_else(|| error("Expected the join table as `through = \"join_table\"`"))?;
+        if fields.iter().any(|f| f.ident == name) {
+            return Err(error(&format!(
+                "Relation '{}' has the same name as a field",
+                name
+            )));
+        }
+        if relations.iter().any(|r| r.name == name)
+            || has_many.iter().any(|r| r.name == name)
+            || many_to_many.iter().any(|r| r.name == name)
+        {
+            return Err(error(&format!("Duplicate relation '{}'", name)));
+        }
+        many_to_many.push(ManyToMany {
+            name,
+            path,
+            through,
+            foreign_key,
+            related_key,
+        });
+    }
+    item.attrs.retain(|a| !a.path.is_ident("many_to_many"));
+
+    Ok(many_to_many)
+}
+
+/// Parse the comma separated sort keys of the `default_sort` property, every
+/// key must refer to a sortable field of the struct.
+fn parse_default_sort(lit: &LitStr, fields: &[CrudField]) -> Result<Vec<DefaultSortKey>> {
+    let error = |message: String| Error::from(syn::Error::new_spanned(lit, message));
+    let value = lit.value();
+    let mut keys = vec![];
+    for key in value.split(',').map(str::trim) {
+        let mut parts = key.split(':');
+        let field = parts.next().unwrap_or_default();
+        let (descending, field) = match field.strip_prefix('-') {
+            Some(field) => (true, field),
+            None => (false, field.strip_prefix('+').unwrap_or(field)),
+        };
+        let field = fields
+            .iter()
+            .find(|f| f.is_sortable && f.ident == field)
+            .ok_or_else(|| error(format!("Unknown sortable field '{}'", field)))?;
+        let mut sort_key = DefaultSortKey {
+            field: field.ident.clone(),
+            descending,
+            nulls_first: None,
+            case_insensitive: false,
+        };
+        for modifier in parts {
+            match modifier {
+                "nullsfirst" | "nullslast" if sort_key.nulls_first.is_none() => {
+                    sort_key.nulls_first = Some(modifier == "nullsfirst");
+                }
+                "ci" if field.is_text && !sort_key.case_insensitive => {
+                    sort_key.case_insensitive = true;
+                }
+                _ => return Err(error(format!("Invalid sort modifier '{}'", modifier))),
+            }
+        }
+        keys.push(sort_key);
+    }
+
+    Ok(keys)
+}
+
 impl CrudPropsBuilder {
     pub fn build(self, mut item: ItemStruct) -> Result<CrudProps> {
         let fields = item
@@ -207,18 +674,115 @@ impl CrudPropsBuilder {
             .iter()
             .map(|f| f.try_into())
             .collect::<Result<Vec<_>>>()?;
-        let primary_type = fields
+        let primary_types = fields
             .iter()
             .filter(|f: &&CrudField| f.is_primary_key)
             .map(|f| f.ty.clone())
             .collect::<Vec<_>>();
-        if primary_type.len() == 0 {
-            return Err(Error::MissingPrimaryKey);
-        } else if primary_type.len() > 1 {
-            return Err(Error::AggregatePrimaryKeyNotSupported);
-        }
+        let primary_type = match primary_types.len() {
+            0 => return Err(Error::MissingPrimaryKey),
+            1 => primary_types[0].clone(),
+            // composite keys are represented as a tuple, which is what diesel
+            // expects when calling `find` on a table with a composite key
+            _ => syn::parse2(quote!((#(#primary_types),*))).expect("Invalid type formed"),
+        };
 
-        let primary_type = primary_type[0].clone();
+        let default_sort = match &self.default_sort {
+            Some(lit) => parse_default_sort(lit, &fields)?,
+            None => vec![],
+        };
+        let relations = parse_relations(&mut item, &fields)?;
+        let has_many = parse_has_many(&mut item, &fields, &relations)?;
+        let many_to_many = parse_many_to_many(&mut item, &fields, &relations, &has_many)?;
+        let parent = match &self.parent {
+            Some(parent) => {
+                let parent_ident = parent.segments.last().map(|s| &s.ident);
+                let relation = relations
+                    .iter()
+                    .find(|r| r.path.segments.last().map(|s| &s.ident) == parent_ident)
+                    .ok_or_else(|| {
+                        syn::Error::new_spanned(
+                            parent,
+                            "The parent must be declared using a `belongs_to` attribute",
+                        )
+                    })?;
+                Some(relation.clone())
+            }
+            None => None,
+        };
+        if primary_types.len() > 1 && !has_many.is_empty() {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "A struct with a composite primary key cannot have `has_many` relations",
+            )
+            .into());
+        }
+        if primary_types.len() > 1 && !many_to_many.is_empty() {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "A struct with a composite primary key cannot have `many_to_many` relations",
+            )
+            .into());
+        }
+        if self.bulk && !self.list {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "The `bulk` routes use the filters of the list endpoint, which must be enabled",
+            )
+            .into());
+        }
+        if self.search && !self.list {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "The `search` route is part of the list endpoint, which must be enabled",
+            )
+            .into());
+        }
+        if fields.iter().any(|f| f.is_searchable) {
+            if let Some(field) = fields.iter().find(|f| f.ident == "relevance") {
+                return Err(syn::Error::new_spanned(
+                    &field.ident,
+                    "A searchable struct cannot have a field named `relevance`, as it is used to sort search results",
+                )
+                .into());
+            }
+        }
+        let versions = fields.iter().filter(|f| f.is_version).collect::<Vec<_>>();
+        if versions.len() > 1 {
+            return Err(syn::Error::new_spanned(
+                &versions[1].ident,
+                "Only a single field can be the `version`",
+            )
+            .into());
+        }
+        if let Some(field) = fields.iter().filter(|f| f.is_last_modified).nth(1) {
+            return Err(syn::Error::new_spanned(
+                &field.ident,
+                "Only a single field can be marked as `last_modified`",
+            )
+            .into());
+        }
+        if let Some(field) = fields.iter().filter(|f| f.is_soft_delete).nth(1) {
+            return Err(syn::Error::new_spanned(
+                &field.ident,
+                "Only a single field can be marked as `soft_delete`",
+            )
+            .into());
+        }
+        if let Some(version) = versions.iter().find(|f| f.is_primary_key) {
+            return Err(syn::Error::new_spanned(
+                &version.ident,
+                "The `version` cannot be part of the primary key",
+            )
+            .into());
+        }
+        if primary_types.len() > 1 && parent.is_some() {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "A struct with a composite primary key cannot have a `parent`",
+            )
+            .into());
+        }
 
         let original_visibility = item.vis.clone();
         item.vis = syn::Visibility::Public(syn::VisPublic {
@@ -232,6 +796,7 @@ impl CrudPropsBuilder {
             schema_path: self
                 .schema_path
                 .unwrap_or_else(|| syn::parse_str("crate::schema").unwrap()),
+            backend: self.backend,
             ident: item.ident.clone(),
             new_ident: format_ident!("New{}", &item.ident),
             patch_ident: format_ident!("UpdatePatch{}", &item.ident),
@@ -248,10 +813,23 @@ impl CrudPropsBuilder {
             update: self.update,
             delete: self.delete,
             partials: self.partials,
+            pagination: self.pagination,
+            search: self.search,
+            bulk: self.bulk,
+            bulk_limit: self.bulk_limit,
+            cache_control: self.cache_control,
+            hooks: self.hooks,
+            audit: self.audit,
+            events: self.events,
             table_name: self
                 .table_name
                 .unwrap_or_else(|| format_ident!("{}", to_snake_case(&item.ident.to_string()))),
             max_limit: self.max_limit.unwrap_or(100),
+            default_sort,
+            relations,
+            has_many,
+            many_to_many,
+            parent,
             primary_type,
             original_visibility,
             fields,
@@ -267,12 +845,31 @@ pub struct CrudProps {
     pub(crate) item: ItemStruct,
     pub(crate) database_struct: Path,
     pub(crate) schema_path: Path,
+    pub(crate) backend: Backend,
     pub(crate) create: bool,
     pub(crate) read: bool,
     pub(crate) update: bool,
     pub(crate) delete: bool,
     pub(crate) list: bool,
     pub(crate) partials: bool,
+    pub(crate) pagination: bool,
+    pub(crate) search: bool,
+    /// Whether the update and delete routes of the items matching a filter
+    /// are enabled.
+    pub(crate) bulk: bool,
+    /// The maximum number of items changed by a single bulk route.
+    pub(crate) bulk_limit: Option<i64>,
+    /// The `Cache-Control` header of the read and list responses.
+    pub(crate) cache_control: Option<String>,
+    /// Whether the create, update and delete routes call the `CrudHooks` of
+    /// the struct.
+    pub(crate) hooks: bool,
+    /// The table in the schema to which the changes of the create, update and
+    /// delete routes are written.
+    pub(crate) audit: Option<Ident>,
+    /// Whether the changed items are published to the `EventChannel` of the
+    /// struct, and streamed by the events route.
+    pub(crate) events: bool,
     pub(crate) module_name: Ident,
     pub(crate) ident: Ident,
     pub(crate) new_ident: Ident,
@@ -282,8 +879,22 @@ pub struct CrudProps {
     pub(crate) partial_ident: Ident,
     pub(crate) partial_output_ident: Ident,
     pub(crate) table_name: Ident,
+    /// Type of the primary key, a tuple for composite primary keys.
     pub(crate) primary_type: Type,
     pub(crate) max_limit: i64,
+    /// Sort keys used when a list request does not specify any.
+    pub(crate) default_sort: Vec<DefaultSortKey>,
+    /// Relations to other crud structs, of which the fields can be used to
+    /// filter and sort the list endpoint.
+    pub(crate) relations: Vec<Relation>,
+    /// Relations to the items of other crud structs referring to this
+    /// struct, which can only be embedded.
+    pub(crate) has_many: Vec<HasMany>,
+    /// Relations to the items of other crud structs through a join table,
+    /// which can be linked and unlinked by the nested routes.
+    pub(crate) many_to_many: Vec<ManyToMany>,
+    /// The relation to the parent of the nested routes, if any.
+    pub(crate) parent: Option<Relation>,
     pub(crate) original_visibility: Visibility,
     pub(crate) fields: Vec<CrudField>,
     pub(crate) auth: bool,
@@ -298,6 +909,72 @@ impl CrudProps {
         self.fields.iter().filter(|f| f.is_filterable)
     }
 
+    pub(crate) fn primary_key_fields(&self) -> impl Iterator<Item = &CrudField> {
+        self.fields.iter().filter(|f| f.is_primary_key)
+    }
+
+    /// Text fields matched against the `q` parameter of a full-text search.
+    pub(crate) fn searchable_fields(&self) -> impl Iterator<Item = &CrudField> {
+        self.fields.iter().filter(|f| f.is_searchable)
+    }
+
+    pub(crate) fn has_search(&self) -> bool {
+        self.searchable_fields().next().is_some()
+    }
+
+    /// Fields that can be part of a keyset cursor, i.e. the sortable fields
+    /// and the primary key which is always used as the final tiebreaker.
+    pub(crate) fn keyset_fields(&self) -> impl Iterator<Item = &CrudField> {
+        self.fields
+            .iter()
+            .filter(|f| f.is_sortable || f.is_primary_key)
+    }
+
+    /// Whether the read and list endpoints accept an `embed` parameter.
+    pub(crate) fn has_embeds(&self) -> bool {
+        !self.relations.is_empty() || !self.has_many.is_empty()
+    }
+
+    /// Names of all relations that can be embedded.
+    pub(crate) fn embed_names(&self) -> impl Iterator<Item = &Ident> {
+        self.relations
+            .iter()
+            .map(|r| &r.name)
+            .chain(self.has_many.iter().map(|r| &r.name))
+    }
+
+    /// The field marked with `#[version]`, which is exposed as the `ETag` of
+    /// an item.
+    pub(crate) fn version_field(&self) -> Option<&CrudField> {
+        self.fields.iter().find(|f| f.is_version)
+    }
+
+    /// The field marked with `#[last_modified]`, which is used for the
+    /// `Last-Modified` header of the read and list routes.
+    pub(crate) fn last_modified_field(&self) -> Option<&CrudField> {
+        self.fields.iter().find(|f| f.is_last_modified)
+    }
+
+    /// The field marked with `#[soft_delete]`, which marks deleted items
+    /// instead of removing their rows.
+    pub(crate) fn soft_delete_field(&self) -> Option<&CrudField> {
+        self.fields.iter().find(|f| f.is_soft_delete)
+    }
+
+    pub(crate) fn has_composite_key(&self) -> bool {
+        self.primary_key_fields().count() > 1
+    }
+
+    /// Fields that can be given when creating a new item. Parts of a composite
+    /// primary key usually reference other tables, so these must be given by
+    /// the user unless they are explicitly marked as generated.
+    pub(crate) fn insertable_fields(&self) -> impl Iterator<Item = &CrudField> {
+        let composite = self.has_composite_key();
+        self.fields
+            .iter()
+            .filter(move |f| !f.is_generated && (!f.is_primary_key || composite))
+    }
+
     pub(crate) fn patch_fields(&self) -> Vec<CrudField> {
         self.user_supplied_fields()
             .map(|f| f.with_wrapped_option())
@@ -0,0 +1,58 @@
+-- Create users table
+CREATE TABLE users (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  username VARCHAR NOT NULL,
+  role VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Create update trigger for users table
+CREATE TRIGGER update_users_updated_at
+AFTER UPDATE ON users
+FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE users SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+-- Create posts table
+CREATE TABLE posts (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  title VARCHAR NOT NULL,
+  subtitle VARCHAR,
+  content TEXT NOT NULL,
+  user_id INTEGER NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT posts_fk_user FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+-- Create update trigger for posts table
+CREATE TRIGGER update_posts_updated_at
+AFTER UPDATE ON posts
+FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE posts SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
+
+-- Create comments table
+CREATE TABLE comments (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  content TEXT NOT NULL,
+  approved BOOLEAN NOT NULL DEFAULT FALSE,
+  post_id INTEGER NOT NULL,
+  user_id INTEGER,
+  anonymous_user VARCHAR,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT comments_fk_post FOREIGN KEY(post_id) REFERENCES posts(id),
+  CONSTRAINT comments_fk_user FOREIGN KEY(user_id) REFERENCES users(id)
+);
+
+-- Create update trigger for comments table
+CREATE TRIGGER update_comments_updated_at
+AFTER UPDATE ON comments
+FOR EACH ROW WHEN NEW.updated_at = OLD.updated_at
+BEGIN
+  UPDATE comments SET updated_at = CURRENT_TIMESTAMP WHERE id = NEW.id;
+END;
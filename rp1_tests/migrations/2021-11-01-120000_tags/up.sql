@@ -0,0 +1,14 @@
+-- Create tags table
+CREATE TABLE tags (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  name VARCHAR NOT NULL
+);
+
+-- Create join table linking posts and tags
+CREATE TABLE post_tags (
+  post_id INTEGER NOT NULL,
+  tag_id INTEGER NOT NULL,
+  PRIMARY KEY (post_id, tag_id),
+  CONSTRAINT post_tags_fk_post FOREIGN KEY(post_id) REFERENCES posts(id),
+  CONSTRAINT post_tags_fk_tag FOREIGN KEY(tag_id) REFERENCES tags(id)
+);
@@ -0,0 +1,7 @@
+-- Create documents table, with a version that is changed on every update
+CREATE TABLE documents (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  title VARCHAR NOT NULL,
+  content TEXT NOT NULL,
+  version INTEGER NOT NULL DEFAULT 1
+);
@@ -0,0 +1,7 @@
+-- Create notes table, of which deleted rows are marked instead of removed
+CREATE TABLE notes (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  title VARCHAR NOT NULL,
+  archived BOOLEAN NOT NULL DEFAULT 0,
+  deleted_at TIMESTAMP
+);
@@ -0,0 +1,11 @@
+-- Create audit_log table, recording the changes made by the crud routes
+CREATE TABLE audit_log (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  resource VARCHAR NOT NULL,
+  record_id VARCHAR NOT NULL,
+  action VARCHAR NOT NULL,
+  actor VARCHAR,
+  created_at TIMESTAMP NOT NULL,
+  before TEXT,
+  after TEXT
+);
@@ -0,0 +1,2 @@
+-- Add an order to the tags of a post
+ALTER TABLE post_tags ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,8 @@
+-- Create join table linking notes and tags
+CREATE TABLE note_tags (
+  note_id INTEGER NOT NULL,
+  tag_id INTEGER NOT NULL,
+  PRIMARY KEY (note_id, tag_id),
+  CONSTRAINT note_tags_fk_note FOREIGN KEY(note_id) REFERENCES notes(id),
+  CONSTRAINT note_tags_fk_tag FOREIGN KEY(tag_id) REFERENCES tags(id)
+);
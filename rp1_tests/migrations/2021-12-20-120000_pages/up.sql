@@ -0,0 +1,8 @@
+-- Create pages table, of which the update time is the version. The default
+-- uses the format in which rp1 stores timestamps, so the version can be
+-- compared in the conditional update
+CREATE TABLE pages (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  title VARCHAR NOT NULL,
+  updated_at TIMESTAMP NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f000', 'now'))
+);
@@ -6,31 +6,25 @@ use rocket::request::Request;
 use rocket::Build;
 use rocket::Rocket;
 
-use rocket_sync_db_pools::database;
 use rp1::CheckPermissions;
 
-#[database("diesel")]
-struct Db(diesel::PgConnection);
+use crate::Db;
 
-pub enum AUser {
-    LoggedIn(User),
-    Anonymous,
-}
-
-#[rp1::crud(database = "Db", table = "users", auth = false)]
+#[rp1::crud(database = "Db", table = "users", backend = "sqlite")]
 struct User {
     #[primary_key]
     id: i32,
     #[validate(email)]
     username: String,
+    #[serde(default)]
     role: String,
     #[generated]
-    created_at: chrono::NaiveDateTime,
+    created_at: rp1::datetime::OffsetDateTime,
     #[generated]
-    updated_at: chrono::NaiveDateTime,
+    updated_at: rp1::datetime::OffsetDateTime,
 }
 
-#[rp1::crud(database = "Db", table = "posts", auth = false)]
+#[rp1::crud(database = "Db", table = "posts", backend = "sqlite")]
 struct Post {
     #[primary_key]
     id: i32,
@@ -39,12 +33,12 @@ struct Post {
     content: String,
     user_id: i32,
     #[generated]
-    created_at: chrono::NaiveDateTime,
+    created_at: rp1::datetime::OffsetDateTime,
     #[generated]
-    updated_at: chrono::NaiveDateTime,
+    updated_at: rp1::datetime::OffsetDateTime,
 }
 
-#[rp1::crud(database = "Db", table = "comments", auth = false)]
+#[rp1::crud(database = "Db", table = "comments", backend = "sqlite", auth = false)]
 struct Comment {
     #[primary_key]
     id: i32,
@@ -62,19 +56,27 @@ struct Comment {
 }
 
 impl CheckPermissions for User {
-    type AuthUser = AUser;
+    type AuthUser = AuthUser;
+
+    fn allow_create(_new: &user::NewUser, user: &AuthUser) -> bool {
+        user.role == "admin"
+    }
 }
 
 impl CheckPermissions for Post {
-    type AuthUser = AUser;
+    type AuthUser = AuthUser;
+
+    fn allow_create(_new: &post::NewPost, user: &AuthUser) -> bool {
+        user.role == "poster"
+    }
 }
 
 impl CheckPermissions for Comment {
-    type AuthUser = AUser;
+    type AuthUser = AuthUser;
 }
 
 #[derive(std::hash::Hash, serde::Serialize, Debug)]
-struct AuthUser {
+pub struct AuthUser {
     id: i32,
     role: String,
 }
@@ -85,6 +87,7 @@ async fn init_rocket() -> Rocket<Build> {
         .mount("/posts", Post::get_routes())
         .mount("/comments", Comment::get_routes())
         .attach(Db::fairing())
+        .attach(crate::run_migrations())
 }
 
 use rocket::http::Status;
@@ -155,6 +158,7 @@ async fn create_post_pass() {
     let client = Client::tracked(init_rocket().await)
         .await
         .expect("valid rocket instance");
+    create_user(&client, 81, "poster").await;
 
     let id = Header::new("X-Auth-Id", "81");
     let role = Header::new("X-Auth-Role", "poster");
@@ -170,31 +174,44 @@ async fn create_post_pass() {
     assert_eq!(response.status(), Status::Ok);
 }
 
+/// Inserts the user with the given id directly, as the id of a created user
+/// cannot be chosen.
+async fn create_user(client: &Client, id: i32, role: &'static str) {
+    use crate::schema::users;
+    use diesel::prelude::*;
+
+    Db::get_one(client.rocket())
+        .await
+        .expect("database connection")
+        .run(move |conn| {
+            diesel::insert_into(users::table)
+                .values((
+                    users::id.eq(id),
+                    users::username.eq(format!("{}@example.com", role)),
+                    users::role.eq(role),
+                ))
+                .execute(conn)
+        })
+        .await
+        .expect("created user");
+}
+
 #[rocket::async_trait]
-impl<'r> FromRequest<'r> for AUser {
+impl<'r> FromRequest<'r> for AuthUser {
     type Error = std::convert::Infallible;
 
     async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
-        use diesel::prelude::*;
-
-        match req.headers().get_one("X-UNSAFE-USER-ID") {
-            Some(user_id_str) => {
-                let db = <Db as FromRequest>::from_request(req).await.unwrap();
-
-                let user_id: i32 = user_id_str.parse().unwrap();
-
-                let user: User = db
-                    .run(move |conn| {
-                        crate::schema::users::table
-                            .find(user_id)
-                            .first::<User>(conn)
-                            .unwrap()
-                    })
-                    .await;
-
-                Outcome::Success(AUser::LoggedIn(user))
-            }
-            None => Outcome::Success(AUser::Anonymous),
-        }
+        let id = req
+            .headers()
+            .get_one("X-Auth-Id")
+            .and_then(|id| id.parse().ok())
+            .unwrap_or(0);
+        let role = req
+            .headers()
+            .get_one("X-Auth-Role")
+            .unwrap_or("")
+            .to_owned();
+
+        Outcome::Success(AuthUser { id, role })
     }
 }
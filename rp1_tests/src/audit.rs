@@ -0,0 +1,241 @@
+use rocket::http::{Method, Status};
+use rocket::local::blocking::Client;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Build;
+use rocket::Rocket;
+use serde_json::{json, Value};
+
+use rp1::CheckPermissions;
+
+use crate::{request, request_with_headers, Db};
+
+#[rp1::crud(
+    database = "Db",
+    table = "documents",
+    backend = "sqlite",
+    audit = "audit_log"
+)]
+#[derive(Debug)]
+struct Document {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    pub content: String,
+    #[version]
+    pub version: i32,
+}
+
+impl CheckPermissions for Document {
+    type AuthUser = AuthUser;
+
+    fn allow_read_history(&self, user: &AuthUser) -> bool {
+        user.name.as_deref() == Some("admin")
+    }
+
+    fn allow_read_deleted(user: &AuthUser) -> bool {
+        user.name.as_deref() == Some("admin")
+    }
+
+    fn actor_id(user: &AuthUser) -> Option<String> {
+        user.name.clone()
+    }
+}
+
+/// The same documents without authorization, which are recorded as a
+/// different resource.
+#[rp1::crud(
+    database = "Db",
+    table = "documents",
+    backend = "sqlite",
+    auth = false,
+    bulk = true,
+    audit = "audit_log"
+)]
+#[derive(Debug)]
+struct Draft {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    pub content: String,
+    #[version]
+    pub version: i32,
+}
+
+pub struct AuthUser {
+    name: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let name = req.headers().get_one("X-User").map(|n| n.to_owned());
+        Outcome::Success(AuthUser { name })
+    }
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/documents", Document::get_routes())
+        .mount("/drafts", Draft::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+#[test]
+fn audit_changes() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let body = json!({ "title": "Notes", "content": "Bla" });
+    let (_, document) = request_with_headers(
+        &client,
+        Method::Post,
+        "/documents",
+        Some(body),
+        &[("X-User", "alice")],
+    );
+    let url = format!("/documents/{}", document["id"]);
+    let history_url = format!("{}/history", url);
+
+    let body = json!({ "title": "Changed" });
+    let (status, _) = request_with_headers(
+        &client,
+        Method::Patch,
+        &url,
+        Some(body),
+        &[("X-User", "bob")],
+    );
+    assert_eq!(status, Status::Ok);
+    // a failed change is not recorded
+    let body = json!({ "id": document["id"], "title": "Stale", "content": "Bla", "version": 1 });
+    let (status, _) =
+        request_with_headers(&client, Method::Put, &url, Some(body), &[("X-User", "bob")]);
+    assert_eq!(status, Status::PreconditionFailed);
+
+    let (status, history) = request_with_headers(
+        &client,
+        Method::Get,
+        &history_url,
+        None,
+        &[("X-User", "admin")],
+    );
+    assert_eq!(status, Status::Ok);
+    let history = history.as_array().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0]["resource"], "document");
+    assert_eq!(history[0]["record_id"], document["id"].to_string());
+    assert_eq!(history[0]["action"], "create");
+    assert_eq!(history[0]["actor"], "alice");
+    assert_eq!(history[0]["before"], Value::Null);
+    assert_eq!(history[0]["after"], document);
+    assert!(history[0]["created_at"].is_string());
+    assert_eq!(history[1]["action"], "update");
+    assert_eq!(history[1]["actor"], "bob");
+    assert_eq!(
+        history[1]["before"],
+        json!({ "title": "Notes", "version": 1 })
+    );
+    assert_eq!(
+        history[1]["after"],
+        json!({ "title": "Changed", "version": 2 })
+    );
+
+    let (status, _) = request_with_headers(
+        &client,
+        Method::Get,
+        &history_url,
+        None,
+        &[("X-User", "bob")],
+    );
+    assert_eq!(status, Status::NotFound);
+    let (status, _) = request_with_headers(
+        &client,
+        Method::Get,
+        "/documents/99/history",
+        None,
+        &[("X-User", "admin")],
+    );
+    assert_eq!(status, Status::NotFound);
+
+    // the history of a deleted item needs allow_read_deleted instead
+    let (status, _) =
+        request_with_headers(&client, Method::Delete, &url, None, &[("X-User", "bob")]);
+    assert_eq!(status, Status::Ok);
+    let (status, history) = request_with_headers(
+        &client,
+        Method::Get,
+        &history_url,
+        None,
+        &[("X-User", "admin")],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(history[2]["action"], "delete");
+    let (status, _) = request_with_headers(
+        &client,
+        Method::Get,
+        &history_url,
+        None,
+        &[("X-User", "bob")],
+    );
+    assert_eq!(status, Status::NotFound);
+}
+
+#[test]
+fn audit_deletes() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let body = json!([
+        { "title": "Notes", "content": "Bla" },
+        { "title": "Notes", "content": "Bla" },
+        { "title": "Todo", "content": "Bla" },
+    ]);
+    let (_, drafts) = request(&client, Method::Post, "/drafts", Some(body));
+    let history_url = |draft: &Value| format!("/drafts/{}/history", draft["id"]);
+
+    let url = format!("/drafts/{}", drafts[2]["id"]);
+    request(&client, Method::Delete, &url, None);
+    let (status, history) = request(&client, Method::Get, &history_url(&drafts[2]), None);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(history[1]["action"], "delete");
+    assert_eq!(history[1]["actor"], Value::Null);
+    assert_eq!(history[1]["before"], drafts[2]);
+    assert_eq!(history[1]["after"], Value::Null);
+
+    // every item changed by a bulk route is recorded
+    let url = "/drafts?filter[title]=Notes";
+    request(
+        &client,
+        Method::Patch,
+        url,
+        Some(json!({ "content": "Changed" })),
+    );
+    request(&client, Method::Delete, url, None);
+    for draft in drafts.as_array().unwrap().iter().take(2) {
+        let (_, history) = request(&client, Method::Get, &history_url(draft), None);
+        let actions = history
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["action"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(actions, vec!["create", "update", "delete"]);
+        assert_eq!(
+            history[1]["after"],
+            json!({ "content": "Changed", "version": 2 })
+        );
+        assert_eq!(history[2]["before"]["content"], "Changed");
+    }
+
+    // the entries of a resource are separate from the other resources
+    let body = json!({ "title": "Notes", "content": "Bla" });
+    let (_, document) = request(&client, Method::Post, "/documents", Some(body));
+    let (_, history) = request(&client, Method::Get, &history_url(&document), None);
+    assert_eq!(history, json!([]));
+}
+
+#[test]
+fn audit_openapi() {
+    let resource = Draft::openapi();
+    let history = &resource.paths["/{id}/history"]["get"];
+    assert_eq!(history["operationId"], "draft_history");
+    assert!(resource.schemas.contains_key("AuditEntry"));
+}
@@ -0,0 +1,372 @@
+use diesel::backend::Backend;
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::blocking::Client;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Build;
+use rocket::Rocket;
+use rp1::{CheckPermissions, CrudStruct, PermissionFilter};
+use serde_json::{json, Value};
+
+use crate::Db;
+
+#[rp1::crud(database = "Db", table = "users", backend = "sqlite")]
+#[derive(Debug)]
+struct User {
+    #[primary_key]
+    pub id: i32,
+    #[validate(email)]
+    pub username: String,
+    #[serde(default)]
+    pub role: String,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(database = "Db", table = "posts", backend = "sqlite", auth = false)]
+#[derive(Debug)]
+struct Post {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub content: String,
+    pub user_id: i32,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(
+    database = "Db",
+    table = "comments",
+    backend = "sqlite",
+    bulk = true,
+    bulk_limit = 3
+)]
+#[derive(Debug)]
+struct Comment {
+    #[primary_key]
+    pub id: i32,
+    pub content: String,
+    pub approved: bool,
+    pub post_id: i32,
+    pub user_id: Option<i32>,
+    pub anonymous_user: Option<String>,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+/// Only admins can create other admins.
+impl CheckPermissions for User {
+    type AuthUser = AuthUser;
+
+    fn allow_create(new: &user::NewUser, user: &AuthUser) -> bool {
+        new.role != "admin" || user.admin
+    }
+}
+
+/// Only admins can see comments that are not approved, and locked comments can
+/// not be deleted.
+impl CheckPermissions for Comment {
+    type AuthUser = AuthUser;
+
+    fn filter_list<DB>(user: &AuthUser) -> PermissionFilter<<Self as CrudStruct>::TableType, DB>
+    where
+        DB: Backend,
+    {
+        if user.admin {
+            PermissionFilter::KeepAll
+        } else {
+            PermissionFilter::Filter(Box::new(crate::schema::comments::approved))
+        }
+    }
+
+    fn allow_delete(&self, _: &AuthUser) -> bool {
+        self.content != "locked"
+    }
+}
+
+pub struct AuthUser {
+    admin: bool,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let admin = req.headers().get_one("X-Admin") == Some("true");
+
+        Outcome::Success(AuthUser { admin })
+    }
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/users", User::get_routes())
+        .mount("/posts", Post::get_routes())
+        .mount("/comments", Comment::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+fn create(client: &Client, url: &str, body: Value) -> (Status, Value) {
+    let response = client
+        .post(url.to_owned())
+        .body(body.to_string())
+        .header(ContentType::JSON)
+        .dispatch();
+    (response.status(), response.into_json::<Value>().unwrap())
+}
+
+fn change(
+    client: &Client,
+    method: rocket::http::Method,
+    url: &str,
+    body: Value,
+    admin: bool,
+) -> (Status, Value) {
+    let response = client
+        .req(method, url.to_owned())
+        .body(body.to_string())
+        .header(ContentType::JSON)
+        .header(Header::new("X-Admin", admin.to_string()))
+        .dispatch();
+    (response.status(), response.into_json::<Value>().unwrap())
+}
+
+fn list(client: &Client, url: &str) -> Vec<Value> {
+    let response = client
+        .get(url.to_owned())
+        .header(Header::new("X-Admin", "true"))
+        .dispatch();
+    serde_json::from_value(response.into_json::<Value>().unwrap()).unwrap()
+}
+
+#[test]
+fn bulk_create() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (status, users) = create(
+        &client,
+        "/users/",
+        json!([
+            { "username": "alice@example.com" },
+            { "username": "bob@example.com", "role": "editor" },
+        ]),
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(users.as_array().unwrap().len(), 2);
+    assert_eq!(users[0]["username"], "alice@example.com");
+    assert_eq!(users[1]["role"], "editor");
+    assert_ne!(users[0]["id"], users[1]["id"]);
+    assert_eq!(
+        list(&client, "/users?sort=id"),
+        users.as_array().unwrap().clone()
+    );
+
+    // a single object still creates a single item
+    let (status, user) = create(
+        &client,
+        "/users/",
+        json!({ "username": "carol@example.com" }),
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(user["username"], "carol@example.com");
+
+    let (status, users) = create(&client, "/users/", json!([]));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(users, json!([]));
+}
+
+#[test]
+fn bulk_create_errors() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+
+    // all failing items are reported, and none of the items are created
+    let (status, error) = create(
+        &client,
+        "/users/",
+        json!([
+            { "username": "alice@example.com" },
+            { "username": "bob" },
+            { "username": "carol@example.com", "role": "admin" },
+        ]),
+    );
+    assert_eq!(status, Status::BadRequest);
+    let errors = error["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0]["index"], 1);
+    assert_eq!(errors[0]["error"], 400);
+    assert_eq!(errors[1]["index"], 2);
+    assert_eq!(errors[1]["error"], 403);
+
+    let (status, error) = create(
+        &client,
+        "/users/",
+        json!([{ "username": "alice@example.com" }, { "role": "editor" }]),
+    );
+    assert_eq!(status, Status::UnprocessableEntity);
+    assert_eq!(error["errors"][0]["index"], 1);
+
+    assert!(list(&client, "/users").is_empty());
+}
+
+#[test]
+fn bulk_create_rollback() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (_, user) = create(
+        &client,
+        "/users/",
+        json!({ "username": "alice@example.com" }),
+    );
+
+    // the second post refers to a user that does not exist
+    let post = |user_id: &Value| json!({ "title": "Tips", "subtitle": null, "content": "Bla", "user_id": user_id });
+    let (status, _) = create(
+        &client,
+        "/posts/",
+        json!([post(&user["id"]), post(&json!(1000))]),
+    );
+    assert_eq!(status, Status::InternalServerError);
+    assert!(list(&client, "/posts").is_empty());
+
+    let (status, posts) = create(
+        &client,
+        "/posts/",
+        json!([post(&user["id"]), post(&user["id"])]),
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(list(&client, "/posts"), posts.as_array().unwrap().clone());
+}
+
+#[test]
+fn bulk_create_openapi() {
+    let resource = User::openapi();
+    let create = &resource.paths["/"]["post"];
+    let schema = &create["requestBody"]["content"]["application/json"]["schema"];
+    assert_eq!(schema["oneOf"][1]["type"], "array");
+    assert_eq!(schema["oneOf"][1]["items"], schema["oneOf"][0]);
+}
+
+/// Creates a post with the comments `a`, `b` (not approved), `c` and
+/// `locked`.
+fn create_comments(client: &Client) -> Value {
+    let (_, user) = create(
+        client,
+        "/users/",
+        json!({ "username": "alice@example.com" }),
+    );
+    let (_, post) = create(
+        client,
+        "/posts/",
+        json!({ "title": "Tips", "subtitle": null, "content": "Bla", "user_id": user["id"] }),
+    );
+    let comments = ["a", "b", "c", "locked"]
+        .iter()
+        .map(|content| {
+            json!({
+                "content": content,
+                "approved": *content != "b",
+                "post_id": post["id"],
+                "user_id": null,
+                "anonymous_user": null,
+            })
+        })
+        .collect::<Vec<_>>();
+    create(client, "/comments/", json!(comments));
+
+    post
+}
+
+#[test]
+fn bulk_update() {
+    use rocket::http::Method::Patch;
+
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let post = create_comments(&client);
+
+    // comments that are not approved can not be seen by other users
+    let url = "/comments?filter[approved]=false";
+    let (status, updated) = change(&client, Patch, url, json!({ "content": "x" }), false);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(updated, json!({ "updated": 0 }));
+
+    let (status, updated) = change(&client, Patch, url, json!({ "approved": true }), true);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(updated, json!({ "updated": 1 }));
+    let url = format!(
+        "/comments?filter[post_id]={}&filter[approved]=true",
+        post["id"]
+    );
+    assert_eq!(list(&client, &url).len(), 4);
+
+    // a filter is required
+    let (status, _) = change(&client, Patch, "/comments", json!({ "content": "x" }), true);
+    assert_eq!(status, Status::BadRequest);
+    assert!(list(&client, "/comments?filter[content]=x").is_empty());
+}
+
+#[test]
+fn bulk_delete() {
+    use rocket::http::Method::Delete;
+
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let post = create_comments(&client);
+
+    let (status, deleted) = change(
+        &client,
+        Delete,
+        "/comments?filter[content]in=a,b",
+        Value::Null,
+        true,
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(deleted, json!({ "deleted": 2 }));
+
+    // none of the comments are deleted if one of them can not be deleted
+    let url = format!("/comments?filter[post_id]={}", post["id"]);
+    let (status, _) = change(&client, Delete, &url, Value::Null, true);
+    assert_eq!(status, Status::Forbidden);
+    assert_eq!(list(&client, "/comments").len(), 2);
+}
+
+#[test]
+fn bulk_limit() {
+    use rocket::http::Method::Patch;
+
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let post = create_comments(&client);
+
+    let url = format!("/comments?filter[post_id]={}", post["id"]);
+    let (status, error) = change(&client, Patch, &url, json!({ "content": "x" }), true);
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(error["message"], "More than 3 items match the filter");
+    assert!(list(&client, "/comments?filter[content]=x").is_empty());
+
+    let url = format!("{}&filter[approved]=true", url);
+    let (_, updated) = change(&client, Patch, &url, json!({ "content": "x" }), true);
+    assert_eq!(updated, json!({ "updated": 3 }));
+}
+
+#[test]
+fn bulk_openapi() {
+    let resource = Comment::openapi();
+    let update = &resource.paths["/"]["patch"];
+    assert_eq!(update["operationId"], "comment_bulk_update");
+    assert!(update["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|p| p["name"] == "filter[post_id]"));
+    assert_eq!(
+        resource.paths["/"]["delete"]["responses"]["200"]["content"]["application/json"]["schema"]
+            ["required"],
+        json!(["deleted"])
+    );
+    assert!(User::openapi().paths["/"].get("delete").is_none());
+}
@@ -0,0 +1,232 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::blocking::Client;
+use rocket::Build;
+use rocket::Rocket;
+use serde_json::{json, Value};
+
+use crate::Db;
+
+#[rp1::crud(
+    database = "Db",
+    table = "users",
+    backend = "sqlite",
+    auth = false,
+    search = true
+)]
+#[derive(Debug)]
+struct User {
+    #[primary_key]
+    pub id: i32,
+    pub username: String,
+    #[serde(default)]
+    pub role: String,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(
+    database = "Db",
+    table = "posts",
+    backend = "sqlite",
+    auth = false,
+    cache_control = "private, max-age=60"
+)]
+#[derive(Debug)]
+struct Post {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub content: String,
+    pub user_id: i32,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    #[last_modified]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/users", User::get_routes())
+        .mount("/posts", Post::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+fn create_post(client: &Client, title: &str) -> Value {
+    let response = client
+        .post("/users")
+        .header(ContentType::JSON)
+        .body(r#"{ "username": "alice" }"#)
+        .dispatch();
+    let user = response.into_json::<Value>().unwrap();
+    let body = json!({ "title": title, "subtitle": null, "content": "Bla", "user_id": user["id"] });
+    client
+        .post("/posts")
+        .header(ContentType::JSON)
+        .body(body.to_string())
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap()
+}
+
+/// Get the url with the given conditional header, returning the status and
+/// the `ETag` and `Last-Modified` headers of the response.
+fn get(client: &Client, url: &str, header: Option<(&str, &str)>) -> (Status, String, String) {
+    let mut request = client.get(url.to_owned());
+    if let Some((name, value)) = header {
+        request = request.header(Header::new(name.to_owned(), value.to_owned()));
+    }
+    let response = request.dispatch();
+    let header = |name| response.headers().get_one(name).unwrap_or("").to_owned();
+    (response.status(), header("ETag"), header("Last-Modified"))
+}
+
+#[test]
+fn read_if_none_match() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let post = create_post(&client, "Tips");
+    let url = format!("/posts/{}", post["id"]);
+
+    let response = client.get(url.clone()).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.headers().get_one("Cache-Control"),
+        Some("private, max-age=60")
+    );
+    let etag = response.headers().get_one("ETag").unwrap().to_owned();
+    assert_eq!(response.into_json::<Value>().unwrap(), post);
+
+    for if_none_match in &[
+        etag.clone(),
+        format!("W/{}", etag),
+        "\"other\", *".to_owned(),
+    ] {
+        let response = client
+            .get(url.clone())
+            .header(Header::new("If-None-Match", if_none_match.clone()))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotModified);
+        assert_eq!(response.headers().get_one("ETag"), Some(etag.as_str()));
+        assert_eq!(
+            response.headers().get_one("Cache-Control"),
+            Some("private, max-age=60")
+        );
+        assert!(response.into_string().is_none());
+    }
+
+    // the tag changes with the contents of the item
+    client
+        .patch(url.clone())
+        .header(ContentType::JSON)
+        .body(r#"{ "title": "More tips" }"#)
+        .dispatch();
+    let (status, changed, _) = get(&client, &url, Some(("If-None-Match", &etag)));
+    assert_eq!(status, Status::Ok);
+    assert_ne!(changed, etag);
+}
+
+#[test]
+fn read_if_modified_since() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let post = create_post(&client, "Tips");
+    let url = format!("/posts/{}", post["id"]);
+
+    let (_, etag, last_modified) = get(&client, &url, None);
+    assert!(last_modified.ends_with(" GMT"), "{}", last_modified);
+
+    let cases = [
+        (
+            ("If-Modified-Since", last_modified.as_str()),
+            Status::NotModified,
+        ),
+        (
+            ("If-Modified-Since", "Fri, 01 Jan 2100 00:00:00 GMT"),
+            Status::NotModified,
+        ),
+        (
+            ("If-Modified-Since", "Thu, 01 Jan 2015 00:00:00 GMT"),
+            Status::Ok,
+        ),
+        (("If-Modified-Since", "yesterday"), Status::Ok),
+    ];
+    for (header, expected) in cases.iter() {
+        let (status, _, _) = get(&client, &url, Some(*header));
+        assert_eq!(status, *expected, "{:?}", header);
+    }
+
+    // `If-None-Match` takes precedence
+    let response = client
+        .get(url.clone())
+        .header(Header::new("If-None-Match", "\"other\""))
+        .header(Header::new("If-Modified-Since", last_modified))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let (status, _, _) = get(&client, &url, Some(("If-None-Match", &etag)));
+    assert_eq!(status, Status::NotModified);
+}
+
+#[test]
+fn list_if_none_match() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    create_post(&client, "Tips");
+
+    let (status, etag, last_modified) = get(&client, "/posts", None);
+    assert_eq!(status, Status::Ok);
+    assert!(!last_modified.is_empty());
+    let (status, _, _) = get(&client, "/posts", Some(("If-None-Match", &etag)));
+    assert_eq!(status, Status::NotModified);
+
+    // every page has its own tag
+    let (_, other, _) = get(&client, "/posts?filter[title]=Other", None);
+    assert_ne!(other, etag);
+
+    create_post(&client, "More tips");
+    let (status, changed, _) = get(&client, "/posts", Some(("If-None-Match", &etag)));
+    assert_eq!(status, Status::Ok);
+    assert_ne!(changed, etag);
+
+    // the search route cannot be conditional
+    let response = client
+        .post("/users/search")
+        .header(ContentType::JSON)
+        .header(Header::new("If-None-Match", "*"))
+        .body("{}")
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn stable_etag() {
+    // the tag of a body is the same for every build and instance
+    assert_eq!(rp1::content_etag(&json!([])), "\"09612b07b5ecb5a5\"");
+}
+
+#[test]
+fn cache_openapi() {
+    let resource = Post::openapi();
+    let read = &resource.paths["/{id}"]["get"]["responses"];
+    assert!(read.get("304").is_some());
+    let headers = read["200"]["headers"].as_object().unwrap();
+    assert!(headers.contains_key("ETag"));
+    assert!(headers.contains_key("Last-Modified"));
+    assert!(headers.contains_key("Cache-Control"));
+
+    let list = &resource.paths["/"]["get"]["responses"];
+    assert!(list["200"]["headers"]
+        .as_object()
+        .unwrap()
+        .contains_key("X-Next-Cursor"));
+    assert!(list["200"]["headers"]
+        .as_object()
+        .unwrap()
+        .contains_key("ETag"));
+    assert!(
+        User::openapi().paths["/"]["get"]["responses"]["200"]["headers"]
+            .get("Last-Modified")
+            .is_none()
+    );
+}
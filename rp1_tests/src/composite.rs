@@ -0,0 +1,87 @@
+use diesel::connection::SimpleConnection;
+use rocket::fairing::AdHoc;
+use rocket::http::{Method, Status};
+use rocket::local::blocking::Client;
+use rocket::Build;
+use rocket::Rocket;
+use serde_json::json;
+
+use crate::{request, Db};
+
+#[rp1::crud(database = "Db", table = "post_tags", backend = "sqlite", auth = false)]
+#[derive(Debug)]
+struct PostTag {
+    #[primary_key]
+    pub post_id: i32,
+    #[primary_key]
+    pub tag_id: i32,
+    pub position: i32,
+}
+
+/// Creates the posts and tags referenced by the join table.
+fn create_posts_and_tags() -> AdHoc {
+    AdHoc::on_ignite("Create posts and tags", |rocket| async {
+        let db = Db::get_one(&rocket).await.expect("database connection");
+        db.run(|conn| {
+            conn.batch_execute(
+                "INSERT INTO users (username, role) VALUES ('alice', 'user');
+                INSERT INTO posts (title, content, user_id) VALUES ('a', 'a', 1), ('b', 'b', 1);
+                INSERT INTO tags (name) VALUES ('a'), ('b');",
+            )
+        })
+        .await
+        .expect("posts and tags");
+        rocket
+    })
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/post_tags", PostTag::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+        .attach(create_posts_and_tags())
+}
+
+#[test]
+fn composite_key_routes() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    for (post_id, tag_id) in [(1, 2), (2, 1)] {
+        let body = json!({ "post_id": post_id, "tag_id": tag_id, "position": 0 });
+        let (status, _) = request(&client, Method::Post, "/post_tags", Some(body));
+        assert_eq!(status, Status::Ok);
+    }
+
+    // the segments are in the order of the key fields
+    let (status, item) = request(&client, Method::Get, "/post_tags/1/2", None);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(item, json!({ "post_id": 1, "tag_id": 2, "position": 0 }));
+    let (status, _) = request(&client, Method::Get, "/post_tags/1/1", None);
+    assert_eq!(status, Status::NotFound);
+
+    let body = json!({ "position": 3 });
+    let (status, item) = request(&client, Method::Patch, "/post_tags/1/2", Some(body));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(item, json!({ "post_id": 1, "tag_id": 2, "position": 3 }));
+
+    let body = json!({ "post_id": 2, "tag_id": 1, "position": 5 });
+    let (status, item) = request(&client, Method::Put, "/post_tags/2/1", Some(body));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(item, json!({ "post_id": 2, "tag_id": 1, "position": 5 }));
+    // the key cannot be changed by an update
+    let body = json!({ "post_id": 1, "tag_id": 2, "position": 6 });
+    let (status, _) = request(&client, Method::Put, "/post_tags/2/1", Some(body));
+    assert_eq!(status, Status::BadRequest);
+    let (_, item) = request(&client, Method::Get, "/post_tags/1/2", None);
+    assert_eq!(item["position"], 3);
+
+    let (status, deleted) = request(&client, Method::Delete, "/post_tags/1/2", None);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(deleted, json!({ "deleted": 1 }));
+    let (status, _) = request(&client, Method::Get, "/post_tags/1/2", None);
+    assert_eq!(status, Status::NotFound);
+    let (status, _) = request(&client, Method::Delete, "/post_tags/1/2", None);
+    assert_eq!(status, Status::NotFound);
+    let (status, _) = request(&client, Method::Get, "/post_tags/2/1", None);
+    assert_eq!(status, Status::Ok);
+}
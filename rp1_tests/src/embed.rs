@@ -0,0 +1,274 @@
+use diesel::backend::Backend;
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::blocking::Client;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Build;
+use rocket::Rocket;
+use rp1::{CheckPermissions, CrudStruct, PermissionFilter};
+use serde_json::{json, Value};
+
+use crate::Db;
+
+#[rp1::crud(database = "Db", table = "users", backend = "sqlite")]
+#[derive(Debug)]
+struct User {
+    #[primary_key]
+    pub id: i32,
+    pub username: String,
+    #[serde(default)]
+    pub role: String,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(database = "Db", table = "posts", backend = "sqlite")]
+#[belongs_to(User)]
+#[has_many(Comment)]
+#[derive(Debug)]
+struct Post {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub content: String,
+    pub user_id: i32,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(database = "Db", table = "comments", backend = "sqlite", search = true)]
+#[belongs_to(Post)]
+#[belongs_to(User)]
+#[derive(Debug)]
+struct Comment {
+    #[primary_key]
+    pub id: i32,
+    pub content: String,
+    pub approved: bool,
+    pub post_id: i32,
+    pub user_id: Option<i32>,
+    pub anonymous_user: Option<String>,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+/// Users can only read themselves.
+impl CheckPermissions for User {
+    type AuthUser = AuthUser;
+
+    fn allow_read(&self, user: &AuthUser) -> bool {
+        self.id == user.id
+    }
+}
+
+impl CheckPermissions for Post {
+    type AuthUser = AuthUser;
+}
+
+/// Only approved comments can be read.
+impl CheckPermissions for Comment {
+    type AuthUser = AuthUser;
+
+    fn filter_list<DB>(_: &AuthUser) -> PermissionFilter<<Self as CrudStruct>::TableType, DB>
+    where
+        DB: Backend,
+    {
+        PermissionFilter::Filter(Box::new(crate::schema::comments::approved))
+    }
+}
+
+pub struct AuthUser {
+    id: i32,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = req
+            .headers()
+            .get_one("X-Auth-Id")
+            .and_then(|id| id.parse().ok())
+            .unwrap_or(0);
+
+        Outcome::Success(AuthUser { id })
+    }
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/users", User::get_routes())
+        .mount("/posts", Post::get_routes())
+        .mount("/comments", Comment::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+fn create(client: &Client, url: &str, body: String) -> Value {
+    client
+        .post(url.to_owned())
+        .body(body)
+        .header(ContentType::JSON)
+        .dispatch()
+        .into_json::<Value>()
+        .unwrap()
+}
+
+/// Creates two users with a post each, and comments on those posts, of which
+/// the third comment is not approved.
+fn create_comments(client: &Client) -> (Vec<Value>, Vec<Value>, Vec<Value>) {
+    let users = ["alice", "bob"]
+        .iter()
+        .map(|username| {
+            create(
+                client,
+                "/users/",
+                format!(r#"{{ "username": "{}" }}"#, username),
+            )
+        })
+        .collect::<Vec<_>>();
+    let posts = [("Rust tips", &users[0]), ("Go tips", &users[1])]
+        .iter()
+        .map(|(title, user)| {
+            create(
+                client,
+                "/posts/",
+                format!(
+                    r#"{{ "title": "{}", "subtitle": null, "content": "Bla", "user_id": {} }}"#,
+                    title, user["id"]
+                ),
+            )
+        })
+        .collect::<Vec<_>>();
+    let comments = [
+        (&posts[0], &users[1], true),
+        (&posts[0], &users[0], true),
+        (&posts[0], &users[1], false),
+        (&posts[1], &Value::Null, true),
+    ]
+    .iter()
+    .map(|(post, user, approved)| {
+        create(
+            client,
+            "/comments/",
+            format!(
+                r#"{{ "content": "Bla", "approved": {}, "post_id": {}, "user_id": {}, "anonymous_user": null }}"#,
+                approved, post["id"], user["id"]
+            ),
+        )
+    })
+    .collect::<Vec<_>>();
+
+    (users, posts, comments)
+}
+
+fn get(client: &Client, url: &str, user: &Value) -> Value {
+    let response = client
+        .get(url.to_owned())
+        .header(Header::new("X-Auth-Id", user["id"].to_string()))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok, "{}", url);
+    response.into_json::<Value>().unwrap()
+}
+
+#[test]
+fn embed_belongs_to() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (users, posts, comments) = create_comments(&client);
+
+    let comment = get(
+        &client,
+        &format!("/comments/{}?embed=post,user", comments[0]["id"]),
+        &users[1],
+    );
+    assert_eq!(comment["content"], "Bla");
+    assert_eq!(comment["post"], posts[0]);
+    assert_eq!(comment["user"], users[1]);
+
+    // users that can not be read are embedded as null, just like missing
+    // users, the comment that is not approved is not listed at all
+    let list = get(&client, "/comments?embed=user&sort=id", &users[1]);
+    let embedded = list
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c["user"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(embedded, vec![users[1].clone(), Value::Null, Value::Null]);
+
+    // the foreign key does not need to be included to embed an item
+    let list = get(
+        &client,
+        "/comments?embed=post&include=id&sort=-id",
+        &users[0],
+    );
+    assert_eq!(
+        list[0],
+        json!({ "id": comments[3]["id"], "post": posts[1] })
+    );
+
+    let comment = get(
+        &client,
+        &format!("/comments/{}", comments[0]["id"]),
+        &users[0],
+    );
+    assert!(comment.get("post").is_none());
+}
+
+#[test]
+fn embed_has_many() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (users, posts, comments) = create_comments(&client);
+
+    // comments that are not approved are excluded by the list filter
+    let post = get(
+        &client,
+        &format!("/posts/{}?embed=comments&embed=user", posts[0]["id"]),
+        &users[0],
+    );
+    assert_eq!(post["comments"], json!([comments[0], comments[1]]));
+    assert_eq!(post["user"], users[0]);
+
+    let list = get(&client, "/posts?embed=comments&sort=-id", &users[0]);
+    assert_eq!(list[0]["comments"], json!([comments[3]]));
+    assert_eq!(list[1]["comments"], json!([comments[0], comments[1]]));
+    assert!(list[0].get("user").is_none());
+}
+
+#[test]
+fn embed_search() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (users, posts, _) = create_comments(&client);
+
+    let response = client
+        .post("/comments/search")
+        .body(r#"{ "embed": ["post"], "sort": [{ "field": "id", "direction": "desc" }] }"#)
+        .header(ContentType::JSON)
+        .header(Header::new("X-Auth-Id", users[0]["id"].to_string()))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let list = response.into_json::<Value>().unwrap();
+    assert_eq!(list[0]["post"], posts[1]);
+}
+
+#[test]
+fn embed_unknown_relation() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (_, posts, _) = create_comments(&client);
+
+    for url in &[
+        "/comments?embed=author".to_owned(),
+        "/posts?embed=comments,post".to_owned(),
+        format!("/posts/{}?embed=tags", posts[0]["id"]),
+    ] {
+        let response = client.get(url.clone()).dispatch();
+        assert_eq!(response.status(), Status::BadRequest, "{}", url);
+    }
+}
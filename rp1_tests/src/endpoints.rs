@@ -2,39 +2,15 @@ use rocket::http::ContentType;
 use rocket::Build;
 use rocket::Rocket;
 
-use crate::schema;
+use crate::Db;
 
-use rocket_sync_db_pools::database;
-
-fn clear_database() {
-    use diesel::connection::Connection;
-    use diesel::prelude::RunQueryDsl;
-
-    let url_origin = "postgres://crud@127.0.0.1:5432/crud";
-    let connection = diesel::PgConnection::establish(url_origin).unwrap();
-
-    diesel::delete(schema::comments::table)
-        .execute(&connection)
-        .unwrap();
-
-    diesel::delete(schema::posts::table)
-        .execute(&connection)
-        .unwrap();
-
-    diesel::delete(schema::users::table)
-        .execute(&connection)
-        .unwrap();
-}
-
-#[database("diesel")]
-struct Db(diesel::PgConnection);
-
-#[rp1::crud(database = "Db", table = "users", auth = false)]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[rp1::crud(database = "Db", table = "users", backend = "sqlite", auth = false)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
 struct User {
     #[primary_key]
     pub id: i32,
     pub username: String,
+    #[serde(default)]
     pub role: String,
     #[generated]
     pub created_at: rp1::datetime::OffsetDateTime,
@@ -48,6 +24,7 @@ fn init_rocket() -> Rocket<Build> {
         // .mount("/posts", Post::get_routes())
         // .mount("/comments", Comment::get_routes())
         .attach(Db::fairing())
+        .attach(crate::run_migrations())
 }
 
 use rocket::http::Status;
@@ -73,8 +50,8 @@ fn create_user_json() {
 fn create_user_form() {
     let client = Client::tracked(init_rocket()).expect("valid rocket instance");
     let response = client
-        .post("/users/form")
-        .body("username=foobar")
+        .post("/users/")
+        .body("username=foobar&role=user")
         .header(ContentType::Form)
         .dispatch();
 
@@ -127,7 +104,7 @@ fn update_user_form() {
     assert_eq!(new_user.username, "foobar");
 
     let response = client
-        .patch(format!("/users/form/{}", new_user.id))
+        .patch(format!("/users/{}", new_user.id))
         .body("username=baz")
         .header(ContentType::Form)
         .dispatch();
@@ -183,8 +160,6 @@ fn retrieve_list_user() {
     let r = init_rocket();
     let client = Client::tracked(r).expect("valid rocket instance");
 
-    clear_database();
-
     let create_user_1 = client
         .post("/users/")
         .body(r#"{ "username" : "alice" }"#)
@@ -221,8 +196,6 @@ fn retrieve_list_user_sort() {
     let r = init_rocket();
     let client = Client::tracked(r).expect("valid rocket instance");
 
-    clear_database();
-
     let create_user_1 = client
         .post("/users/")
         .body(r#"{ "username" : "alice" }"#)
@@ -282,8 +255,6 @@ fn retrieve_list_user_filter() {
     let r = init_rocket();
     let client = Client::tracked(r).expect("valid rocket instance");
 
-    clear_database();
-
     let create_user_1 = client
         .post("/users/")
         .body(r#"{ "username" : "alice" }"#)
@@ -326,3 +297,107 @@ fn retrieve_list_user_filter() {
     let users = response.into_json::<Vec<User>>().unwrap();
     assert_eq!(&users, &[create_user_2, create_user_3]);
 }
+
+#[test]
+fn retrieve_list_user_filter_text() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let users = ["alice", "Malice_x", "bob%", "ALI"]
+        .iter()
+        .map(|username| {
+            client
+                .post("/users/")
+                .body(format!(r#"{{ "username" : "{}" }}"#, username))
+                .header(ContentType::JSON)
+                .dispatch()
+                .into_json::<User>()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    let filter = |query: &str| {
+        client
+            .get(format!("/users?{}", query))
+            .dispatch()
+            .into_json::<Vec<User>>()
+            .unwrap()
+    };
+
+    assert_eq!(
+        filter("filter[username]icontains=ALI"),
+        vec![users[0].clone(), users[1].clone(), users[3].clone()]
+    );
+    assert_eq!(
+        filter("filter[username]contains=%25"),
+        vec![users[2].clone()]
+    );
+    assert_eq!(filter("filter[username]contains=_"), vec![users[1].clone()]);
+    assert_eq!(
+        filter("filter[username]startswith=b"),
+        vec![users[2].clone()]
+    );
+    assert_eq!(
+        filter("filter[username]iendswith=X"),
+        vec![users[1].clone()]
+    );
+    assert_eq!(
+        filter("filter[username]like=_lice%"),
+        vec![users[0].clone()]
+    );
+    assert_eq!(
+        filter("filter[username]ilike=%25LI_%25&sort=id"),
+        vec![users[0].clone(), users[1].clone()]
+    );
+
+    let response = client.get("/users?filter[id]contains=1").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn retrieve_list_user_filter_text_case() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let users = ["Alice", "alice", "a*c?[d]"]
+        .iter()
+        .map(|username| {
+            client
+                .post("/users/")
+                .body(format!(r#"{{ "username" : "{}" }}"#, username))
+                .header(ContentType::JSON)
+                .dispatch()
+                .into_json::<User>()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    let filter = |query: &str| {
+        client
+            .get(format!("/users?{}", query))
+            .dispatch()
+            .into_json::<Vec<User>>()
+            .unwrap()
+    };
+
+    // the operators without an `i` prefix are case-sensitive on every backend
+    assert_eq!(
+        filter("filter[username]contains=Ali"),
+        vec![users[0].clone()]
+    );
+    assert_eq!(
+        filter("filter[username]startswith=a"),
+        vec![users[1].clone(), users[2].clone()]
+    );
+    assert_eq!(filter("filter[username]like=A%25"), vec![users[0].clone()]);
+    assert_eq!(
+        filter("filter[username]like=_lice"),
+        vec![users[0].clone(), users[1].clone()]
+    );
+    // wildcards of other pattern syntaxes are matched literally
+    assert_eq!(
+        filter("filter[username]contains=*c%3F[d]"),
+        vec![users[2].clone()]
+    );
+    assert_eq!(
+        filter("filter[username]like=a\\*%25"),
+        vec![users[2].clone()]
+    );
+    assert!(filter("filter[username]contains=*x").is_empty());
+}
@@ -0,0 +1,233 @@
+use std::io::Read;
+
+use rocket::http::{ContentType, Header, Method, Status};
+use rocket::local::blocking::{Client, LocalResponse};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Build;
+use rocket::Rocket;
+use serde_json::{json, Value};
+
+use rp1::CheckPermissions;
+
+use crate::{request, Db};
+
+#[rp1::crud(
+    database = "Db",
+    table = "tags",
+    backend = "sqlite",
+    bulk = true,
+    events = true
+)]
+#[derive(Debug, Clone)]
+struct Tag {
+    #[primary_key]
+    pub id: i32,
+    pub name: String,
+}
+
+impl CheckPermissions for Tag {
+    type AuthUser = AuthUser;
+
+    fn allow_read(&self, user: &AuthUser) -> bool {
+        !self.name.starts_with("secret") || user.name.as_deref() == Some("admin")
+    }
+}
+
+pub struct AuthUser {
+    name: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let name = req.headers().get_one("X-User").map(|n| n.to_owned());
+        Outcome::Success(AuthUser { name })
+    }
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/tags", Tag::get_routes())
+        .attach(Tag::events_fairing())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+fn subscribe<'c>(
+    client: &'c Client,
+    last_event_id: Option<u64>,
+    user: Option<&str>,
+) -> LocalResponse<'c> {
+    let mut request = client.get("/tags/events");
+    if let Some(id) = last_event_id {
+        request = request.header(Header::new("Last-Event-ID", id.to_string()));
+    }
+    if let Some(user) = user {
+        request = request.header(Header::new("X-User", user.to_owned()));
+    }
+    let response = request.dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::EventStream));
+    response
+}
+
+/// Reads the next `count` events from the stream, as their id, name and data.
+fn read_events(response: &mut LocalResponse<'_>, count: usize) -> Vec<(u64, String, Value)> {
+    let mut body = String::new();
+    while body.matches("\n\n").count() < count {
+        let mut buffer = [0; 1024];
+        let read = response.read(&mut buffer).expect("event stream");
+        assert!(read > 0, "event stream ended");
+        body.push_str(std::str::from_utf8(&buffer[..read]).unwrap());
+    }
+
+    body.split("\n\n")
+        .take(count)
+        .map(|event| {
+            let mut parsed = (0, String::new(), Value::Null);
+            for line in event.lines() {
+                if let Some(id) = line.strip_prefix("id:") {
+                    parsed.0 = id.trim().parse().unwrap();
+                } else if let Some(name) = line.strip_prefix("event:") {
+                    parsed.1 = name.trim().to_owned();
+                } else if let Some(data) = line.strip_prefix("data:") {
+                    parsed.2 = serde_json::from_str(data.trim()).unwrap();
+                }
+            }
+            parsed
+        })
+        .collect()
+}
+
+/// The names and data of the events, without their ids.
+fn without_ids(events: &[(u64, String, Value)]) -> Vec<(String, Value)> {
+    events
+        .iter()
+        .map(|(_, name, data)| (name.clone(), data.clone()))
+        .collect()
+}
+
+#[test]
+fn events_stream() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (_, tag) = request(&client, Method::Post, "/tags", Some(json!({ "name": "a" })));
+    let url = format!("/tags/{}", tag["id"]);
+    let mut stream = subscribe(&client, None, None);
+
+    let (status, updated) = request(&client, Method::Patch, &url, Some(json!({ "name": "b" })));
+    assert_eq!(status, Status::Ok);
+    let (status, _) = request(&client, Method::Delete, &url, None);
+    assert_eq!(status, Status::Ok);
+    // failed changes are not published
+    let (status, _) = request(&client, Method::Delete, &url, None);
+    assert_eq!(status, Status::NotFound);
+    let (_, tag) = request(&client, Method::Post, "/tags", Some(json!({ "name": "c" })));
+
+    let events = read_events(&mut stream, 3);
+    assert_eq!(
+        without_ids(&events),
+        vec![
+            ("updated".to_owned(), updated.clone()),
+            ("deleted".to_owned(), updated),
+            ("created".to_owned(), tag),
+        ]
+    );
+    assert_eq!(events[1].0, events[0].0 + 1);
+    assert_eq!(events[2].0, events[1].0 + 1);
+
+    // a reconnecting client receives the events it missed
+    let mut stream = subscribe(&client, Some(events[0].0), None);
+    assert_eq!(read_events(&mut stream, 2), events[1..].to_vec());
+}
+
+#[test]
+fn events_reset() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (_, tag) = request(&client, Method::Post, "/tags", Some(json!({ "name": "a" })));
+    let mut stream = subscribe(&client, None, None);
+    request(&client, Method::Post, "/tags", Some(json!({ "name": "b" })));
+    let last_id = read_events(&mut stream, 1)[0].0;
+
+    // the ids keep increasing after a restart, so an id from before the
+    // restart is older than any buffered event and resets the subscriber
+    let mut stream = subscribe(&client, Some(1), None);
+    assert_eq!(
+        read_events(&mut stream, 1),
+        vec![(last_id, "reset".to_owned(), Value::Null)]
+    );
+    let mut stream = subscribe(&client, Some(last_id + 1), None);
+    assert_eq!(read_events(&mut stream, 1)[0].1, "reset");
+
+    // the id of the reset event continues the stream
+    let mut stream = subscribe(&client, Some(last_id), None);
+    let url = format!("/tags/{}", tag["id"]);
+    let (_, updated) = request(&client, Method::Patch, &url, Some(json!({ "name": "c" })));
+    assert_eq!(
+        read_events(&mut stream, 1),
+        vec![(last_id + 1, "updated".to_owned(), updated)]
+    );
+}
+
+#[test]
+fn events_bulk() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let mut stream = subscribe(&client, None, None);
+    let body = json!([{ "name": "a" }, { "name": "b" }]);
+    let (status, tags) = request(&client, Method::Post, "/tags", Some(body));
+    assert_eq!(status, Status::Ok);
+
+    let (status, _) = request(&client, Method::Delete, "/tags?filter[name]in=a,b", None);
+    assert_eq!(status, Status::Ok);
+
+    assert_eq!(
+        without_ids(&read_events(&mut stream, 4)),
+        vec![
+            ("created".to_owned(), tags[0].clone()),
+            ("created".to_owned(), tags[1].clone()),
+            ("deleted".to_owned(), tags[0].clone()),
+            ("deleted".to_owned(), tags[1].clone()),
+        ]
+    );
+}
+
+#[test]
+fn events_permissions() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let mut bob = subscribe(&client, None, Some("bob"));
+    let mut admin = subscribe(&client, None, Some("admin"));
+    let (_, secret) = request(
+        &client,
+        Method::Post,
+        "/tags",
+        Some(json!({ "name": "secret" })),
+    );
+    let (_, public) = request(
+        &client,
+        Method::Post,
+        "/tags",
+        Some(json!({ "name": "public" })),
+    );
+
+    // events of items that cannot be read are skipped
+    assert_eq!(
+        without_ids(&read_events(&mut bob, 1)),
+        vec![("created".to_owned(), public.clone())]
+    );
+    assert_eq!(
+        without_ids(&read_events(&mut admin, 2)),
+        vec![
+            ("created".to_owned(), secret),
+            ("created".to_owned(), public),
+        ]
+    );
+}
+
+#[test]
+fn events_openapi() {
+    let operation = &Tag::openapi().paths["/events"]["get"];
+    assert_eq!(operation["operationId"], "tag_events");
+    assert_eq!(operation["parameters"][0]["name"], "Last-Event-ID");
+    assert!(operation["responses"]["200"]["content"]["text/event-stream"].is_object());
+}
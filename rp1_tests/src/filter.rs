@@ -0,0 +1,227 @@
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rocket::Build;
+use rocket::Rocket;
+
+use crate::{create_users_with_roles, Db};
+
+#[rp1::crud(database = "Db", table = "users", backend = "sqlite", auth = false)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct User {
+    #[primary_key]
+    pub id: i32,
+    pub username: String,
+    #[serde(default)]
+    pub role: String,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(database = "Db", table = "posts", backend = "sqlite", auth = false)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct Post {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub content: String,
+    pub user_id: i32,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(database = "Db", table = "comments", backend = "sqlite", auth = false)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct Comment {
+    #[primary_key]
+    pub id: i32,
+    pub content: String,
+    pub approved: bool,
+    pub post_id: i32,
+    pub user_id: Option<i32>,
+    pub anonymous_user: Option<String>,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/users", User::get_routes())
+        .mount("/posts", Post::get_routes())
+        .mount("/comments", Comment::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+/// Creates a comment for each of the given user ids and anonymous users, on
+/// a newly created post. The comments refer to the users created by this
+/// function, with the ids starting at 1.
+fn create_comments(client: &Client, users: &[(Option<i32>, Option<&str>)]) -> Vec<Comment> {
+    for username in &["alice", "bob"] {
+        client
+            .post("/users/")
+            .body(format!(r#"{{ "username": "{}" }}"#, username))
+            .header(ContentType::JSON)
+            .dispatch();
+    }
+    let post = client
+        .post("/posts/")
+        .body(r#"{ "title": "Bla", "content": "Bla", "user_id": 1 }"#)
+        .header(ContentType::JSON)
+        .dispatch()
+        .into_json::<Post>()
+        .unwrap();
+
+    users
+        .iter()
+        .map(|(user_id, anonymous_user)| {
+            let body = serde_json::json!({
+                "content": "Bla",
+                "approved": true,
+                "post_id": post.id,
+                "user_id": user_id,
+                "anonymous_user": anonymous_user,
+            });
+            client
+                .post("/comments/")
+                .body(body.to_string())
+                .header(ContentType::JSON)
+                .dispatch()
+                .into_json::<Comment>()
+                .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn retrieve_list_comment_filter_null() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let comments = create_comments(
+        &client,
+        &[
+            (Some(1), None),
+            (None, Some("anon")),
+            (Some(2), None),
+            (None, None),
+        ],
+    );
+
+    let filter = |query: &str| {
+        let response = client.get(format!("/comments?{}", query)).dispatch();
+        assert_eq!(response.status(), Status::Ok, "{}", query);
+        response.into_json::<Vec<Comment>>().unwrap()
+    };
+
+    let with_user = vec![comments[0].clone(), comments[2].clone()];
+    let without_user = vec![comments[1].clone(), comments[3].clone()];
+    assert_eq!(filter("filter[user_id]isnull=true"), without_user);
+    assert_eq!(filter("filter[user_id]isnull=false"), with_user);
+    assert_eq!(filter("filter[user_id]notnull=true"), with_user);
+    assert_eq!(filter("filter[user_id]="), without_user);
+    assert_eq!(filter("filter[user_id]ne="), with_user);
+    assert_eq!(
+        filter("filter[user_id]ne=1"),
+        vec![
+            comments[1].clone(),
+            comments[2].clone(),
+            comments[3].clone()
+        ]
+    );
+    assert_eq!(
+        filter("filter[user_id]in=,2"),
+        vec![
+            comments[1].clone(),
+            comments[2].clone(),
+            comments[3].clone()
+        ]
+    );
+    assert_eq!(filter("filter[user_id]in=1,2"), with_user);
+    assert_eq!(filter("filter[user_id]gt=1"), vec![comments[2].clone()]);
+    assert_eq!(filter("filter[user_id]le=1"), vec![comments[0].clone()]);
+    assert_eq!(
+        filter("filter[anonymous_user]notnull=true"),
+        vec![comments[1].clone()]
+    );
+    assert_eq!(
+        filter("filter[anonymous_user]startswith=an"),
+        vec![comments[1].clone()]
+    );
+    assert!(filter("filter[anonymous_user]startswith=AN").is_empty());
+
+    for query in &["filter[user_id]gt=", "filter[user_id]isnull=maybe"] {
+        let response = client.get(format!("/comments?{}", query)).dispatch();
+        assert_eq!(response.status(), Status::BadRequest, "{}", query);
+    }
+}
+
+#[test]
+fn retrieve_list_comment_filter_isnull_not_nullable() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let response = client
+        .get("/comments?filter[post_id]isnull=true")
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn retrieve_list_user_filter_groups() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let users: Vec<User> = create_users_with_roles(
+        &client,
+        &[
+            ("alice", "admin"),
+            ("bob", "editor"),
+            ("eve", "user"),
+            ("mallory", "admin"),
+        ],
+    );
+
+    let filter = |query: &str| {
+        let response = client.get(format!("/users?{}", query)).dispatch();
+        assert_eq!(response.status(), Status::Ok, "{}", query);
+        response.into_json::<Vec<User>>().unwrap()
+    };
+
+    assert_eq!(
+        filter("filter[or][0][role]=admin&filter[or][1][role]=editor"),
+        vec![users[0].clone(), users[1].clone(), users[3].clone()]
+    );
+    assert_eq!(
+        filter("filter[or][0][role]=admin&filter[or][1][role]=editor&filter[id]gt=1"),
+        vec![users[1].clone(), users[3].clone()]
+    );
+    assert_eq!(
+        filter("filter[not][role]=admin"),
+        vec![users[1].clone(), users[2].clone()]
+    );
+    assert_eq!(
+        filter("filter[or][a][role]=admin&filter[or][a][username]=alice&filter[or][b][role]=user"),
+        vec![users[0].clone(), users[2].clone()]
+    );
+    assert_eq!(
+        filter(concat!(
+            "filter[and][0][or][0][role]=admin&filter[and][0][or][1][role]=user",
+            "&filter[and][1][or][0][username]startswith=m&filter[and][1][or][1][username]=eve",
+        )),
+        vec![users[2].clone(), users[3].clone()]
+    );
+    assert_eq!(
+        filter("filter[not][or][0][role]=admin&filter[not][or][1][username]=eve"),
+        vec![users[1].clone()]
+    );
+
+    for query in &[
+        "filter[or][role]=admin",
+        "filter[not]=admin",
+        "filter[or][0][foo]=bar",
+    ] {
+        let response = client.get(format!("/users?{}", query)).dispatch();
+        assert_eq!(response.status(), Status::BadRequest, "{}", query);
+    }
+}
@@ -0,0 +1,347 @@
+use diesel::prelude::*;
+use diesel::sqlite::Sqlite;
+use rocket::http::{Method, Status};
+use rocket::local::blocking::Client;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Build;
+use rocket::Rocket;
+use serde_json::json;
+
+use rp1::{CheckPermissions, CrudError, CrudHooks, CrudResult};
+
+use crate::schema::tags;
+use crate::{request, Db};
+
+#[rp1::crud(
+    database = "Db",
+    table = "tags",
+    backend = "sqlite",
+    auth = false,
+    hooks = true,
+    bulk = true
+)]
+#[derive(Debug)]
+struct Tag {
+    #[primary_key]
+    pub id: i32,
+    #[validate(length(min = 1))]
+    pub name: String,
+}
+
+impl CrudHooks for Tag {
+    type Backend = Sqlite;
+
+    fn before_create<C>(new: &mut tag::NewTag, conn: &C) -> CrudResult<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        new.name = new.name.trim().to_lowercase();
+        let existing = tags::table
+            .filter(tags::name.eq(&new.name))
+            .count()
+            .get_result::<i64>(conn)?;
+        if existing > 0 {
+            return Err(CrudError::InvalidItem(format!(
+                "duplicate tag {}",
+                new.name
+            )));
+        }
+        Ok(())
+    }
+
+    fn after_create<C>(&self, _conn: &C) -> CrudResult<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        if self.name == "rollback" {
+            return Err(CrudError::Forbidden);
+        }
+        Ok(())
+    }
+
+    fn before_update<C>(&self, new: &mut tag::UpdatePutTag, _conn: &C) -> CrudResult<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        new.name = new.name.trim().to_lowercase();
+        Ok(())
+    }
+
+    fn before_delete<C>(&self, _conn: &C) -> CrudResult<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        if self.name == "protected" {
+            return Err(CrudError::Forbidden);
+        }
+        Ok(())
+    }
+}
+
+/// The tags of which the names are normalized by a hook, after which reserved
+/// names are not allowed to be created.
+#[rp1::crud(
+    database = "Db",
+    table = "tags",
+    backend = "sqlite",
+    hooks = true,
+    bulk = true
+)]
+#[derive(Debug)]
+struct Label {
+    #[primary_key]
+    pub id: i32,
+    pub name: String,
+}
+
+impl CheckPermissions for Label {
+    type AuthUser = AuthUser;
+
+    fn allow_create(new: &label::NewLabel, _: &AuthUser) -> bool {
+        new.name != "admin"
+    }
+
+    fn allow_update(&self, new: &label::UpdatePutLabel, _: &AuthUser) -> bool {
+        new.name != "admin"
+    }
+}
+
+impl CrudHooks for Label {
+    type Backend = Sqlite;
+
+    fn before_create<C>(new: &mut label::NewLabel, _conn: &C) -> CrudResult<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        new.name = new.name.trim().to_lowercase();
+        Ok(())
+    }
+
+    fn before_update<C>(&self, new: &mut label::UpdatePutLabel, _conn: &C) -> CrudResult<()>
+    where
+        C: Connection<Backend = Sqlite>,
+    {
+        new.name = new.name.trim().to_lowercase();
+        Ok(())
+    }
+}
+
+pub struct AuthUser;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(_: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(AuthUser)
+    }
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/tags", Tag::get_routes())
+        .mount("/labels", Label::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+#[test]
+fn create_hooks() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (status, tag) = request(
+        &client,
+        Method::Post,
+        "/tags",
+        Some(json!({ "name": " Rust " })),
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(tag["name"], "rust");
+
+    // the hook queries the table within the transaction
+    let body = json!([{ "name": "diesel" }, { "name": "RUST" }]);
+    let (status, _) = request(&client, Method::Post, "/tags", Some(body));
+    assert_eq!(status, Status::UnprocessableEntity);
+
+    // an error after the insert rolls back the created item
+    let body = json!({ "name": "rollback" });
+    let (status, _) = request(&client, Method::Post, "/tags", Some(body));
+    assert_eq!(status, Status::Forbidden);
+
+    let (_, list) = request(&client, Method::Get, "/tags", None);
+    assert_eq!(list, json!([tag]));
+}
+
+#[test]
+fn hooks_validation() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+
+    // the new item is validated after the hooks trimmed the name
+    let body = json!({ "name": "   " });
+    let (status, _) = request(&client, Method::Post, "/tags", Some(body.clone()));
+    assert_eq!(status, Status::BadRequest);
+    let (status, _) = request(&client, Method::Post, "/tags", Some(json!([body.clone()])));
+    assert_eq!(status, Status::BadRequest);
+
+    let (_, tag) = request(
+        &client,
+        Method::Post,
+        "/tags",
+        Some(json!({ "name": "rust" })),
+    );
+    let url = format!("/tags/{}", tag["id"]);
+    let (status, _) = request(&client, Method::Patch, &url, Some(body));
+    assert_eq!(status, Status::BadRequest);
+
+    let (_, list) = request(&client, Method::Get, "/tags", None);
+    assert_eq!(list, json!([tag]));
+}
+
+#[test]
+fn hooks_permissions() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+
+    // the permissions are checked again after the hook changed the new item
+    let body = json!({ "name": " Admin " });
+    let (status, _) = request(&client, Method::Post, "/labels", Some(body.clone()));
+    assert_eq!(status, Status::Forbidden);
+    let body = json!([{ "name": "rust" }, body]);
+    let (status, _) = request(&client, Method::Post, "/labels", Some(body));
+    assert_eq!(status, Status::Forbidden);
+
+    let (_, list) = request(&client, Method::Get, "/labels", None);
+    assert_eq!(list, json!([]));
+
+    let (_, label) = request(
+        &client,
+        Method::Post,
+        "/labels",
+        Some(json!({ "name": "rust" })),
+    );
+    let url = format!("/labels/{}", label["id"]);
+    let body = json!({ "name": "ADMIN" });
+    let (status, _) = request(&client, Method::Patch, &url, Some(body));
+    assert_eq!(status, Status::NotFound);
+    let body = json!({ "id": label["id"], "name": "Admin" });
+    let (status, _) = request(&client, Method::Put, &url, Some(body));
+    assert_eq!(status, Status::NotFound);
+
+    let (_, list) = request(&client, Method::Get, "/labels", None);
+    assert_eq!(list, json!([label]));
+}
+
+#[test]
+fn update_hooks() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (_, tag) = request(
+        &client,
+        Method::Post,
+        "/tags",
+        Some(json!({ "name": "rust" })),
+    );
+    let url = format!("/tags/{}", tag["id"]);
+
+    let body = json!({ "id": tag["id"], "name": " Rocket " });
+    let (status, updated) = request(&client, Method::Put, &url, Some(body));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(updated["name"], "rocket");
+
+    let body = json!({ "name": "DIESEL" });
+    let (status, updated) = request(&client, Method::Patch, &url, Some(body));
+    assert_eq!(status, Status::Ok);
+    assert_eq!(updated["name"], "diesel");
+
+    let body = json!({ "name": "diesel" });
+    let (status, _) = request(&client, Method::Patch, "/tags/99", Some(body));
+    assert_eq!(status, Status::NotFound);
+}
+
+#[test]
+fn delete_hooks() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (_, tag) = request(
+        &client,
+        Method::Post,
+        "/tags",
+        Some(json!({ "name": "rust" })),
+    );
+    let (_, protected) = request(
+        &client,
+        Method::Post,
+        "/tags",
+        Some(json!({ "name": "protected" })),
+    );
+
+    let url = format!("/tags/{}", protected["id"]);
+    let (status, _) = request(&client, Method::Delete, &url, None);
+    assert_eq!(status, Status::Forbidden);
+
+    let url = format!("/tags/{}", tag["id"]);
+    let (status, deleted) = request(&client, Method::Delete, &url, None);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(deleted, json!({ "deleted": 1 }));
+    let (status, _) = request(&client, Method::Delete, &url, None);
+    assert_eq!(status, Status::NotFound);
+
+    let (_, list) = request(&client, Method::Get, "/tags", None);
+    assert_eq!(list, json!([protected]));
+}
+
+#[test]
+fn bulk_hooks() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    for name in ["rust", "rocket", "protected"] {
+        request(
+            &client,
+            Method::Post,
+            "/tags",
+            Some(json!({ "name": name })),
+        );
+    }
+
+    let body = json!({ "name": " Diesel " });
+    let (status, updated) = request(
+        &client,
+        Method::Patch,
+        "/tags?filter[name]=rust",
+        Some(body),
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(updated, json!({ "updated": 1 }));
+    // the items are validated and checked again after the hooks
+    let body = json!({ "name": "   " });
+    let (status, _) = request(
+        &client,
+        Method::Patch,
+        "/tags?filter[name]=rocket",
+        Some(body),
+    );
+    assert_eq!(status, Status::BadRequest);
+    let body = json!({ "name": " Admin " });
+    let (status, _) = request(
+        &client,
+        Method::Patch,
+        "/labels?filter[name]=rocket",
+        Some(body),
+    );
+    assert_eq!(status, Status::Forbidden);
+
+    let (status, _) = request(
+        &client,
+        Method::Delete,
+        "/tags?filter[name]in=rocket,protected",
+        None,
+    );
+    assert_eq!(status, Status::Forbidden);
+    let (_, list) = request(&client, Method::Get, "/tags", None);
+    let names = list
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tag| tag["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["diesel", "rocket", "protected"]);
+
+    let (status, deleted) = request(&client, Method::Delete, "/tags?filter[name]=rocket", None);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(deleted, json!({ "deleted": 1 }));
+}
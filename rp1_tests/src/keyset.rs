@@ -0,0 +1,232 @@
+use rocket::http::ContentType;
+use rocket::Build;
+use rocket::Rocket;
+
+use crate::{create_users, Db};
+
+#[rp1::crud(database = "Db", table = "users", backend = "sqlite", auth = false)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct User {
+    #[primary_key]
+    pub id: i32,
+    pub username: String,
+    #[serde(default)]
+    pub role: String,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(
+    database = "Db",
+    table = "posts",
+    backend = "sqlite",
+    auth = false,
+    default_sort = "-subtitle:nullslast"
+)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct Post {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub content: String,
+    pub user_id: i32,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/users", User::get_routes())
+        .mount("/posts", Post::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+use rocket::http::Status;
+use rocket::local::blocking::Client;
+
+/// Retrieves all pages for the given query using keyset pagination.
+fn walk_pages<T: serde::de::DeserializeOwned + Send + 'static>(
+    client: &Client,
+    url: &str,
+) -> Vec<Vec<T>> {
+    let mut pages = vec![];
+    let mut cursor: Option<String> = None;
+    loop {
+        let url = match &cursor {
+            Some(cursor) => format!("{}&after={}", url, cursor),
+            None => url.to_owned(),
+        };
+        let response = client.get(url).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        cursor = response
+            .headers()
+            .get_one("X-Next-Cursor")
+            .map(|c| c.to_owned());
+        pages.push(response.into_json::<Vec<T>>().unwrap());
+        if cursor.is_none() {
+            return pages;
+        }
+    }
+}
+
+#[test]
+fn retrieve_list_user_after() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let users: Vec<User> = create_users(&client, &["bob", "alice", "eve", "bob", "alice"]);
+
+    let pages = walk_pages::<User>(&client, "/users?sort=username&limit=2");
+    let expected = [&users[1], &users[4], &users[0], &users[3], &users[2]];
+    assert_eq!(pages.len(), 3);
+    assert_eq!(
+        pages.iter().flatten().collect::<Vec<_>>(),
+        expected.to_vec()
+    );
+
+    let pages = walk_pages::<User>(&client, "/users?sort=-username&limit=2");
+    let expected = [&users[2], &users[0], &users[3], &users[1], &users[4]];
+    assert_eq!(
+        pages.iter().flatten().collect::<Vec<_>>(),
+        expected.to_vec()
+    );
+}
+
+#[test]
+fn retrieve_list_post_after_nullable() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let user = &create_users::<User>(&client, &["alice"])[0];
+    let posts = [Some("b"), None, Some("a"), None, Some("b")]
+        .iter()
+        .map(|subtitle| {
+            let subtitle = subtitle.map_or("null".to_owned(), |s| format!(r#""{}""#, s));
+            client
+                .post("/posts/")
+                .body(format!(
+                    r#"{{ "title": "Bla", "subtitle": {}, "content": "Bla", "user_id": {} }}"#,
+                    subtitle, user.id
+                ))
+                .header(ContentType::JSON)
+                .dispatch()
+                .into_json::<Post>()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    for sort in &[
+        "subtitle",
+        "-subtitle",
+        "subtitle:nullsfirst",
+        "subtitle:nullslast",
+        "-subtitle:nullsfirst",
+        "-subtitle:nullslast:ci",
+    ] {
+        let all = client
+            .get(format!("/posts?sort={}", sort))
+            .dispatch()
+            .into_json::<Vec<Post>>()
+            .unwrap();
+        let pages = walk_pages::<Post>(&client, &format!("/posts?sort={}&limit=2", sort));
+        assert_eq!(pages.into_iter().flatten().collect::<Vec<_>>(), all);
+        assert_eq!(all.len(), posts.len());
+
+        let null_positions = all.iter().map(|p| p.subtitle.is_none()).collect::<Vec<_>>();
+        if sort.contains("nullsfirst") {
+            assert_eq!(null_positions, [true, true, false, false, false]);
+        } else if sort.contains("nullslast") {
+            assert_eq!(null_positions, [false, false, false, true, true]);
+        }
+    }
+}
+
+#[test]
+fn retrieve_list_post_default_sort() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let user = &create_users::<User>(&client, &["alice"])[0];
+    let posts = [Some("a"), None, Some("b"), Some("a"), None]
+        .iter()
+        .map(|subtitle| {
+            let subtitle = subtitle.map_or("null".to_owned(), |s| format!(r#""{}""#, s));
+            client
+                .post("/posts/")
+                .body(format!(
+                    r#"{{ "title": "Bla", "subtitle": {}, "content": "Bla", "user_id": {} }}"#,
+                    subtitle, user.id
+                ))
+                .header(ContentType::JSON)
+                .dispatch()
+                .into_json::<Post>()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+
+    // ties are broken by the primary key
+    let expected = [&posts[2], &posts[0], &posts[3], &posts[1], &posts[4]];
+    let pages = walk_pages::<Post>(&client, "/posts?limit=2");
+    assert_eq!(pages.len(), 3);
+    assert_eq!(
+        pages.iter().flatten().collect::<Vec<_>>(),
+        expected.to_vec()
+    );
+
+    // an explicit sort replaces the default sort
+    let all = client
+        .get("/posts?sort=id")
+        .dispatch()
+        .into_json::<Vec<Post>>()
+        .unwrap();
+    assert_eq!(all, posts);
+}
+
+#[test]
+fn retrieve_list_user_sort_case_insensitive() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let users: Vec<User> = create_users(&client, &["bob", "Carol", "alice", "Bob"]);
+
+    let pages = walk_pages::<User>(&client, "/users?sort=username&limit=3");
+    let expected = [&users[3], &users[1], &users[2], &users[0]];
+    assert_eq!(
+        pages.iter().flatten().collect::<Vec<_>>(),
+        expected.to_vec()
+    );
+
+    let pages = walk_pages::<User>(&client, "/users?sort=username:ci&limit=1");
+    let expected = [&users[2], &users[0], &users[3], &users[1]];
+    assert_eq!(pages.len(), 5);
+    assert_eq!(
+        pages.iter().flatten().collect::<Vec<_>>(),
+        expected.to_vec()
+    );
+
+    let pages = walk_pages::<User>(&client, "/users?sort=-username:ci&limit=3");
+    let expected = [&users[1], &users[0], &users[3], &users[2]];
+    assert_eq!(
+        pages.iter().flatten().collect::<Vec<_>>(),
+        expected.to_vec()
+    );
+
+    for sort in &["id:ci", "username:unknown", "username:nullsfirst:nullslast"] {
+        let response = client.get(format!("/users?sort={}", sort)).dispatch();
+        assert_eq!(response.status(), Status::BadRequest, "{}", sort);
+    }
+}
+
+#[test]
+fn retrieve_list_user_invalid_cursor() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    create_users::<User>(&client, &["alice", "bob"]);
+
+    let response = client.get("/users?after=nonsense").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let response = client.get("/users?sort=username&limit=1").dispatch();
+    let cursor = response.headers().get_one("X-Next-Cursor").unwrap();
+    let response = client
+        .get(format!("/users?sort=-username&after={}", cursor))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
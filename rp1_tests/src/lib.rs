@@ -2,8 +2,157 @@
 
 #[macro_use]
 extern crate diesel;
+#[macro_use]
+extern crate diesel_migrations;
 
 mod access_control;
+mod audit;
+mod bulk;
+mod cache;
+mod composite;
+mod embed;
 mod endpoints;
+mod events;
+mod filter;
+mod hooks;
+mod keyset;
+mod many_to_many;
+#[cfg(feature = "mysql")]
+mod mysql;
+mod nested;
+mod openapi;
+mod pagination;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod relation;
 mod schema;
+mod search;
+mod soft_delete;
 mod validate;
+mod version;
+
+use rocket::fairing::AdHoc;
+use rocket::http::{ContentType, Header, Method, Status};
+use rocket::local::blocking::{Client, LocalResponse};
+use rocket_sync_db_pools::database;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+embed_migrations!();
+
+#[database("diesel")]
+pub struct Db(diesel::SqliteConnection);
+
+/// Every rocket instance gets its own in-memory database, this fairing makes
+/// sure the schema is created before any request is handled.
+pub fn run_migrations() -> AdHoc {
+    AdHoc::on_ignite("Run migrations", |rocket| async {
+        let db = Db::get_one(&rocket)
+            .await
+            .expect("database connection for migrations");
+        db.run(|conn| embedded_migrations::run(conn))
+            .await
+            .expect("database migrations");
+        rocket
+    })
+}
+
+/// Dispatches a request with an optional JSON body and the given headers.
+pub fn send<'c>(
+    client: &'c Client,
+    method: Method,
+    url: &str,
+    body: Option<String>,
+    headers: Vec<Header<'static>>,
+) -> LocalResponse<'c> {
+    let mut request = client.req(method, url.to_owned());
+    if let Some(body) = body {
+        request = request.body(body).header(ContentType::JSON);
+    }
+    for header in headers {
+        request = request.header(header);
+    }
+    request.dispatch()
+}
+
+/// The status and JSON body of a response, the body is `null` if it is not
+/// JSON.
+pub fn json_response(response: LocalResponse<'_>) -> (Status, Value) {
+    (
+        response.status(),
+        response.into_json::<Value>().unwrap_or(Value::Null),
+    )
+}
+
+/// Dispatches a request with an optional JSON body, returning the status and
+/// JSON body of the response.
+pub fn request(client: &Client, method: Method, url: &str, body: Option<Value>) -> (Status, Value) {
+    request_with_headers(client, method, url, body, &[])
+}
+
+/// Dispatches a request like [request], with the given headers.
+pub fn request_with_headers(
+    client: &Client,
+    method: Method,
+    url: &str,
+    body: Option<Value>,
+    headers: &[(&str, &str)],
+) -> (Status, Value) {
+    let (status, _, body) = request_with_etag(client, method, url, body, headers);
+    (status, body)
+}
+
+/// Dispatches a request like [request_with_headers], also returning the `ETag`
+/// of the response.
+pub fn request_with_etag(
+    client: &Client,
+    method: Method,
+    url: &str,
+    body: Option<Value>,
+    headers: &[(&str, &str)],
+) -> (Status, Option<String>, Value) {
+    let body = body.map(|body| body.to_string());
+    let headers = headers
+        .iter()
+        .map(|(name, value)| Header::new(name.to_string(), value.to_string()))
+        .collect();
+    let response = send(client, method, url, body, headers);
+    let etag = response
+        .headers()
+        .get_one("ETag")
+        .map(|etag| etag.to_owned());
+    let (status, body) = json_response(response);
+    (status, etag, body)
+}
+
+/// Creates the users with the given usernames, which get the default role.
+pub fn create_users<T: DeserializeOwned + Send + 'static>(
+    client: &Client,
+    usernames: &[&str],
+) -> Vec<T> {
+    usernames
+        .iter()
+        .map(|username| create_user(client, json!({ "username": username })))
+        .collect()
+}
+
+/// Creates the users with the given usernames and roles.
+pub fn create_users_with_roles<T: DeserializeOwned + Send + 'static>(
+    client: &Client,
+    users: &[(&str, &str)],
+) -> Vec<T> {
+    users
+        .iter()
+        .map(|(username, role)| create_user(client, json!({ "username": username, "role": role })))
+        .collect()
+}
+
+fn create_user<T: DeserializeOwned + Send + 'static>(client: &Client, body: Value) -> T {
+    client
+        .post("/users/")
+        .body(body.to_string())
+        .header(ContentType::JSON)
+        .dispatch()
+        .into_json::<T>()
+        .unwrap()
+}
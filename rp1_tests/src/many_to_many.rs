@@ -0,0 +1,300 @@
+use diesel::connection::SimpleConnection;
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::local::blocking::Client;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Build;
+use rocket::Rocket;
+use rp1::CheckPermissions;
+use serde_json::{json, Value};
+
+use crate::{request_with_headers, Db};
+
+#[rp1::crud(database = "Db", table = "users", backend = "sqlite", auth = false)]
+#[derive(Debug)]
+struct User {
+    #[primary_key]
+    pub id: i32,
+    pub username: String,
+    #[serde(default)]
+    pub role: String,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(database = "Db", table = "posts", backend = "sqlite")]
+#[many_to_many(Tag, through = "post_tags")]
+#[derive(Debug)]
+struct Post {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub content: String,
+    pub user_id: i32,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(database = "Db", table = "tags", backend = "sqlite")]
+#[derive(Debug)]
+struct Tag {
+    #[primary_key]
+    pub id: i32,
+    pub name: String,
+}
+
+/// Only the author of a post can change its tags.
+impl CheckPermissions for Post {
+    type AuthUser = AuthUser;
+
+    fn allow_link(&self, relation: &str, user: &AuthUser) -> bool {
+        relation == "tags" && self.user_id == user.id
+    }
+}
+
+/// Hidden tags can not be read, and locked tags can not be linked.
+impl CheckPermissions for Tag {
+    type AuthUser = AuthUser;
+
+    fn allow_read(&self, _: &AuthUser) -> bool {
+        self.name != "hidden"
+    }
+
+    fn allow_link(&self, relation: &str, _: &AuthUser) -> bool {
+        relation == "tags" && self.name != "locked"
+    }
+}
+
+pub struct AuthUser {
+    id: i32,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = req
+            .headers()
+            .get_one("X-Auth-Id")
+            .and_then(|id| id.parse().ok())
+            .unwrap_or(0);
+
+        Outcome::Success(AuthUser { id })
+    }
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/users", User::get_routes())
+        .mount("/posts", Post::get_routes())
+        .mount("/tags", Tag::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+/// Creates a user with a post, and the tags `a`, `b`, `c`, `hidden` and
+/// `locked`.
+fn create_post_and_tags(client: &Client) -> (Value, Vec<Value>) {
+    use rocket::http::Method::Post;
+
+    let body = json!({ "username": "alice" });
+    request_with_headers(client, Post, "/users/", Some(body), &[("X-Auth-Id", "1")]);
+    let body = json!({ "title": "Tips", "subtitle": null, "content": "Bla", "user_id": 1 });
+    let (_, post) =
+        request_with_headers(client, Post, "/posts/", Some(body), &[("X-Auth-Id", "1")]);
+    let tags = ["a", "b", "c", "hidden", "locked"]
+        .iter()
+        .map(|name| {
+            let body = json!({ "name": name });
+            request_with_headers(client, Post, "/tags/", Some(body), &[("X-Auth-Id", "1")]).1
+        })
+        .collect();
+
+    (post, tags)
+}
+
+#[test]
+fn link_and_unlink() {
+    use rocket::http::Method::{Delete, Get, Put};
+
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (post, tags) = create_post_and_tags(&client);
+    let url = |tag: &Value| format!("/posts/{}/tags/{}", post["id"], tag["id"]);
+
+    // linking is idempotent
+    for tag in &[&tags[0], &tags[0], &tags[1]] {
+        let (status, linked) =
+            request_with_headers(&client, Put, &url(tag), None, &[("X-Auth-Id", "1")]);
+        assert_eq!(status, Status::Ok);
+        assert_eq!(&linked, *tag);
+    }
+
+    let list_url = format!("/posts/{}/tags", post["id"]);
+    let (status, list) = request_with_headers(
+        &client,
+        Get,
+        &format!("{}?sort=-name", list_url),
+        None,
+        &[("X-Auth-Id", "1")],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(list, json!([tags[1], tags[0]]));
+
+    // the list parameters of the related struct can be used
+    let (_, list) = request_with_headers(
+        &client,
+        Get,
+        &format!("{}?filter[name]=a&include=name", list_url),
+        None,
+        &[("X-Auth-Id", "1")],
+    );
+    assert_eq!(list, json!([{ "name": "a" }]));
+    let (status, _) = request_with_headers(
+        &client,
+        Get,
+        &format!("{}?sort=title", list_url),
+        None,
+        &[("X-Auth-Id", "1")],
+    );
+    assert_eq!(status, Status::BadRequest);
+
+    let (status, deleted) =
+        request_with_headers(&client, Delete, &url(&tags[0]), None, &[("X-Auth-Id", "1")]);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(deleted, json!({ "deleted": 1 }));
+    let (_, deleted) =
+        request_with_headers(&client, Delete, &url(&tags[0]), None, &[("X-Auth-Id", "1")]);
+    assert_eq!(deleted, json!({ "deleted": 0 }));
+
+    let (_, list) = request_with_headers(&client, Get, &list_url, None, &[("X-Auth-Id", "1")]);
+    assert_eq!(list, json!([tags[1]]));
+}
+
+#[test]
+fn set_links() {
+    use rocket::http::Method::{Get, Put};
+
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (post, tags) = create_post_and_tags(&client);
+    let url = format!("/posts/{}/tags", post["id"]);
+
+    let body = json!([tags[2]["id"], tags[0]["id"], tags[2]["id"]]);
+    let (status, linked) =
+        request_with_headers(&client, Put, &url, Some(body), &[("X-Auth-Id", "1")]);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(linked, json!([tags[2], tags[0]]));
+
+    let body = json!([tags[1]["id"], tags[0]["id"]]);
+    request_with_headers(&client, Put, &url, Some(body), &[("X-Auth-Id", "1")]);
+    let (_, list) = request_with_headers(
+        &client,
+        Get,
+        &format!("{}?sort=id", url),
+        None,
+        &[("X-Auth-Id", "1")],
+    );
+    assert_eq!(list, json!([tags[0], tags[1]]));
+
+    let (status, linked) =
+        request_with_headers(&client, Put, &url, Some(json!([])), &[("X-Auth-Id", "1")]);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(linked, json!([]));
+    let (_, list) = request_with_headers(&client, Get, &url, None, &[("X-Auth-Id", "1")]);
+    assert_eq!(list, json!([]));
+}
+
+#[test]
+fn link_permissions() {
+    use rocket::http::Method::{Delete, Get, Put};
+
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (post, tags) = create_post_and_tags(&client);
+    let url = |post_id: &Value, tag: &Value| format!("/posts/{}/tags/{}", post_id, tag["id"]);
+    request_with_headers(
+        &client,
+        Put,
+        &url(&post["id"], &tags[0]),
+        None,
+        &[("X-Auth-Id", "1")],
+    );
+
+    // only the author can change the tags of a post, hidden tags, locked tags
+    // and missing posts are not found
+    let cases = [
+        (Put, url(&post["id"], &tags[1]), "2"),
+        (Delete, url(&post["id"], &tags[0]), "2"),
+        (Put, url(&post["id"], &tags[3]), "1"),
+        (Put, url(&post["id"], &tags[4]), "1"),
+        (Delete, url(&post["id"], &tags[4]), "1"),
+        (Put, url(&json!(1000), &tags[1]), "1"),
+        (Get, "/posts/1000/tags".to_owned(), "1"),
+    ];
+    for (method, url, user) in cases.iter() {
+        let (status, _) = request_with_headers(&client, *method, url, None, &[("X-Auth-Id", user)]);
+        assert_eq!(status, Status::NotFound, "{} {}", method, url);
+    }
+
+    // no links are changed when one of the tags is not found
+    let list_url = format!("/posts/{}/tags", post["id"]);
+    for tag in &[&tags[3], &tags[4]] {
+        let body = json!([tags[1]["id"], tag["id"]]);
+        let (status, _) =
+            request_with_headers(&client, Put, &list_url, Some(body), &[("X-Auth-Id", "1")]);
+        assert_eq!(status, Status::NotFound);
+    }
+
+    let (_, list) = request_with_headers(&client, Get, &list_url, None, &[("X-Auth-Id", "2")]);
+    assert_eq!(list, json!([tags[0]]));
+}
+
+#[test]
+fn list_many_links() {
+    use rocket::http::Method::Get;
+
+    // more tags than SQLite allows parameters in a single query, which is at
+    // most 250000 on common builds
+    let fixture = AdHoc::on_ignite("Link many tags", |rocket| async {
+        let db = Db::get_one(&rocket).await.expect("database connection");
+        db.run(|conn| {
+            conn.batch_execute(
+                "INSERT INTO users (username, role) VALUES ('alice', 'user');
+                INSERT INTO posts (title, content, user_id) VALUES ('Tips', 'Bla', 1);
+                WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 250001)
+                INSERT INTO tags (name) SELECT 'tag' || i FROM n;
+                INSERT INTO post_tags (post_id, tag_id) SELECT 1, id FROM tags;",
+            )
+        })
+        .await
+        .expect("linked tags");
+        rocket
+    });
+    let client = Client::tracked(init_rocket().attach(fixture)).expect("valid rocket instance");
+
+    let url = "/posts/1/tags?filter[name]=tag250001";
+    let (status, list) = request_with_headers(&client, Get, url, None, &[("X-Auth-Id", "1")]);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(list, json!([{ "id": 250001, "name": "tag250001" }]));
+}
+
+#[test]
+fn link_openapi() {
+    let resource = Post::openapi();
+    let list = &resource.paths["/{id}/tags"]["get"];
+    assert_eq!(list["operationId"], "post_tag_list");
+    assert_eq!(list["parameters"][0]["name"], "id");
+    assert!(resource.schemas.contains_key("Tag"));
+    assert_eq!(
+        resource.paths["/{id}/tags"]["put"]["requestBody"]["content"]["application/json"]["schema"]
+            ["items"]["type"],
+        "integer"
+    );
+    let link = &resource.paths["/{id}/tags/{related_id}"];
+    assert_eq!(link["put"]["operationId"], "post_link_tags");
+    assert_eq!(link["delete"]["parameters"][1]["name"], "related_id");
+}
@@ -0,0 +1,114 @@
+//! The routes generated for MySQL, which are compiled but not run, as the
+//! tests run on SQLite. Check them with `cargo check --tests --features mysql`.
+
+use diesel::mysql::Mysql;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket_sync_db_pools::database;
+
+use rp1::{CheckPermissions, CrudHooks};
+
+#[database("mysql")]
+pub struct MysqlDb(diesel::MysqlConnection);
+
+pub mod schema {
+    table! {
+        audit_log (id) {
+            id -> BigInt,
+            resource -> Text,
+            record_id -> Text,
+            action -> Text,
+            actor -> Nullable<Text>,
+            created_at -> Datetime,
+            before -> Nullable<Text>,
+            after -> Nullable<Text>,
+        }
+    }
+
+    table! {
+        notes (id) {
+            id -> Integer,
+            title -> Text,
+            version -> Integer,
+            deleted_at -> Nullable<Datetime>,
+        }
+    }
+
+    table! {
+        post_tags (post_id, tag_id) {
+            post_id -> Integer,
+            tag_id -> Integer,
+            position -> Integer,
+        }
+    }
+}
+
+#[rp1::crud(
+    database = "MysqlDb",
+    table = "notes",
+    schema = "crate::mysql::schema",
+    backend = "mysql",
+    hooks = true,
+    bulk = true,
+    bulk_limit = 100,
+    audit = "audit_log",
+    events = true,
+    search = true,
+    pagination = true
+)]
+#[derive(Debug, Clone)]
+struct Note {
+    #[primary_key]
+    pub id: i32,
+    #[searchable]
+    pub title: String,
+    #[version]
+    pub version: i32,
+    #[soft_delete]
+    pub deleted_at: Option<rp1::datetime::OffsetDateTime>,
+}
+
+impl CheckPermissions for Note {
+    type AuthUser = AuthUser;
+}
+
+impl CrudHooks for Note {
+    type Backend = Mysql;
+}
+
+#[rp1::crud(
+    database = "MysqlDb",
+    table = "post_tags",
+    schema = "crate::mysql::schema",
+    backend = "mysql",
+    auth = false,
+    bulk = true
+)]
+#[derive(Debug)]
+struct PostTag {
+    #[primary_key]
+    pub post_id: i32,
+    #[primary_key]
+    pub tag_id: i32,
+    pub position: i32,
+}
+
+pub struct AuthUser;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(_: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(AuthUser)
+    }
+}
+
+#[test]
+fn mysql_routes() {
+    let rocket = rocket::build()
+        .mount("/notes", Note::get_routes())
+        .mount("/post_tags", PostTag::get_routes());
+    assert!(rocket
+        .routes()
+        .any(|route| route.uri.path() == "/notes/<id>/history"));
+}
@@ -0,0 +1,309 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::blocking::Client;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Build;
+use rocket::Rocket;
+use rp1::CheckPermissions;
+use serde_json::Value;
+
+use crate::Db;
+
+#[rp1::crud(database = "Db", table = "users", backend = "sqlite", auth = false)]
+#[derive(Debug)]
+struct User {
+    #[primary_key]
+    pub id: i32,
+    pub username: String,
+    #[serde(default)]
+    pub role: String,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(database = "Db", table = "posts", backend = "sqlite")]
+#[derive(Debug)]
+struct Post {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub content: String,
+    pub user_id: i32,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(
+    database = "Db",
+    table = "comments",
+    backend = "sqlite",
+    parent = "Post"
+)]
+#[belongs_to(Post)]
+#[derive(Debug)]
+struct Comment {
+    #[primary_key]
+    pub id: i32,
+    pub content: String,
+    pub approved: bool,
+    pub post_id: i32,
+    pub user_id: Option<i32>,
+    pub anonymous_user: Option<String>,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+/// Users can only read their own posts.
+impl CheckPermissions for Post {
+    type AuthUser = AuthUser;
+
+    fn allow_read(&self, user: &AuthUser) -> bool {
+        self.user_id == user.id
+    }
+}
+
+impl CheckPermissions for Comment {
+    type AuthUser = AuthUser;
+}
+
+pub struct AuthUser {
+    id: i32,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let id = req
+            .headers()
+            .get_one("X-Auth-Id")
+            .and_then(|id| id.parse().ok())
+            .unwrap_or(0);
+
+        Outcome::Success(AuthUser { id })
+    }
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/users", User::get_routes())
+        .mount("/posts", Post::get_routes())
+        .mount("/posts", Comment::get_nested_routes())
+        .mount("/comments", Comment::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+fn create(client: &Client, url: &str, body: String, user: &Value) -> (Status, Value) {
+    let response = client
+        .post(url.to_owned())
+        .body(body)
+        .header(ContentType::JSON)
+        .header(Header::new("X-Auth-Id", user["id"].to_string()))
+        .dispatch();
+    (response.status(), response.into_json::<Value>().unwrap())
+}
+
+fn get(client: &Client, url: &str, user: &Value) -> (Status, Value) {
+    let response = client
+        .get(url.to_owned())
+        .header(Header::new("X-Auth-Id", user["id"].to_string()))
+        .dispatch();
+    (
+        response.status(),
+        response.into_json::<Value>().unwrap_or(Value::Null),
+    )
+}
+
+/// Creates two users with a post each, of which the first post has two
+/// comments and the second post one comment, created using the nested route.
+fn create_comments(client: &Client) -> (Vec<Value>, Vec<Value>, Vec<Value>) {
+    let users = ["alice", "bob"]
+        .iter()
+        .map(|username| {
+            let body = format!(r#"{{ "username": "{}" }}"#, username);
+            create(client, "/users/", body, &Value::Null).1
+        })
+        .collect::<Vec<_>>();
+    let posts = users
+        .iter()
+        .map(|user| {
+            let body = format!(
+                r#"{{ "title": "Tips", "subtitle": null, "content": "Bla", "user_id": {} }}"#,
+                user["id"]
+            );
+            create(client, "/posts/", body, user).1
+        })
+        .collect::<Vec<_>>();
+    let comments = [
+        (&posts[0], &users[0]),
+        (&posts[0], &users[0]),
+        (&posts[1], &users[1]),
+    ]
+    .iter()
+    .map(|(post, user)| {
+        let (status, comment) = create(
+            client,
+            &format!("/posts/{}/comments", post["id"]),
+            r#"{ "content": "Bla", "approved": true, "user_id": null, "anonymous_user": null }"#
+                .to_owned(),
+            user,
+        );
+        assert_eq!(status, Status::Ok);
+        comment
+    })
+    .collect::<Vec<_>>();
+
+    (users, posts, comments)
+}
+
+#[test]
+fn nested_create() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (users, posts, comments) = create_comments(&client);
+
+    assert_eq!(comments[0]["post_id"], posts[0]["id"]);
+    assert_eq!(comments[2]["post_id"], posts[1]["id"]);
+
+    // the foreign key in the path takes precedence over the body
+    let (status, comment) = create(
+        &client,
+        &format!("/posts/{}/comments", posts[0]["id"]),
+        format!(
+            r#"{{ "content": "Bla", "approved": true, "post_id": {}, "user_id": null, "anonymous_user": null }}"#,
+            posts[1]["id"]
+        ),
+        &users[0],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(comment["post_id"], posts[0]["id"]);
+
+    // arrays of items are created for the same parent
+    let (status, created) = create(
+        &client,
+        &format!("/posts/{}/comments", posts[1]["id"]),
+        r#"[{ "content": "A", "approved": true, "user_id": null, "anonymous_user": null }, { "content": "B", "approved": true, "user_id": null, "anonymous_user": null }]"#
+            .to_owned(),
+        &users[1],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(created[0]["post_id"], posts[1]["id"]);
+    assert_eq!(created[1]["post_id"], posts[1]["id"]);
+
+    // missing fields are still rejected
+    let (status, _) = create(
+        &client,
+        &format!("/posts/{}/comments", posts[0]["id"]),
+        r#"{ "approved": true }"#.to_owned(),
+        &users[0],
+    );
+    assert_eq!(status, Status::UnprocessableEntity);
+
+    // a post that does not exist or can not be read by the user is not found
+    for (post_id, user) in &[
+        (&Value::from(1000), &users[0]),
+        (&posts[1]["id"], &users[0]),
+    ] {
+        let (status, _) = create(
+            &client,
+            &format!("/posts/{}/comments", post_id),
+            r#"{ "content": "Bla", "approved": true, "user_id": null, "anonymous_user": null }"#
+                .to_owned(),
+            user,
+        );
+        assert_eq!(status, Status::NotFound);
+    }
+}
+
+#[test]
+fn nested_list() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (users, posts, comments) = create_comments(&client);
+
+    let (status, list) = get(
+        &client,
+        &format!("/posts/{}/comments?sort=-id", posts[0]["id"]),
+        &users[0],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(
+        list,
+        Value::from(vec![comments[1].clone(), comments[0].clone()])
+    );
+
+    // the other list parameters can be used as well
+    let (_, list) = get(
+        &client,
+        &format!(
+            "/posts/{}/comments?filter[id]={}&embed=post",
+            posts[0]["id"], comments[0]["id"]
+        ),
+        &users[0],
+    );
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert_eq!(list[0]["post"], posts[0]);
+
+    let (status, _) = get(&client, "/posts/1000/comments", &users[0]);
+    assert_eq!(status, Status::NotFound);
+    let (status, _) = get(
+        &client,
+        &format!("/posts/{}/comments", posts[1]["id"]),
+        &users[0],
+    );
+    assert_eq!(status, Status::NotFound);
+
+    // the flat list is not restricted to a parent
+    let (_, list) = get(&client, "/comments", &users[0]);
+    assert_eq!(list.as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn nested_read() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let (users, posts, comments) = create_comments(&client);
+
+    let (status, comment) = get(
+        &client,
+        &format!("/posts/{}/comments/{}", posts[0]["id"], comments[0]["id"]),
+        &users[0],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(comment, comments[0]);
+
+    // comments of other posts are not found
+    let (status, _) = get(
+        &client,
+        &format!("/posts/{}/comments/{}", posts[0]["id"], comments[2]["id"]),
+        &users[0],
+    );
+    assert_eq!(status, Status::NotFound);
+    let (status, _) = get(
+        &client,
+        &format!("/posts/{}/comments/{}", posts[1]["id"], comments[2]["id"]),
+        &users[0],
+    );
+    assert_eq!(status, Status::NotFound);
+}
+
+#[test]
+fn nested_openapi() {
+    let resource = Comment::nested_openapi();
+    let list = &resource.paths["/{post_id}/comments"]["get"];
+    assert_eq!(list["operationId"], "nested_comment_list");
+    assert_eq!(list["parameters"][0]["name"], "post_id");
+    assert_eq!(list["responses"]["404"]["description"], "Parent not found");
+    assert!(resource.paths["/{post_id}/comments"].get("post").is_some());
+    assert!(resource.paths["/{post_id}/comments/{id}"]
+        .get("get")
+        .is_some());
+    assert!(resource.paths["/{post_id}/comments/{id}"]
+        .get("patch")
+        .is_none());
+}
@@ -0,0 +1,192 @@
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Build;
+use rocket::Rocket;
+use rp1::openapi::OpenApi;
+use rp1::CheckPermissions;
+use serde_json::Value;
+
+use crate::Db;
+
+#[rp1::crud(
+    database = "Db",
+    table = "users",
+    backend = "sqlite",
+    auth = false,
+    search = true
+)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct User {
+    #[primary_key]
+    pub id: i32,
+    #[searchable]
+    pub username: String,
+    #[serde(default)]
+    pub role: String,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(
+    database = "Db",
+    table = "posts",
+    backend = "sqlite",
+    auth = false,
+    delete = false,
+    partials = false
+)]
+#[belongs_to(User)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct Post {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    pub subtitle: Option<String>,
+    #[not_filterable]
+    pub content: String,
+    pub user_id: i32,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+/// The comments with authorization, which is documented in their operations.
+#[rp1::crud(database = "Db", table = "comments", backend = "sqlite")]
+struct Comment {
+    #[primary_key]
+    pub id: i32,
+    pub content: String,
+    pub approved: bool,
+    pub post_id: i32,
+    pub user_id: Option<i32>,
+    pub anonymous_user: Option<String>,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+impl CheckPermissions for Comment {
+    type AuthUser = AuthUser;
+}
+
+pub struct AuthUser;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(_: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(AuthUser)
+    }
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/users", User::get_routes())
+        .mount("/posts", Post::get_routes())
+        .mount(
+            "/api",
+            OpenApi::new("Test", "1.0.0")
+                .resource("/users", User::openapi())
+                .resource("/posts", Post::openapi()),
+        )
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+#[test]
+fn retrieve_openapi_document() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let response = client.get("/api/openapi.json").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::JSON));
+    let document: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+
+    assert_eq!(document["info"]["title"], "Test");
+    let paths = document["paths"].as_object().unwrap();
+    let mut path_names = paths.keys().collect::<Vec<_>>();
+    path_names.sort();
+    assert_eq!(
+        path_names,
+        vec![
+            "/posts",
+            "/posts/{id}",
+            "/users",
+            "/users/search",
+            "/users/{id}"
+        ]
+    );
+    assert_eq!(paths["/users/search"]["post"]["operationId"], "user_search");
+    assert!(
+        paths["/users"]["get"]["parameters"][0]["schema"]["items"]["pattern"]
+            .as_str()
+            .unwrap()
+            .ends_with("|relevance)(:(nullsfirst|nullslast|ci))*$")
+    );
+
+    let users = &paths["/users/{id}"];
+    for method in &["get", "patch", "put", "delete"] {
+        assert!(users[method].is_object(), "missing {}", method);
+    }
+    assert!(paths["/posts/{id}"]["delete"].is_null());
+    assert_eq!(
+        users["get"]["parameters"][0]["schema"],
+        serde_json::json!({ "type": "integer", "format": "int32" })
+    );
+
+    let schemas = &document["components"]["schemas"];
+    assert_eq!(
+        schemas["NewUser"]["required"],
+        serde_json::json!(["username"])
+    );
+    assert_eq!(
+        schemas["Post"]["properties"]["subtitle"],
+        serde_json::json!({ "type": "string", "nullable": true })
+    );
+    assert!(schemas["PartialOutputUser"].is_object());
+    assert!(schemas["PartialOutputPost"].is_null());
+    assert!(schemas["CrudError"].is_object());
+
+    let parameters = paths["/posts"]["get"]["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert!(parameters.contains(&"filter[title]"));
+    assert!(parameters.contains(&"filter[user_id]ge"));
+    assert!(parameters.contains(&"filter[title]icontains"));
+    assert!(!parameters.contains(&"filter[user_id]icontains"));
+    assert!(parameters.contains(&"filter[subtitle]isnull"));
+    assert!(!parameters.contains(&"filter[title]isnull"));
+    assert!(!parameters.contains(&"filter[content]"));
+    assert!(!parameters.contains(&"include"));
+    assert!(!parameters.contains(&"q"));
+    assert!(parameters.contains(&"embed"));
+    assert_eq!(
+        paths["/posts/{id}"]["get"]["parameters"][1]["schema"]["items"]["enum"],
+        serde_json::json!(["user"])
+    );
+    assert!(paths["/users/{id}"]["get"]["parameters"][1].is_null());
+}
+
+#[test]
+fn openapi_not_allowed() {
+    let openapi = Comment::openapi();
+    let create = &openapi.paths["/"]["post"]["responses"];
+    assert!(create["403"].is_object());
+    // single items are not found instead of forbidden
+    for method in &["get", "patch", "put", "delete"] {
+        let responses = &openapi.paths["/{id}"][method]["responses"];
+        assert!(responses["403"].is_null());
+        assert_eq!(
+            responses["404"]["description"],
+            "Item not found, or not allowed"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use rocket::Build;
+use rocket::Rocket;
+
+use crate::{create_users, Db};
+
+#[rp1::crud(
+    database = "Db",
+    table = "users",
+    backend = "sqlite",
+    auth = false,
+    pagination = true
+)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct User {
+    #[primary_key]
+    pub id: i32,
+    pub username: String,
+    #[serde(default)]
+    pub role: String,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Page {
+    items: Vec<User>,
+    total: i64,
+    offset: i64,
+    limit: i64,
+    next: Option<String>,
+    prev: Option<String>,
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/users", User::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+use rocket::http::Status;
+use rocket::local::blocking::Client;
+
+#[test]
+fn retrieve_list_user_page() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let users: Vec<User> = create_users(&client, &["alice", "eve", "bob", "mallory", "trent"]);
+
+    let response = client.get("/users?sort=id&offset=2&limit=2").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("X-Total-Count"), Some("5"));
+    assert_eq!(
+        response.headers().get_one("Link"),
+        Some(
+            r#"</users?sort=id&offset=4&limit=2>; rel="next", </users?sort=id&offset=0&limit=2>; rel="prev""#
+        )
+    );
+
+    let page = response.into_json::<Page>().unwrap();
+    assert_eq!(&page.items, &users[2..4]);
+    assert_eq!(page.total, 5);
+    assert_eq!(page.offset, 2);
+    assert_eq!(page.limit, 2);
+    assert_eq!(
+        page.next.as_deref(),
+        Some("/users?sort=id&offset=4&limit=2")
+    );
+    assert_eq!(
+        page.prev.as_deref(),
+        Some("/users?sort=id&offset=0&limit=2")
+    );
+
+    let response = client.get("/users?sort=id&offset=4&limit=2").dispatch();
+    let page = response.into_json::<Page>().unwrap();
+    assert_eq!(&page.items, &users[4..]);
+    assert_eq!(page.next, None);
+}
+
+#[test]
+fn retrieve_list_user_page_filter() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    create_users::<User>(&client, &["alice", "eve", "bob"]);
+
+    let response = client.get("/users?filter[username]ne=eve").dispatch();
+    assert_eq!(response.headers().get_one("X-Total-Count"), Some("2"));
+    assert_eq!(response.headers().get_one("Link"), None);
+
+    let page = response.into_json::<Page>().unwrap();
+    assert_eq!(page.items.len(), 2);
+    assert_eq!(page.total, 2);
+    assert_eq!(page.next, None);
+    assert_eq!(page.prev, None);
+}
@@ -0,0 +1,140 @@
+//! The routes generated for Postgres, which are compiled but not run, as the
+//! tests run on SQLite. Check them with `cargo check --tests --features postgres`.
+//! The conversions of the date and time types are tested against the server at
+//! `POSTGRES_URL` with `cargo test --features postgres -- --ignored`.
+
+use diesel::pg::Pg;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket_sync_db_pools::database;
+
+use rp1::{CheckPermissions, CrudHooks};
+
+#[database("postgres")]
+pub struct PgDb(diesel::PgConnection);
+
+pub mod schema {
+    table! {
+        audit_log (id) {
+            id -> BigInt,
+            resource -> Text,
+            record_id -> Text,
+            action -> Text,
+            actor -> Nullable<Text>,
+            created_at -> Timestamptz,
+            before -> Nullable<Text>,
+            after -> Nullable<Text>,
+        }
+    }
+
+    table! {
+        notes (id) {
+            id -> Integer,
+            title -> Text,
+            version -> Integer,
+            deleted_at -> Nullable<Timestamptz>,
+        }
+    }
+
+    table! {
+        post_tags (post_id, tag_id) {
+            post_id -> Integer,
+            tag_id -> Integer,
+            position -> Integer,
+        }
+    }
+}
+
+#[rp1::crud(
+    database = "PgDb",
+    table = "notes",
+    schema = "crate::postgres::schema",
+    backend = "postgres",
+    hooks = true,
+    bulk = true,
+    bulk_limit = 100,
+    audit = "audit_log",
+    events = true,
+    search = true,
+    pagination = true
+)]
+#[derive(Debug, Clone)]
+struct Note {
+    #[primary_key]
+    pub id: i32,
+    #[searchable]
+    pub title: String,
+    #[version]
+    pub version: i32,
+    #[soft_delete]
+    pub deleted_at: Option<rp1::datetime::OffsetDateTime>,
+}
+
+impl CheckPermissions for Note {
+    type AuthUser = AuthUser;
+}
+
+impl CrudHooks for Note {
+    type Backend = Pg;
+}
+
+#[rp1::crud(
+    database = "PgDb",
+    table = "post_tags",
+    schema = "crate::postgres::schema",
+    backend = "postgres",
+    auth = false,
+    bulk = true
+)]
+#[derive(Debug)]
+struct PostTag {
+    #[primary_key]
+    pub post_id: i32,
+    #[primary_key]
+    pub tag_id: i32,
+    pub position: i32,
+}
+
+pub struct AuthUser;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(_: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(AuthUser)
+    }
+}
+
+#[test]
+fn postgres_routes() {
+    let rocket = rocket::build()
+        .mount("/notes", Note::get_routes())
+        .mount("/post_tags", PostTag::get_routes());
+    assert!(rocket
+        .routes()
+        .any(|route| route.uri.path() == "/notes/<id>/history"));
+}
+
+#[test]
+#[ignore = "needs a Postgres server at POSTGRES_URL"]
+fn date_round_trip() {
+    use diesel::dsl::sql;
+    use diesel::prelude::*;
+    use diesel::sql_types::{Date, Text};
+
+    let url = std::env::var("POSTGRES_URL").expect("POSTGRES_URL");
+    let conn = diesel::PgConnection::establish(&url).expect("postgres connection");
+    for text in &["2000-01-01", "1999-12-31", "2021-06-15"] {
+        let date: rp1::datetime::Date = text.parse().unwrap();
+        let (stored, read) = diesel::select((
+            sql::<Text>("CAST(")
+                .bind::<Date, _>(date.clone())
+                .sql(" AS TEXT)"),
+            sql::<Date>("").bind::<Date, _>(date.clone()),
+        ))
+        .get_result::<(String, rp1::datetime::Date)>(&conn)
+        .unwrap();
+        assert_eq!(&stored, text);
+        assert_eq!(read, date);
+    }
+}
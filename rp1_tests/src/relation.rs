@@ -0,0 +1,231 @@
+use rocket::http::ContentType;
+use rocket::Build;
+use rocket::Rocket;
+
+use crate::Db;
+
+#[rp1::crud(database = "Db", table = "users", backend = "sqlite", auth = false)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct User {
+    #[primary_key]
+    pub id: i32,
+    pub username: String,
+    #[serde(default)]
+    pub role: String,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(database = "Db", table = "posts", backend = "sqlite", auth = false)]
+#[belongs_to(User)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct Post {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    #[not_sortable]
+    pub subtitle: Option<String>,
+    #[not_filterable]
+    pub content: String,
+    pub user_id: i32,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[rp1::crud(
+    database = "Db",
+    table = "comments",
+    backend = "sqlite",
+    auth = false,
+    search = true
+)]
+#[belongs_to(Post, foreign_key = "post_id")]
+#[belongs_to(User)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct Comment {
+    #[primary_key]
+    pub id: i32,
+    pub content: String,
+    pub approved: bool,
+    pub post_id: i32,
+    pub user_id: Option<i32>,
+    pub anonymous_user: Option<String>,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/users", User::get_routes())
+        .mount("/posts", Post::get_routes())
+        .mount("/comments", Comment::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+use rocket::http::Status;
+use rocket::local::blocking::Client;
+
+fn create<T: serde::de::DeserializeOwned + Send + 'static>(
+    client: &Client,
+    url: &str,
+    body: String,
+) -> T {
+    client
+        .post(url.to_owned())
+        .body(body)
+        .header(ContentType::JSON)
+        .dispatch()
+        .into_json::<T>()
+        .unwrap()
+}
+
+/// Creates two users with a post each, and four comments on those posts.
+fn create_comments(client: &Client) -> Vec<Comment> {
+    let users: Vec<User> = ["alice", "bob"]
+        .iter()
+        .map(|username| {
+            create(
+                client,
+                "/users/",
+                format!(r#"{{ "username": "{}" }}"#, username),
+            )
+        })
+        .collect();
+    let posts: Vec<Post> = [("Rust tips", &users[0]), ("Go tips", &users[1])]
+        .iter()
+        .map(|(title, user)| {
+            create(
+                client,
+                "/posts/",
+                format!(
+                    r#"{{ "title": "{}", "subtitle": null, "content": "Bla", "user_id": {} }}"#,
+                    title, user.id
+                ),
+            )
+        })
+        .collect();
+
+    [
+        (&posts[0], Some(&users[1])),
+        (&posts[1], Some(&users[0])),
+        (&posts[0], None),
+        (&posts[1], Some(&users[1])),
+    ]
+    .iter()
+    .map(|(post, user)| {
+        let user_id = user.map_or("null".to_owned(), |u| u.id.to_string());
+        create(
+            client,
+            "/comments/",
+            format!(
+                r#"{{ "content": "Bla", "approved": true, "post_id": {}, "user_id": {}, "anonymous_user": null }}"#,
+                post.id, user_id
+            ),
+        )
+    })
+    .collect()
+}
+
+fn list_comments(client: &Client, query: &str) -> Vec<Comment> {
+    let response = client.get(format!("/comments?{}", query)).dispatch();
+    assert_eq!(response.status(), Status::Ok, "{}", query);
+    response.into_json::<Vec<Comment>>().unwrap()
+}
+
+#[test]
+fn filter_related() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let comments = create_comments(&client);
+
+    let cases: &[(&str, &[usize])] = &[
+        ("filter[post.title]contains=Rust", &[0, 2]),
+        ("filter[post][title]=Go%20tips", &[1, 3]),
+        ("filter[user.username]=bob", &[0, 3]),
+        ("filter[post.user.username]=bob", &[1, 3]),
+        (
+            "filter[or][0][post.title]=Go%20tips&filter[or][1][user.username]=bob",
+            &[0, 1, 3],
+        ),
+        (
+            "filter[post.title]contains=tips&filter[user_id]isnull=true",
+            &[2],
+        ),
+    ];
+    for (query, expected) in cases {
+        let expected = expected.iter().map(|i| &comments[*i]).collect::<Vec<_>>();
+        let result = list_comments(&client, &format!("{}&sort=id", query));
+        assert_eq!(result.iter().collect::<Vec<_>>(), expected, "{}", query);
+    }
+
+    // only the filterable fields of the related struct can be used
+    for query in &["filter[post.content]=Bla", "filter[post.unknown]=Bla"] {
+        let response = client.get(format!("/comments?{}", query)).dispatch();
+        assert_eq!(response.status(), Status::BadRequest, "{}", query);
+    }
+}
+
+#[test]
+fn sort_related() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let comments = create_comments(&client);
+
+    let cases: &[(&str, &[usize])] = &[
+        ("sort=post.title", &[1, 3, 0, 2]),
+        ("sort=-post.title:ci", &[0, 2, 1, 3]),
+        ("sort=post.user.username&sort=-id", &[2, 0, 3, 1]),
+        // comments without a user are excluded, as with an inner join
+        ("sort=-user.username", &[0, 3, 1]),
+        ("sort=user.username&filter[user_id]isnull=true", &[]),
+    ];
+    for (query, expected) in cases {
+        let expected = expected.iter().map(|i| &comments[*i]).collect::<Vec<_>>();
+        let result = list_comments(&client, query);
+        assert_eq!(result.iter().collect::<Vec<_>>(), expected, "{}", query);
+    }
+
+    // the fields of related rows can not be used in a cursor
+    let response = client.get("/comments?sort=post.title&limit=1").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("X-Next-Cursor"), None);
+    let response = client.get("/comments?sort=id&limit=1").dispatch();
+    let cursor = response.headers().get_one("X-Next-Cursor").unwrap();
+    let response = client
+        .get(format!("/comments?sort=post.title&after={}", cursor))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    // only the sortable fields of the related struct can be used
+    for query in &["sort=post.subtitle", "sort=post.unknown", "sort=user"] {
+        let response = client.get(format!("/comments?{}", query)).dispatch();
+        assert_eq!(response.status(), Status::BadRequest, "{}", query);
+    }
+}
+
+#[test]
+fn search_related() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let comments = create_comments(&client);
+
+    let response = client
+        .post("/comments/search")
+        .body(
+            r#"{
+                "filter": { "post": { "title": { "contains": "tips" } }, "user.username": "bob" },
+                "sort": [{ "field": "post.title", "direction": "desc" }]
+            }"#,
+        )
+        .header(ContentType::JSON)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(
+        response.into_json::<Vec<Comment>>().unwrap(),
+        vec![comments[0].clone(), comments[3].clone()]
+    );
+}
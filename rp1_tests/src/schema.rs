@@ -1,40 +1,107 @@
+table! {
+    audit_log (id) {
+        id -> BigInt,
+        resource -> Text,
+        record_id -> Text,
+        action -> Text,
+        actor -> Nullable<Text>,
+        created_at -> Timestamp,
+        before -> Nullable<Text>,
+        after -> Nullable<Text>,
+    }
+}
+
 table! {
     comments (id) {
-        id -> Int4,
+        id -> Integer,
         content -> Text,
         approved -> Bool,
-        post_id -> Int4,
-        user_id -> Nullable<Int4>,
-        anonymous_user -> Nullable<Varchar>,
-        created_at -> Timestamptz,
-        updated_at -> Timestamptz,
+        post_id -> Integer,
+        user_id -> Nullable<Integer>,
+        anonymous_user -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    documents (id) {
+        id -> Integer,
+        title -> Text,
+        content -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    note_tags (note_id, tag_id) {
+        note_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
+table! {
+    notes (id) {
+        id -> Integer,
+        title -> Text,
+        archived -> Bool,
+        deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    pages (id) {
+        id -> Integer,
+        title -> Text,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    post_tags (post_id, tag_id) {
+        post_id -> Integer,
+        tag_id -> Integer,
+        position -> Integer,
     }
 }
 
 table! {
     posts (id) {
-        id -> Int4,
-        title -> Varchar,
-        subtitle -> Nullable<Varchar>,
+        id -> Integer,
+        title -> Text,
+        subtitle -> Nullable<Text>,
         content -> Text,
-        user_id -> Int4,
-        created_at -> Timestamptz,
-        updated_at -> Timestamptz,
+        user_id -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    tags (id) {
+        id -> Integer,
+        name -> Text,
     }
 }
 
 table! {
     users (id) {
-        id -> Int4,
-        username -> Varchar,
-        role -> Varchar,
-        created_at -> Timestamptz,
-        updated_at -> Timestamptz,
+        id -> Integer,
+        username -> Text,
+        role -> Text,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
     }
 }
 
 joinable!(comments -> posts (post_id));
 joinable!(comments -> users (user_id));
+joinable!(note_tags -> notes (note_id));
+joinable!(note_tags -> tags (tag_id));
+joinable!(post_tags -> posts (post_id));
+joinable!(post_tags -> tags (tag_id));
 joinable!(posts -> users (user_id));
 
-allow_tables_to_appear_in_same_query!(comments, posts, users,);
+allow_tables_to_appear_in_same_query!(
+    audit_log, comments, documents, note_tags, notes, pages, post_tags, posts, tags, users,
+);
@@ -0,0 +1,152 @@
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rocket::Build;
+use rocket::Rocket;
+
+use crate::{create_users_with_roles, Db};
+
+#[rp1::crud(
+    database = "Db",
+    table = "users",
+    backend = "sqlite",
+    auth = false,
+    pagination = true,
+    search = true
+)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+struct User {
+    #[primary_key]
+    pub id: i32,
+    #[searchable]
+    pub username: String,
+    #[serde(default)]
+    #[searchable]
+    pub role: String,
+    #[generated]
+    pub created_at: rp1::datetime::OffsetDateTime,
+    #[generated]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Page {
+    items: Vec<User>,
+    total: i64,
+    next: Option<String>,
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/users", User::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+#[test]
+fn search_user() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let users: Vec<User> = create_users_with_roles(
+        &client,
+        &[
+            ("alice", "admin"),
+            ("bob", "editor"),
+            ("eve", "user"),
+            ("mallory", "admin"),
+        ],
+    );
+
+    let response = client
+        .post("/users/search")
+        .body(
+            r#"{
+                "filter": {
+                    "or": [{ "role": "editor" }, { "role": { "in": ["admin", "root"] } }],
+                    "not": { "username": "mallory" }
+                },
+                "sort": [{ "field": "username", "direction": "desc" }],
+                "limit": 1
+            }"#,
+        )
+        .header(ContentType::JSON)
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("X-Total-Count"), Some("2"));
+    let page = response.into_json::<Page>().unwrap();
+    assert_eq!(page.items, vec![users[1].clone()]);
+    assert_eq!(page.total, 2);
+    assert_eq!(page.next, None);
+
+    let response = client
+        .post("/users/search")
+        .body("{}")
+        .header(ContentType::JSON)
+        .dispatch();
+    let page = response.into_json::<Page>().unwrap();
+    assert_eq!(page.items, users);
+}
+
+#[test]
+fn search_user_invalid() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+
+    for body in &[
+        r#"{ "filter": { "unknown": 1 } }"#,
+        r#"{ "filter": { "id": { "lt": "one" } } }"#,
+        r#"{ "sort": [{ "field": "unknown", "direction": "asc" }] }"#,
+        r#"{ "unknown": true }"#,
+    ] {
+        let response = client
+            .post("/users/search")
+            .body(body)
+            .header(ContentType::JSON)
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest, "{}", body);
+    }
+}
+
+#[test]
+fn search_user_query() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let users: Vec<User> = create_users_with_roles(
+        &client,
+        &[
+            ("alice", "admin"),
+            ("bob", "editor"),
+            ("adminbot", "user"),
+            ("mallory", "admin"),
+        ],
+    );
+
+    let response = client.get("/users?q=ADMIN&sort=relevance").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.headers().get_one("X-Total-Count"), Some("3"));
+    let page = response.into_json::<Page>().unwrap();
+    // matches in the username are more relevant than matches in the role
+    assert_eq!(
+        page.items,
+        vec![users[2].clone(), users[0].clone(), users[3].clone()]
+    );
+
+    let response = client
+        .get("/users?q=admin%20ali&filter[username]ne=adminbot")
+        .dispatch();
+    let page = response.into_json::<Page>().unwrap();
+    assert_eq!(page.items, vec![users[0].clone()]);
+
+    let response = client
+        .post("/users/search")
+        .body(r#"{ "q": "bob", "sort": [{ "field": "relevance", "direction": "asc" }] }"#)
+        .header(ContentType::JSON)
+        .dispatch();
+    let page = response.into_json::<Page>().unwrap();
+    assert_eq!(page.items, vec![users[1].clone()]);
+
+    let response = client
+        .get("/users?q=admin&sort=relevance&limit=1")
+        .dispatch();
+    assert_eq!(response.headers().get_one("X-Next-Cursor"), None);
+    let response = client
+        .get("/users?q=admin&sort=relevance&after=abc")
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
@@ -0,0 +1,384 @@
+use rocket::http::{Method, Status};
+use rocket::local::blocking::Client;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Build;
+use rocket::Rocket;
+use serde_json::{json, Value};
+
+use rp1::CheckPermissions;
+
+use crate::{request, request_with_headers, Db};
+
+#[rp1::crud(database = "Db", table = "notes", backend = "sqlite", search = true)]
+#[many_to_many(Tag, through = "note_tags")]
+#[derive(Debug)]
+struct Note {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    #[serde(default)]
+    pub archived: bool,
+    #[soft_delete]
+    pub deleted_at: Option<rp1::datetime::OffsetDateTime>,
+}
+
+impl CheckPermissions for Note {
+    type AuthUser = AuthUser;
+
+    fn allow_read_deleted(user: &AuthUser) -> bool {
+        user.admin
+    }
+
+    fn allow_restore(&self, user: &AuthUser) -> bool {
+        user.admin
+    }
+}
+
+/// The notes that are archived instead of deleted.
+#[rp1::crud(
+    database = "Db",
+    table = "notes",
+    backend = "sqlite",
+    auth = false,
+    bulk = true,
+    audit = "audit_log"
+)]
+#[derive(Debug)]
+struct Archive {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    #[soft_delete]
+    pub archived: bool,
+    #[generated]
+    pub deleted_at: Option<rp1::datetime::OffsetDateTime>,
+}
+
+/// The notes without authorization, of which the rows are changed by a single
+/// statement in the bulk routes.
+#[rp1::crud(
+    database = "Db",
+    table = "notes",
+    backend = "sqlite",
+    auth = false,
+    bulk = true
+)]
+#[derive(Debug)]
+struct PublicNote {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    #[serde(default)]
+    pub archived: bool,
+    #[soft_delete]
+    pub deleted_at: Option<rp1::datetime::OffsetDateTime>,
+}
+
+#[rp1::crud(database = "Db", table = "tags", backend = "sqlite", auth = false)]
+#[derive(Debug)]
+struct Tag {
+    #[primary_key]
+    pub id: i32,
+    pub name: String,
+}
+
+impl CheckPermissions for Tag {
+    type AuthUser = AuthUser;
+}
+
+pub struct AuthUser {
+    admin: bool,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthUser {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let admin = req.headers().get_one("X-Auth-Role") == Some("admin");
+        Outcome::Success(AuthUser { admin })
+    }
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/notes", Note::get_routes())
+        .mount("/archive", Archive::get_routes())
+        .mount("/public_notes", PublicNote::get_routes())
+        .mount("/tags", Tag::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+fn create_note(client: &Client, title: &str) -> Value {
+    let body = json!({ "title": title });
+    request(client, Method::Post, "/notes", Some(body)).1
+}
+
+#[test]
+fn soft_delete() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let note = create_note(&client, "Groceries");
+    assert_eq!(note["deleted_at"], Value::Null);
+    let url = format!("/notes/{}", note["id"]);
+
+    let (status, deleted) = request(&client, Method::Delete, &url, None);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(deleted, json!({ "deleted": 1 }));
+
+    // the deleted item is excluded from all routes
+    let (status, _) = request(&client, Method::Get, &url, None);
+    assert_eq!(status, Status::NotFound);
+    let (_, list) = request(&client, Method::Get, "/notes", None);
+    assert_eq!(list, json!([]));
+    let (_, list) = request_with_headers(
+        &client,
+        Method::Post,
+        "/notes/search",
+        Some(json!({})),
+        &[("X-Auth-Role", "admin")],
+    );
+    assert_eq!(list, json!([]));
+    let body = json!({ "title": "Changed" });
+    let (status, _) = request(&client, Method::Patch, &url, Some(body));
+    assert_eq!(status, Status::NotFound);
+    let (status, _) = request(&client, Method::Delete, &url, None);
+    assert_eq!(status, Status::NotFound);
+
+    // but the row still exists
+    let (status, read) = request(&client, Method::Get, "/archive", None);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(read[0]["title"], "Groceries");
+    assert!(read[0]["deleted_at"].is_string());
+}
+
+#[test]
+fn soft_delete_links() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let note = create_note(&client, "Groceries");
+    let body = json!({ "name": "food" });
+    let (_, tag) = request(&client, Method::Post, "/tags", Some(body));
+    let url = format!("/notes/{}/tags", note["id"]);
+    let tag_url = format!("{}/{}", url, tag["id"]);
+    let (status, _) = request(&client, Method::Put, &tag_url, None);
+    assert_eq!(status, Status::Ok);
+    request(
+        &client,
+        Method::Delete,
+        &format!("/notes/{}", note["id"]),
+        None,
+    );
+
+    // the links of a deleted item can not be listed or changed
+    let cases = [
+        (Method::Get, &url, None),
+        (Method::Put, &url, Some(json!([tag["id"]]))),
+        (Method::Put, &tag_url, None),
+        (Method::Delete, &tag_url, None),
+    ];
+    for (method, url, body) in cases.iter() {
+        let (status, _) = request_with_headers(
+            &client,
+            *method,
+            url,
+            body.clone(),
+            &[("X-Auth-Role", "admin")],
+        );
+        assert_eq!(status, Status::NotFound, "{} {}", method, url);
+    }
+}
+
+#[test]
+fn with_deleted() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let note = create_note(&client, "Groceries");
+    create_note(&client, "Chores");
+    let url = format!("/notes/{}", note["id"]);
+    request(&client, Method::Delete, &url, None);
+
+    let url = format!("{}?with_deleted=true", url);
+    let (status, _) = request(&client, Method::Get, &url, None);
+    assert_eq!(status, Status::Forbidden);
+    let (status, read) = request_with_headers(
+        &client,
+        Method::Get,
+        &url,
+        None,
+        &[("X-Auth-Role", "admin")],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(read["title"], "Groceries");
+
+    let (status, _) = request(&client, Method::Get, "/notes?with_deleted=true", None);
+    assert_eq!(status, Status::Forbidden);
+    let (_, list) = request_with_headers(
+        &client,
+        Method::Get,
+        "/notes?with_deleted=true",
+        None,
+        &[("X-Auth-Role", "admin")],
+    );
+    assert_eq!(list.as_array().unwrap().len(), 2);
+    let (_, list) = request_with_headers(
+        &client,
+        Method::Get,
+        "/notes?with_deleted=false",
+        None,
+        &[("X-Auth-Role", "admin")],
+    );
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    let body = json!({ "with_deleted": true });
+    let (_, list) = request_with_headers(
+        &client,
+        Method::Post,
+        "/notes/search",
+        Some(body),
+        &[("X-Auth-Role", "admin")],
+    );
+    assert_eq!(list.as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn restore() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let note = create_note(&client, "Groceries");
+    let url = format!("/notes/{}", note["id"]);
+    let restore_url = format!("{}/restore", url);
+    request(&client, Method::Delete, &url, None);
+
+    let (status, _) = request(&client, Method::Post, &restore_url, None);
+    assert_eq!(status, Status::NotFound);
+    let (status, restored) = request_with_headers(
+        &client,
+        Method::Post,
+        &restore_url,
+        None,
+        &[("X-Auth-Role", "admin")],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(restored, note);
+
+    let (status, read) = request(&client, Method::Get, &url, None);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(read, note);
+    // restoring an item that is not deleted does not change it
+    let (status, restored) = request_with_headers(
+        &client,
+        Method::Post,
+        &restore_url,
+        None,
+        &[("X-Auth-Role", "admin")],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(restored, note);
+    let (status, _) = request_with_headers(
+        &client,
+        Method::Post,
+        "/notes/99/restore",
+        None,
+        &[("X-Auth-Role", "admin")],
+    );
+    assert_eq!(status, Status::NotFound);
+}
+
+#[test]
+fn bulk_soft_delete() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    create_note(&client, "Groceries");
+    create_note(&client, "Groceries");
+    create_note(&client, "Chores");
+
+    let url = "/archive?filter[title]=Groceries";
+    let (_, deleted) = request(&client, Method::Delete, url, None);
+    assert_eq!(deleted, json!({ "deleted": 2 }));
+    let (_, deleted) = request(&client, Method::Delete, url, None);
+    assert_eq!(deleted, json!({ "deleted": 0 }));
+
+    let (_, list) = request(&client, Method::Get, "/archive", None);
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    let (_, list) = request(&client, Method::Get, "/archive?with_deleted=true", None);
+    assert_eq!(list.as_array().unwrap().len(), 3);
+    assert_eq!(list[0]["archived"], true);
+
+    let restore_url = format!("/archive/{}/restore", list[0]["id"]);
+    let (_, restored) = request(&client, Method::Post, &restore_url, None);
+    assert_eq!(restored["archived"], false);
+    let history_url = format!("/archive/{}/history", list[0]["id"]);
+    let (_, history) = request(&client, Method::Get, &history_url, None);
+    assert_eq!(history[0]["action"], "delete");
+    assert_eq!(history[1]["action"], "restore");
+    assert_eq!(history[1]["before"], json!({ "archived": true }));
+    assert_eq!(history[1]["after"], json!({ "archived": false }));
+    let (_, list) = request(&client, Method::Get, "/archive", None);
+    assert_eq!(list.as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn bulk_soft_delete_without_auth() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    create_note(&client, "Groceries");
+    create_note(&client, "Groceries");
+    create_note(&client, "Chores");
+
+    let url = "/public_notes?filter[title]=Groceries";
+    let body = json!({ "archived": true });
+    let (_, updated) = request(&client, Method::Patch, url, Some(body.clone()));
+    assert_eq!(updated, json!({ "updated": 2 }));
+    let (_, deleted) = request(&client, Method::Delete, url, None);
+    assert_eq!(deleted, json!({ "deleted": 2 }));
+    let (_, deleted) = request(&client, Method::Delete, url, None);
+    assert_eq!(deleted, json!({ "deleted": 0 }));
+    // deleted rows are not changed again
+    let (_, updated) = request(&client, Method::Patch, url, Some(body));
+    assert_eq!(updated, json!({ "updated": 0 }));
+
+    let (_, list) = request(&client, Method::Get, "/public_notes", None);
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert_eq!(list[0]["title"], "Chores");
+    assert_eq!(list[0]["archived"], false);
+}
+
+#[test]
+fn soft_delete_without_auth() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let note = create_note(&client, "Groceries");
+    let url = format!("/public_notes/{}", note["id"]);
+    let (status, _) = request(&client, Method::Delete, &url, None);
+    assert_eq!(status, Status::Ok);
+
+    // a deleted item is not found when the row is not loaded before changing
+    let body = json!({ "title": "Changed" });
+    let (status, _) = request(&client, Method::Patch, &url, Some(body));
+    assert_eq!(status, Status::NotFound);
+    let body =
+        json!({ "id": note["id"], "title": "Changed", "archived": false, "deleted_at": null });
+    let (status, _) = request(&client, Method::Put, &url, Some(body));
+    assert_eq!(status, Status::NotFound);
+    let (status, _) = request(&client, Method::Delete, &url, None);
+    assert_eq!(status, Status::NotFound);
+
+    let (_, list) = request(&client, Method::Get, "/archive?with_deleted=true", None);
+    assert_eq!(list[0]["title"], "Groceries");
+}
+
+#[test]
+fn read_deleted_not_allowed_by_default() {
+    let user = AuthUser { admin: true };
+    assert!(!<Tag as CheckPermissions>::allow_read_deleted(&user));
+}
+
+#[test]
+fn soft_delete_openapi() {
+    let resource = Note::openapi();
+    let restore = &resource.paths["/{id}/restore"]["post"];
+    assert_eq!(restore["operationId"], "note_restore");
+    let read = &resource.paths["/{id}"]["get"];
+    assert_eq!(read["parameters"][1]["name"], "with_deleted");
+    assert!(read["responses"].get("403").is_some());
+    let list = resource.paths["/"]["get"]["parameters"].as_array().unwrap();
+    assert!(list.iter().any(|p| p["name"] == "with_deleted"));
+    assert!(!resource.schemas["NewNote"]["properties"]
+        .as_object()
+        .unwrap()
+        .contains_key("deleted_at"));
+}
@@ -2,18 +2,16 @@ use rocket::http::ContentType;
 use rocket::Build;
 use rocket::Rocket;
 
-use rocket_sync_db_pools::database;
+use crate::Db;
 
-#[database("diesel")]
-struct Db(diesel::PgConnection);
-
-#[rp1::crud(database = "Db", table = "users", auth = false)]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[rp1::crud(database = "Db", table = "users", backend = "sqlite", auth = false)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
 struct User {
     #[primary_key]
     pub id: i32,
     #[validate(email)]
     pub username: String,
+    #[serde(default)]
     pub role: String,
     #[generated]
     pub created_at: rp1::datetime::OffsetDateTime,
@@ -27,6 +25,7 @@ fn init_rocket() -> Rocket<Build> {
         // .mount("/posts", Post::get_routes())
         // .mount("/comments", Comment::get_routes())
         .attach(Db::fairing())
+        .attach(crate::run_migrations())
 }
 
 use rocket::http::Status;
@@ -0,0 +1,251 @@
+use rocket::http::{Method, Status};
+use rocket::local::blocking::Client;
+use rocket::Build;
+use rocket::Rocket;
+use serde_json::{json, Value};
+
+use crate::{request, request_with_etag, request_with_headers, Db};
+
+#[rp1::crud(
+    database = "Db",
+    table = "documents",
+    backend = "sqlite",
+    auth = false,
+    bulk = true
+)]
+#[derive(Debug)]
+struct Document {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    pub content: String,
+    #[version]
+    pub version: i32,
+}
+
+/// The pages of which the time of the last update is the version, which is
+/// not changed by the database.
+#[rp1::crud(database = "Db", table = "pages", backend = "sqlite", auth = false)]
+#[derive(Debug)]
+struct Page {
+    #[primary_key]
+    pub id: i32,
+    pub title: String,
+    #[version]
+    pub updated_at: rp1::datetime::OffsetDateTime,
+}
+
+fn init_rocket() -> Rocket<Build> {
+    rocket::build()
+        .mount("/documents", Document::get_routes())
+        .mount("/pages", Page::get_routes())
+        .attach(Db::fairing())
+        .attach(crate::run_migrations())
+}
+
+fn create_document(client: &Client) -> Value {
+    let body = json!({ "title": "Notes", "content": "Bla" });
+    request(client, Method::Post, "/documents/", Some(body)).1
+}
+
+#[test]
+fn etag() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let document = create_document(&client);
+    assert_eq!(document["version"], 1);
+
+    let url = format!("/documents/{}", document["id"]);
+    let (status, etag, read) = request_with_etag(&client, Method::Get, &url, None, &[]);
+    assert_eq!(status, Status::Ok);
+    assert_eq!(etag.as_deref(), Some("\"1\""));
+    assert_eq!(read, document);
+
+    // every update changes the version, also without `If-Match`
+    let (status, etag, updated) = request_with_etag(
+        &client,
+        Method::Patch,
+        &url,
+        Some(json!({ "title": "Changed" })),
+        &[],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(etag.as_deref(), Some("\"2\""));
+    assert_eq!(updated["version"], 2);
+    assert_eq!(updated["title"], "Changed");
+}
+
+#[test]
+fn if_match() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let document = create_document(&client);
+    let url = format!("/documents/{}", document["id"]);
+
+    let mut changed = document.clone();
+    changed["content"] = json!("First");
+    let (status, etag, updated) = request_with_etag(
+        &client,
+        Method::Put,
+        &url,
+        Some(changed.clone()),
+        &[("If-Match", "\"1\"")],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(etag.as_deref(), Some("\"2\""));
+    assert_eq!(updated["content"], "First");
+
+    // the second edit of the same version fails, both using `If-Match` and
+    // using the version in the body
+    changed["content"] = json!("Second");
+    for headers in &[&[("If-Match", "\"1\"")][..], &[]] {
+        let (status, _) =
+            request_with_headers(&client, Method::Put, &url, Some(changed.clone()), headers);
+        assert_eq!(status, Status::PreconditionFailed);
+    }
+    let (status, _) = request_with_headers(
+        &client,
+        Method::Patch,
+        &url,
+        Some(json!({ "content": "Second" })),
+        &[("If-Match", "\"0\", \"1\"")],
+    );
+    assert_eq!(status, Status::PreconditionFailed);
+    let (_, read) = request(&client, Method::Get, &url, None);
+    assert_eq!(read, updated);
+
+    // any of the listed tags can match, as well as `*`
+    let (status, _) = request_with_headers(
+        &client,
+        Method::Patch,
+        &url,
+        Some(json!({ "content": "Second" })),
+        &[("If-Match", "\"1\", \"2\"")],
+    );
+    assert_eq!(status, Status::Ok);
+    let (status, etag, _) = request_with_etag(
+        &client,
+        Method::Patch,
+        &url,
+        Some(json!({ "content": "Third" })),
+        &[("If-Match", "*")],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(etag.as_deref(), Some("\"4\""));
+}
+
+#[test]
+fn timestamp_version() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let body = json!({ "title": "Home" });
+    let (_, page) = request(&client, Method::Post, "/pages/", Some(body));
+    let url = format!("/pages/{}", page["id"]);
+    let (_, etag, _) = request_with_etag(&client, Method::Get, &url, None, &[]);
+    let etag = etag.unwrap();
+    assert_eq!(
+        etag,
+        format!("\"{}\"", page["updated_at"].as_str().unwrap())
+    );
+
+    // every update sets the version to the current time
+    let body = json!({ "title": "About" });
+    let (status, changed_etag, updated) = request_with_etag(
+        &client,
+        Method::Patch,
+        &url,
+        Some(body),
+        &[("If-Match", &etag)],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_ne!(changed_etag.as_deref(), Some(etag.as_str()));
+    assert_ne!(updated["updated_at"], page["updated_at"]);
+
+    let body = json!({ "title": "Contact" });
+    let (status, _) = request_with_headers(
+        &client,
+        Method::Patch,
+        &url,
+        Some(body),
+        &[("If-Match", &etag)],
+    );
+    assert_eq!(status, Status::PreconditionFailed);
+    let (status, _) =
+        request_with_headers(&client, Method::Delete, &url, None, &[("If-Match", &etag)]);
+    assert_eq!(status, Status::PreconditionFailed);
+    let (status, _) = request_with_headers(
+        &client,
+        Method::Delete,
+        &url,
+        None,
+        &[("If-Match", &changed_etag.unwrap())],
+    );
+    assert_eq!(status, Status::Ok);
+}
+
+#[test]
+fn delete_if_match() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let document = create_document(&client);
+    let url = format!("/documents/{}", document["id"]);
+
+    let (status, _) = request_with_headers(
+        &client,
+        Method::Delete,
+        &url,
+        None,
+        &[("If-Match", "\"2\"")],
+    );
+    assert_eq!(status, Status::PreconditionFailed);
+    let (status, deleted) = request_with_headers(
+        &client,
+        Method::Delete,
+        &url,
+        None,
+        &[("If-Match", "\"1\"")],
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(deleted, json!({ "deleted": 1 }));
+
+    let (status, _) = request_with_headers(
+        &client,
+        Method::Delete,
+        &url,
+        None,
+        &[("If-Match", "\"1\"")],
+    );
+    assert_eq!(status, Status::NotFound);
+    let (status, _) = request(&client, Method::Delete, &url, None);
+    assert_eq!(status, Status::NotFound);
+}
+
+#[test]
+fn bulk_version() {
+    let client = Client::tracked(init_rocket()).expect("valid rocket instance");
+    let document = create_document(&client);
+
+    let (_, updated) = request(
+        &client,
+        Method::Patch,
+        "/documents?filter[title]=Notes",
+        Some(json!({ "content": "Changed" })),
+    );
+    assert_eq!(updated, json!({ "updated": 1 }));
+    let url = format!("/documents/{}", document["id"]);
+    let (_, etag, _) = request_with_etag(&client, Method::Get, &url, None, &[]);
+    assert_eq!(etag.as_deref(), Some("\"2\""));
+}
+
+#[test]
+fn version_openapi() {
+    let resource = Document::openapi();
+    let item = &resource.paths["/{id}"];
+    assert!(item["get"]["responses"]["200"]["headers"]
+        .get("ETag")
+        .is_some());
+    for method in &["put", "patch", "delete"] {
+        assert_eq!(item[method]["parameters"][1]["name"], "If-Match");
+        assert!(item[method]["responses"].get("412").is_some());
+    }
+    assert!(!resource.schemas["NewDocument"]["properties"]
+        .as_object()
+        .unwrap()
+        .contains_key("version"));
+}